@@ -0,0 +1,301 @@
+//!Codec throughput benchmarks.
+//!
+//!Throughput is reported as realtime factor, i.e. how many seconds of audio are processed per second.
+//!
+//!To compare build features, save baseline and then run against it:
+//!
+//!```text
+//!cargo bench --bench codec -- --save-baseline default
+//!cargo bench --bench codec --features no-simd -- --baseline default
+//!cargo bench --bench codec --features fixed-point -- --baseline default
+//!```
+
+use std::hint::black_box;
+use std::mem::MaybeUninit;
+use std::time::{Duration, Instant};
+
+use criterion::measurement::{Measurement, ValueFormatter};
+use criterion::{criterion_group, criterion_main, BenchmarkGroup, Criterion, Throughput};
+
+use opusic_c::{multistream, repacketizer, utils};
+use opusic_c::{Encoder, Decoder, SampleRate, Channels, Application, Bitrate, FrameDuration, InbandFec};
+
+const RATE: SampleRate = SampleRate::Hz48000;
+//Number of samples per channel in 20ms
+const FRAME_20MS: usize = RATE as usize / 50;
+//Maximum packet size for up to 120ms of single stream
+const MAX_PACKET: usize = 1275 * 6 + 7;
+//Amount of audio processed in each iteration
+const DURATION_MS: usize = 120;
+
+///Wall time measurement that reports throughput as realtime factor
+///
+///Expects throughput to be specified as `Throughput::Elements` with duration of processed audio in microseconds.
+struct Realtime;
+
+struct RealtimeFormatter;
+
+impl ValueFormatter for RealtimeFormatter {
+    fn scale_values(&self, ns: f64, values: &mut [f64]) -> &'static str {
+        let (factor, unit) = if ns < 1e3 {
+            (1.0, "ns")
+        } else if ns < 1e6 {
+            (1e-3, "µs")
+        } else if ns < 1e9 {
+            (1e-6, "ms")
+        } else {
+            (1e-9, "s")
+        };
+
+        for value in values {
+            *value *= factor;
+        }
+
+        unit
+    }
+
+    fn scale_throughputs(&self, _typical: f64, throughput: &Throughput, values: &mut [f64]) -> &'static str {
+        let audio_us = match throughput {
+            Throughput::Elements(audio_us) => *audio_us as f64,
+            Throughput::ElementsAndBytes { elements, .. } => *elements as f64,
+            _ => unreachable!("Realtime measurement expects audio duration in elements"),
+        };
+
+        for value in values {
+            *value = audio_us * 1e3 / *value;
+        }
+
+        "x realtime"
+    }
+
+    fn scale_for_machines(&self, _values: &mut [f64]) -> &'static str {
+        "ns"
+    }
+}
+
+impl Measurement for Realtime {
+    type Intermediate = Instant;
+    type Value = Duration;
+
+    fn start(&self) -> Self::Intermediate {
+        Instant::now()
+    }
+
+    fn end(&self, start: Self::Intermediate) -> Self::Value {
+        start.elapsed()
+    }
+
+    fn add(&self, left: &Self::Value, right: &Self::Value) -> Self::Value {
+        *left + *right
+    }
+
+    fn zero(&self) -> Self::Value {
+        Duration::ZERO
+    }
+
+    fn to_f64(&self, value: &Self::Value) -> f64 {
+        value.as_nanos() as f64
+    }
+
+    fn formatter(&self) -> &dyn ValueFormatter {
+        &RealtimeFormatter
+    }
+}
+
+type Group<'a> = BenchmarkGroup<'a, Realtime>;
+
+fn new_group<'a>(c: &'a mut Criterion<Realtime>, name: &str) -> Group<'a> {
+    let mut group = c.benchmark_group(name);
+    group.throughput(Throughput::Elements(DURATION_MS as u64 * 1000));
+    group
+}
+
+//Mix of tones and noise to keep both SILK and CELT busy
+fn signal(channels: usize) -> Vec<u16> {
+    let len = RATE as usize / 1000 * DURATION_MS * channels;
+    let mut seed = 0x1234_5678u32;
+    (0..len).map(|idx| {
+        seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+        let noise = (seed >> 16) as i16 as f32 / 16.0;
+        let time = (idx / channels) as f32 / RATE as usize as f32;
+        let tone = (time * 440.0 * std::f32::consts::TAU).sin() * 6000.0 + (time * (1000.0 + 300.0 * (idx % channels) as f32) * std::f32::consts::TAU).sin() * 3000.0;
+        (tone + noise) as i16 as u16
+    }).collect()
+}
+
+fn encode_all(encoder: &mut Encoder, input: &[u16], frame_size: usize) -> Vec<Vec<u8>> {
+    let mut packet = vec![0u8; MAX_PACKET];
+    input.chunks_exact(frame_size * encoder.channels() as usize).map(|frame| {
+        let len = encoder.encode_to_slice(frame, &mut packet).expect("encode");
+        packet[..len].to_vec()
+    }).collect()
+}
+
+fn bench_encoder(group: &mut Group<'_>, id: String, mut encoder: Encoder, frame_size: usize) {
+    let input = signal(encoder.channels() as usize);
+    let mut packet = vec![0u8; MAX_PACKET];
+    let frame_len = frame_size * encoder.channels() as usize;
+    group.bench_function(id, |bencher| bencher.iter(|| {
+        for frame in input.chunks_exact(frame_len) {
+            black_box(encoder.encode_to_slice(black_box(frame), &mut packet).expect("encode"));
+        }
+    }));
+}
+
+fn encoder(c: &mut Criterion<Realtime>) {
+    let mut group = new_group(c, "encoder/complexity");
+    for complexity in 0..=10 {
+        let mut encoder = Encoder::new(Channels::Stereo, RATE, Application::Audio).expect("create encoder");
+        encoder.set_complexity(complexity).expect("set complexity");
+        bench_encoder(&mut group, complexity.to_string(), encoder, FRAME_20MS);
+    }
+    group.finish();
+
+    let durations = [
+        (FrameDuration::Size2_5, "2.5ms", FRAME_20MS / 8),
+        (FrameDuration::Size5, "5ms", FRAME_20MS / 4),
+        (FrameDuration::Size10, "10ms", FRAME_20MS / 2),
+        (FrameDuration::Size20, "20ms", FRAME_20MS),
+        (FrameDuration::Size40, "40ms", FRAME_20MS * 2),
+        (FrameDuration::Size60, "60ms", FRAME_20MS * 3),
+        (FrameDuration::Size120, "120ms", FRAME_20MS * 6),
+    ];
+    let mut group = new_group(c, "encoder/frame_duration");
+    for (duration, name, frame_size) in durations {
+        let mut encoder = Encoder::new(Channels::Stereo, RATE, Application::Audio).expect("create encoder");
+        encoder.set_frame_duration(duration).expect("set frame duration");
+        bench_encoder(&mut group, name.to_owned(), encoder, frame_size);
+    }
+    group.finish();
+
+    let applications = [
+        (Application::Voip, "voip", Channels::Mono, 24000),
+        (Application::Audio, "audio", Channels::Stereo, 96000),
+        (Application::LowDelay, "low_delay", Channels::Stereo, 96000),
+    ];
+    let mut group = new_group(c, "encoder/application");
+    for (application, name, channels, bitrate) in applications {
+        let mut encoder = Encoder::new(channels, RATE, application).expect("create encoder");
+        encoder.set_bitrate(Bitrate::Value(bitrate)).expect("set bitrate");
+        bench_encoder(&mut group, name.to_owned(), encoder, FRAME_20MS);
+    }
+    group.finish();
+}
+
+fn decoder(c: &mut Criterion<Realtime>) {
+    let mut group = new_group(c, "decoder");
+
+    let configs = [
+        ("audio", Channels::Stereo, Application::Audio, 96000),
+        ("voip", Channels::Mono, Application::Voip, 24000),
+    ];
+    for (name, channels, application, bitrate) in configs {
+        let mut encoder = Encoder::new(channels, RATE, application).expect("create encoder");
+        encoder.set_bitrate(Bitrate::Value(bitrate)).expect("set bitrate");
+        encoder.set_inband_fec(InbandFec::Mode1).expect("set inband fec");
+        encoder.set_packet_loss(20).expect("set packet loss");
+        let packets = encode_all(&mut encoder, &signal(channels as usize), FRAME_20MS);
+
+        let mut decoder = Decoder::new(channels, RATE).expect("create decoder");
+        let mut output = vec![0u16; FRAME_20MS * channels as usize];
+        group.bench_function(name, |bencher| bencher.iter(|| {
+            for packet in packets.iter() {
+                black_box(decoder.decode_to_slice(black_box(packet), &mut output, false).expect("decode"));
+            }
+        }));
+
+        //Recover every frame from the next packet's redundancy
+        group.bench_function(format!("{name}_fec"), |bencher| bencher.iter(|| {
+            for packet in packets.iter() {
+                black_box(decoder.decode_to_slice(black_box(packet), &mut output, true).expect("decode fec"));
+            }
+        }));
+    }
+
+    group.finish();
+}
+
+fn bench_multistream<const CH: usize>(group: &mut Group<'_>, name: &str) {
+    let config = || multistream::Config::<CH>::vorbis().expect("vorbis config");
+    let mut encoder = multistream::Encoder::new(config(), RATE, Application::Audio).expect("create encoder");
+    let mut decoder = multistream::Decoder::new(config(), RATE).expect("create decoder");
+
+    let input = signal(CH);
+    let mut packet = vec![0u8; MAX_PACKET * CH];
+    group.bench_function(format!("{name}/encode"), |bencher| bencher.iter(|| {
+        for frame in input.chunks_exact(FRAME_20MS * CH) {
+            black_box(encoder.encode_to_slice(black_box(frame), &mut packet).expect("encode"));
+        }
+    }));
+
+    let packets: Vec<Vec<u8>> = input.chunks_exact(FRAME_20MS * CH).map(|frame| {
+        let len = encoder.encode_to_slice(frame, &mut packet).expect("encode");
+        packet[..len].to_vec()
+    }).collect();
+    let mut output = vec![0u16; FRAME_20MS * CH];
+    group.bench_function(format!("{name}/decode"), |bencher| bencher.iter(|| {
+        for packet in packets.iter() {
+            black_box(decoder.decode_to_slice(black_box(packet), &mut output, false).expect("decode"));
+        }
+    }));
+}
+
+fn multistream(c: &mut Criterion<Realtime>) {
+    let mut group = new_group(c, "multistream");
+    bench_multistream::<6>(&mut group, "5.1");
+    bench_multistream::<8>(&mut group, "7.1");
+    group.finish();
+}
+
+fn soft_clip(c: &mut Criterion<Realtime>) {
+    let mut group = new_group(c, "soft_clip");
+    //Overdriven signal, so that clipping actually happens
+    let input: Vec<f32> = signal(2).into_iter().map(|sample| sample as i16 as f32 / 8192.0).collect();
+    let mut output = input.clone();
+    group.bench_function("stereo", |bencher| bencher.iter(|| {
+        output.copy_from_slice(&input);
+        for frame in output.chunks_exact_mut(FRAME_20MS * 2) {
+            utils::soft_clip(black_box(frame), Channels::Stereo);
+        }
+    }));
+    group.finish();
+}
+
+fn repacketizer(c: &mut Criterion<Realtime>) {
+    let mut group = new_group(c, "repacketizer");
+
+    let mut encoder = Encoder::new(Channels::Stereo, RATE, Application::Audio).expect("create encoder");
+    let packets = encode_all(&mut encoder, &signal(2), FRAME_20MS);
+    let mut repacketizer = repacketizer::Repacketizer::new().expect("create repacketizer");
+    let mut out = vec![MaybeUninit::<u8>::uninit(); MAX_PACKET * 3];
+
+    //Combine 20ms packets into 60ms packets
+    group.bench_function("combine", |bencher| bencher.iter(|| {
+        for chunk in packets.chunks_exact(3) {
+            let chunk = [chunk[0].as_slice(), chunk[1].as_slice(), chunk[2].as_slice()];
+            black_box(repacketizer.combine_all(black_box(&chunk), &mut out).expect("combine"));
+        }
+    }));
+
+    group.bench_function("pad_unpad", |bencher| bencher.iter(|| {
+        for packet in packets.iter() {
+            let padded_len = packet.len() + 100;
+            let mut packet = packet.clone();
+            repacketizer::pad_packet_vec(&mut packet, padded_len).expect("pad");
+            black_box(repacketizer::unpad_packet(black_box(&mut packet)).expect("unpad"));
+        }
+    }));
+
+    group.finish();
+}
+
+fn config() -> Criterion<Realtime> {
+    Criterion::default().with_measurement(Realtime)
+}
+
+criterion_group! {
+    name = benches;
+    config = config();
+    targets = encoder, decoder, multistream, soft_clip, repacketizer
+}
+criterion_main!(benches);
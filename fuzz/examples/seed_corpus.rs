@@ -0,0 +1,99 @@
+//!Seeds fuzzing corpus with encoder output.
+//!
+//!Run from `fuzz` directory: `cargo run --example seed_corpus`
+
+use std::fs;
+use std::path::Path;
+
+use opusic_c::{repacketizer, Encoder, SampleRate, Channels, Application, Bitrate, FrameDuration, InbandFec};
+
+const RATES: [SampleRate; 5] = [SampleRate::Hz8000, SampleRate::Hz12000, SampleRate::Hz16000, SampleRate::Hz24000, SampleRate::Hz48000];
+
+fn write(target: &str, name: &str, data: &[u8]) {
+    let dir = Path::new("corpus").join(target);
+    fs::create_dir_all(&dir).expect("create corpus directory");
+    fs::write(dir.join(name), data).expect("write corpus entry");
+}
+
+fn encode(channels: Channels, application: Application, bitrate: u32, duration: FrameDuration, frame_size: usize) -> Vec<Vec<u8>> {
+    let mut encoder = Encoder::new(channels, SampleRate::Hz48000, application).expect("create encoder");
+    encoder.set_bitrate(Bitrate::Value(bitrate)).expect("set bitrate");
+    encoder.set_frame_duration(duration).expect("set frame duration");
+    encoder.set_packet_loss(10).expect("set packet loss");
+    encoder.set_inband_fec(InbandFec::Mode1).expect("set inband fec");
+
+    let input: Vec<u16> = (0..2880 * channels as usize * 4).map(|idx| ((idx as f32 / 23.0).sin() * 9000.0 + (idx as f32 / 7.0).cos() * 3000.0) as i16 as u16).collect();
+
+    let mut packets = Vec::new();
+    let mut packet = vec![0u8; 1275 * 3 + 7];
+    for frame in input.chunks_exact(frame_size * channels as usize) {
+        let len = encoder.encode_to_slice(frame, &mut packet).expect("encode");
+        packets.push(packet[..len].to_vec());
+    }
+    packets
+}
+
+fn main() {
+    let settings = [
+        (Channels::Mono, Application::Voip, 12000, FrameDuration::Size20, 960),
+        (Channels::Mono, Application::Voip, 24000, FrameDuration::Size60, 2880),
+        (Channels::Stereo, Application::Audio, 64000, FrameDuration::Size20, 960),
+        (Channels::Stereo, Application::Audio, 128000, FrameDuration::Size10, 480),
+        (Channels::Mono, Application::LowDelay, 48000, FrameDuration::Size2_5, 120),
+        (Channels::Stereo, Application::Audio, 96000, FrameDuration::Size60, 2880),
+    ];
+
+    let mut count = 0;
+    for (idx, (channels, application, bitrate, duration, frame_size)) in settings.iter().enumerate() {
+        let packets = encode(*channels, *application, *bitrate, *duration, *frame_size);
+        for (packet_idx, packet) in packets.iter().enumerate() {
+            let name = format!("{idx}_{packet_idx}");
+
+            for (rate_idx, _) in RATES.iter().enumerate() {
+                let mut data = vec![rate_idx as u8 | ((*channels as u8 - 1) << 3)];
+                data.extend_from_slice(packet);
+                write("decode", &format!("{name}_{rate_idx}"), &data);
+            }
+
+            //Single stream, mapping matching encoder's channels
+            let mut data = vec![*channels as u8 - 1, 1, *channels as u8 - 1];
+            data.extend((0..*channels as u8).collect::<Vec<_>>());
+            data.extend_from_slice(packet);
+            write("multistream_decode", &name, &data);
+
+            let mut data = vec![0x40];
+            data.extend_from_slice(packet);
+            write("packet", &name, &data);
+
+            let mut padded = packet.clone();
+            repacketizer::pad_packet_vec(&mut padded, packet.len() + 64).expect("pad packet");
+            let mut data = vec![0];
+            data.extend_from_slice(&padded);
+            write("packet", &format!("{name}_padded"), &data);
+
+            #[cfg(feature = "dred")]
+            write("dred_decode", &name, &data);
+
+            count += 1;
+        }
+
+        //Sequence of length-prefixed packets
+        let mut data = vec![0xf0];
+        for packet in packets.iter().take(4).filter(|packet| packet.len() <= u8::MAX as usize) {
+            data.push(packet.len() as u8);
+            data.extend_from_slice(packet);
+        }
+        write("repacketizer", &idx.to_string(), &data);
+    }
+
+    for (rate_idx, rate) in RATES.iter().enumerate() {
+        for channels in [Channels::Mono, Channels::Stereo] {
+            let mut data = vec![rate_idx as u8 | ((channels as u8 - 1) << 3) | 0x10, 32, 3];
+            let len = *rate as usize / 50 * channels as usize * 4;
+            data.extend((0..len).flat_map(|idx| (((idx as f32 / 11.0).sin() * 12000.0) as i16).to_le_bytes()));
+            write("encode_decode", &format!("{rate_idx}_{}", channels as u8), &data);
+        }
+    }
+
+    println!("Written corpus for {count} packets");
+}
@@ -0,0 +1,39 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use opusic_c::{Decoder, SampleRate, Channels};
+
+const RATES: [SampleRate; 5] = [SampleRate::Hz8000, SampleRate::Hz12000, SampleRate::Hz16000, SampleRate::Hz24000, SampleRate::Hz48000];
+//120ms at 48kHz stereo
+const MAX_OUTPUT: usize = 5760 * 2;
+
+fuzz_target!(|data: &[u8]| {
+    let (config, input) = match data.split_first() {
+        Some((config, input)) => (*config, input),
+        None => return,
+    };
+
+    let rate = RATES[(config & 0x7) as usize % RATES.len()];
+    let channels = if config & 0x8 == 0 { Channels::Mono } else { Channels::Stereo };
+    let decode_fec = config & 0x10 != 0;
+    //Exercise undersized output buffers too
+    let output_len = if config & 0x20 == 0 { MAX_OUTPUT } else { MAX_OUTPUT / 8 };
+
+    let mut decoder = Decoder::new(channels, rate).expect("create decoder");
+    let _ = decoder.get_nb_samples(input);
+
+    if config & 0x40 == 0 {
+        let mut output = vec![0u16; output_len];
+        if let Ok(len) = decoder.decode_to_slice(input, &mut output, decode_fec) {
+            assert!(len * channels as usize <= output.len());
+        }
+    } else {
+        let mut output = vec![0f32; output_len];
+        if let Ok(len) = decoder.decode_float_to_slice(input, &mut output, decode_fec) {
+            assert!(len * channels as usize <= output.len());
+        }
+    }
+
+    let _ = decoder.get_last_packet_duration();
+    let _ = decoder.get_final_range();
+});
@@ -0,0 +1,27 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use opusic_c::dred::Dred;
+use opusic_c::{Decoder, SampleRate, Channels};
+
+//20ms at 48kHz stereo
+const MAX_OUTPUT: usize = 960 * 2;
+
+fuzz_target!(|data: &[u8]| {
+    let (config, input) = match data.split_first() {
+        Some((config, input)) => (*config, input),
+        None => return,
+    };
+
+    let channels = if config & 0x1 == 0 { Channels::Mono } else { Channels::Stereo };
+    let decoder = Decoder::new(channels, SampleRate::Hz48000).expect("create decoder");
+    let mut dred = Dred::new(decoder).expect("create dred decoder");
+
+    if config & 0x2 == 0 {
+        let mut output = vec![0u16; MAX_OUTPUT];
+        let _ = dred.decode_to_slice(input, &mut output);
+    } else {
+        let mut output = vec![0f32; MAX_OUTPUT];
+        let _ = dred.decode_float_to_slice(input, &mut output);
+    }
+});
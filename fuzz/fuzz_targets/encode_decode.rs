@@ -0,0 +1,44 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use opusic_c::{Encoder, Decoder, SampleRate, Channels, Application, Bitrate};
+
+const RATES: [SampleRate; 5] = [SampleRate::Hz8000, SampleRate::Hz12000, SampleRate::Hz16000, SampleRate::Hz24000, SampleRate::Hz48000];
+const APPLICATIONS: [Application; 3] = [Application::Voip, Application::Audio, Application::LowDelay];
+//Frame durations in units of 2.5ms
+const DURATIONS: [usize; 6] = [1, 2, 4, 8, 16, 24];
+const MAX_PACKET: usize = 1275 * 3 + 7;
+
+fuzz_target!(|data: &[u8]| {
+    if data.len() < 3 {
+        return;
+    }
+
+    //Layout: config, bitrate/complexity, duration, PCM
+    let (config, data) = data.split_at(3);
+    let rate = RATES[(config[0] & 0x7) as usize % RATES.len()];
+    let channels = if config[0] & 0x8 == 0 { Channels::Mono } else { Channels::Stereo };
+    let application = APPLICATIONS[(config[0] >> 4) as usize % APPLICATIONS.len()];
+    let bitrate = 6000 + (config[1] as u32) * 2000;
+    let complexity = config[1] % 11;
+    let frame_size = DURATIONS[config[2] as usize % DURATIONS.len()] * rate as usize / 400;
+
+    let mut encoder = Encoder::new(channels, rate, application).expect("create encoder");
+    encoder.set_bitrate(Bitrate::Value(bitrate)).expect("set bitrate");
+    encoder.set_complexity(complexity).expect("set complexity");
+    let mut decoder = Decoder::new(channels, rate).expect("create decoder");
+
+    let frame_len = frame_size * channels as usize;
+    let pcm: Vec<u16> = data.chunks_exact(2).map(|sample| u16::from_le_bytes([sample[0], sample[1]])).collect();
+    let mut packet = vec![0u8; MAX_PACKET];
+    let mut output = vec![0u16; frame_len];
+    for frame in pcm.chunks_exact(frame_len) {
+        let len = encoder.encode_to_slice(frame, &mut packet).expect("encode");
+        assert!(len <= packet.len());
+        let encoder_range = encoder.get_final_range().expect("encoder final range");
+
+        let decoded = decoder.decode_to_slice(&packet[..len], &mut output, false).expect("decode encoder output");
+        assert_eq!(decoded, frame_size);
+        assert_eq!(decoder.get_final_range().expect("decoder final range"), encoder_range);
+    }
+});
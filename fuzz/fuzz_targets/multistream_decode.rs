@@ -0,0 +1,63 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use opusic_c::multistream::{Config, Decoder};
+use opusic_c::SampleRate;
+
+//120ms at 48kHz
+const MAX_FRAME: usize = 5760;
+
+fn decode<const CH: usize>(config: &[u8], input: &[u8], float: bool) {
+    if config.len() < 2 + CH {
+        return;
+    }
+
+    let mut mapping = [0u8; CH];
+    mapping.copy_from_slice(&config[2..2 + CH]);
+    let config = match Config::try_new(config[0], config[1], mapping) {
+        Some(config) => config,
+        None => return,
+    };
+
+    let mut decoder = match Decoder::new(config, SampleRate::Hz48000) {
+        Ok(decoder) => decoder,
+        Err(_) => return,
+    };
+
+    if float {
+        let mut output = vec![0f32; MAX_FRAME * CH];
+        if let Ok(len) = decoder.decode_float_to_slice(input, &mut output, false) {
+            assert!(len * CH <= output.len());
+        }
+    } else {
+        let mut output = vec![0u16; MAX_FRAME * CH];
+        if let Ok(len) = decoder.decode_to_slice(input, &mut output, false) {
+            assert!(len * CH <= output.len());
+        }
+    }
+
+    let _ = decoder.get_last_packet_duration();
+    let _ = decoder.get_final_range();
+}
+
+fuzz_target!(|data: &[u8]| {
+    let (selector, data) = match data.split_first() {
+        Some((selector, data)) => (*selector, data),
+        None => return,
+    };
+
+    //Layout: streams, coupled streams, mapping[CH], packet
+    let channels = (selector & 0x7) as usize + 1;
+    let float = selector & 0x8 != 0;
+    let (config, input) = data.split_at(data.len().min(2 + channels));
+    match channels {
+        1 => decode::<1>(config, input, float),
+        2 => decode::<2>(config, input, float),
+        3 => decode::<3>(config, input, float),
+        4 => decode::<4>(config, input, float),
+        5 => decode::<5>(config, input, float),
+        6 => decode::<6>(config, input, float),
+        7 => decode::<7>(config, input, float),
+        _ => decode::<8>(config, input, float),
+    }
+});
@@ -0,0 +1,45 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use opusic_c::{repacketizer, utils, SampleRate};
+
+fuzz_target!(|data: &[u8]| {
+    let (pad, input) = match data.split_first() {
+        Some((pad, input)) => (*pad as usize, input),
+        None => return,
+    };
+
+    if let Ok(toc) = utils::Toc::parse(input) {
+        let _ = (toc.config(), toc.mode(), toc.bandwidth(), toc.frame_size(), toc.channels(), toc.code());
+        assert_eq!(toc.bandwidth(), utils::get_bandwidth(input).expect("get bandwidth"));
+        assert_eq!(toc.channels(), utils::get_nb_channels(input).expect("get channels"));
+    }
+
+    let _ = utils::get_nb_frames(input);
+    let _ = utils::get_nb_samples(input, SampleRate::Hz48000);
+    let _ = utils::has_lbrr(input);
+    let _ = utils::has_dred(input);
+    if let Ok(padding) = utils::get_padding(input) {
+        assert!(padding.len() <= input.len());
+    }
+
+    //Padding to arbitrary size must never write outside of buffer
+    let mut packet = input.to_vec();
+    let _ = repacketizer::pad_packet(&mut packet, input.len() + pad);
+    assert_eq!(packet.len(), input.len());
+
+    let mut packet = input.to_vec();
+    //libopus doesn't validate packet when its size is unchanged
+    if repacketizer::pad_packet_vec(&mut packet, input.len() + pad).is_ok() && pad > 0 {
+        assert_eq!(packet.len(), input.len() + pad);
+        assert_eq!(utils::get_nb_frames(&packet).ok(), utils::get_nb_frames(input).ok());
+
+        let len = repacketizer::unpad_packet(&mut packet).expect("unpad padded packet");
+        assert!(len <= packet.len());
+    }
+
+    let mut packet = input.to_vec();
+    if let Ok(len) = repacketizer::unpad_packet(&mut packet) {
+        assert!(len <= input.len());
+    }
+});
@@ -0,0 +1,57 @@
+#![no_main]
+
+use core::mem::MaybeUninit;
+
+use libfuzzer_sys::fuzz_target;
+use opusic_c::repacketizer::Repacketizer;
+use opusic_c::utils;
+
+//Upper bound of 120ms worth of frames, 1275 bytes each, plus framing overhead
+const MAX_OUTPUT: usize = 1277 * 48;
+
+fuzz_target!(|data: &[u8]| {
+    let (selector, data) = match data.split_first() {
+        Some((selector, data)) => (*selector, data),
+        None => return,
+    };
+
+    //Split input into packets of varying size prefixed by single length byte
+    let mut packets = Vec::new();
+    let mut cursor = data;
+    while let Some((len, rest)) = cursor.split_first() {
+        let len = (*len as usize).min(rest.len());
+        let (packet, rest) = rest.split_at(len);
+        packets.push(packet);
+        cursor = rest;
+    }
+
+    let mut repacketizer = Repacketizer::new().expect("create repacketizer");
+    let mut state = repacketizer.start();
+    for packet in packets.iter() {
+        let _ = state.add_packet(packet);
+    }
+
+    let nb_frames = state.get_nb_frames();
+    let mut out = vec![MaybeUninit::<u8>::uninit(); MAX_OUTPUT];
+    let out_len = if selector & 0x80 == 0 { out.len() } else { (selector & 0x7f) as usize };
+
+    if let Ok(len) = state.create_full_packet(&mut out[..out_len]) {
+        assert!(len <= out_len);
+        let packet: Vec<u8> = out[..len].iter().map(|byte| unsafe { byte.assume_init() }).collect();
+        assert_eq!(utils::get_nb_frames(&packet).expect("parse repacketized"), nb_frames as usize);
+    }
+
+    //Arbitrary, possibly invalid ranges
+    let begin = (selector & 0xf) as u32;
+    let end = (selector >> 4) as u32;
+    if let Ok(len) = state.create_packet((begin, end), &mut out[..out_len]) {
+        assert!(len <= out_len);
+        assert!(begin < end && end <= nb_frames);
+    }
+
+    let mut out = vec![MaybeUninit::<u8>::uninit(); MAX_OUTPUT];
+    drop(state);
+    if let Ok(len) = repacketizer.combine_all(&packets, &mut out) {
+        assert!(len <= out.len());
+    }
+});
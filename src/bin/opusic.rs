@@ -0,0 +1,553 @@
+//! Command line tool to encode, decode and inspect Opus streams
+
+use opusic_c::{demo_format, ogg, resample, utils, wav};
+use opusic_c::header::{OpusHead, OpusTags};
+use opusic_c::{Encoder, Decoder, ErrorCode, SampleRate, Channels, Application, Bitrate, Bandwidth, Signal, InbandFec, FrameDuration};
+
+use std::fs;
+use std::io::Write;
+use std::process::ExitCode;
+
+const USAGE: &str = "Usage: opusic <command> [options] <input> [output]
+
+Commands:
+  encode [options] <input.wav> <output>   Encodes WAV file into Ogg Opus or opus_demo bitstream
+  decode [options] <input> <output.wav>   Decodes Ogg Opus or opus_demo bitstream into WAV file
+  inspect <input>                         Dumps information about every packet
+
+Encode options:
+  --format <ogg|raw>                 Output format. Defaults to raw for .bit files and ogg otherwise
+  --application <voip|audio|lowdelay>
+                                     Intended application. Default: audio
+  --bitrate <bps|auto|max>           Bitrate in bits per second
+  --vbr <on|off>                     Variable bitrate
+  --vbr-constraint <on|off>          Constrained variable bitrate
+  --complexity <0-10>                Computational complexity
+  --signal <auto|voice|music>        Type of signal being encoded
+  --bandwidth <auto|nb|mb|wb|swb|fb> Bandpass
+  --max-bandwidth <nb|mb|wb|swb|fb>  Maximum bandpass
+  --force-channels <auto|mono|stereo>
+                                     Forced number of channels
+  --inband-fec <off|1|2>             In-band forward error correction mode
+  --packet-loss <0-100>              Expected packet loss percentage
+  --prediction-disabled <on|off>     Disables inter-frame prediction
+  --lsb-depth <8-24>                 Depth of signal being encoded
+  --framesize <2.5|5|10|20|40|60|80|100|120>
+                                     Frame duration in milliseconds. Default: 20
+  --dtx <on|off>                     Discontinuous transmission
+  --phase-inversion-disabled <on|off>
+                                     Disables phase inversion for intensity stereo
+  --dred-duration <0-104>            DRED duration in 10ms units (requires `dred` feature)
+  --serial <number>                  Ogg stream serial number
+  --comment <NAME=value>             Adds comment to Ogg stream. Can be repeated
+
+Decode options:
+  --rate <8000|12000|16000|24000|48000>
+                                     Output sample rate. Default: 48000
+  --channels <1|2>                   Output channels. Defaults to stream's channels for Ogg and 2 for raw
+  --float                            Writes 32-bit float samples instead of 16-bit";
+
+//120ms at 48kHz
+const MAX_FRAME_SIZE: usize = 5760;
+
+type Result<T> = core::result::Result<T, String>;
+
+fn opus_error(context: &'static str) -> impl FnOnce(ErrorCode) -> String {
+    move |error| format!("{context}: {}", error.message())
+}
+
+fn output_error(error: std::io::Error) -> String {
+    format!("Unable to write output: {error}")
+}
+
+fn next_value(args: &mut impl Iterator<Item = String>, name: &str) -> Result<String> {
+    args.next().ok_or_else(|| format!("{name} requires value"))
+}
+
+fn parse_number<T: core::str::FromStr>(value: &str, name: &str) -> Result<T> {
+    value.parse().map_err(|_| format!("{name}: invalid value '{value}'"))
+}
+
+fn parse_switch(value: &str, name: &str) -> Result<bool> {
+    match value {
+        "on" | "true" | "1" => Ok(true),
+        "off" | "false" | "0" => Ok(false),
+        _ => Err(format!("{name}: expected on or off, got '{value}'")),
+    }
+}
+
+fn parse_bandwidth(value: &str, name: &str) -> Result<Bandwidth> {
+    match value {
+        "auto" => Ok(Bandwidth::Auto),
+        "nb" => Ok(Bandwidth::Narrow),
+        "mb" => Ok(Bandwidth::Medium),
+        "wb" => Ok(Bandwidth::Wide),
+        "swb" => Ok(Bandwidth::Superwide),
+        "fb" => Ok(Bandwidth::Full),
+        _ => Err(format!("{name}: invalid bandwidth '{value}'")),
+    }
+}
+
+//Returns frame duration and corresponding number of samples at 48kHz
+fn parse_frame_duration(value: &str) -> Result<(FrameDuration, usize)> {
+    match value {
+        "2.5" => Ok((FrameDuration::Size2_5, 120)),
+        "5" => Ok((FrameDuration::Size5, 240)),
+        "10" => Ok((FrameDuration::Size10, 480)),
+        "20" => Ok((FrameDuration::Size20, 960)),
+        "40" => Ok((FrameDuration::Size40, 1920)),
+        "60" => Ok((FrameDuration::Size60, 2880)),
+        "80" => Ok((FrameDuration::Size80, 3840)),
+        "100" => Ok((FrameDuration::Size100, 4800)),
+        "120" => Ok((FrameDuration::Size120, 5760)),
+        _ => Err(format!("--framesize: invalid duration '{value}'")),
+    }
+}
+
+fn sample_rate(value: u32) -> Option<SampleRate> {
+    match value {
+        8000 => Some(SampleRate::Hz8000),
+        12000 => Some(SampleRate::Hz12000),
+        16000 => Some(SampleRate::Hz16000),
+        24000 => Some(SampleRate::Hz24000),
+        48000 => Some(SampleRate::Hz48000),
+        _ => None,
+    }
+}
+
+fn channels(value: u16) -> Result<Channels> {
+    match value {
+        1 => Ok(Channels::Mono),
+        2 => Ok(Channels::Stereo),
+        _ => Err(format!("Unsupported number of channels {value}: only mono and stereo are supported")),
+    }
+}
+
+#[derive(Default)]
+struct EncodeOptions {
+    raw: Option<bool>,
+    application: Option<Application>,
+    bitrate: Option<Bitrate>,
+    vbr: Option<bool>,
+    vbr_constraint: Option<bool>,
+    complexity: Option<u8>,
+    signal: Option<Signal>,
+    bandwidth: Option<Bandwidth>,
+    max_bandwidth: Option<Bandwidth>,
+    force_channels: Option<Option<Channels>>,
+    inband_fec: Option<InbandFec>,
+    packet_loss: Option<u8>,
+    prediction_disabled: Option<bool>,
+    lsb_depth: Option<u8>,
+    frame_duration: Option<(FrameDuration, usize)>,
+    dtx: Option<bool>,
+    phase_inversion_disabled: Option<bool>,
+    #[cfg(feature = "dred")]
+    dred_duration: Option<u8>,
+    serial: Option<u32>,
+    comments: Vec<String>,
+    files: Vec<String>,
+}
+
+impl EncodeOptions {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self> {
+        let mut result = Self::default();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--format" => result.raw = Some(match next_value(&mut args, &arg)?.as_str() {
+                    "ogg" => false,
+                    "raw" => true,
+                    value => return Err(format!("{arg}: invalid format '{value}'")),
+                }),
+                "--application" => result.application = Some(match next_value(&mut args, &arg)?.as_str() {
+                    "voip" => Application::Voip,
+                    "audio" => Application::Audio,
+                    "lowdelay" => Application::LowDelay,
+                    value => return Err(format!("{arg}: invalid application '{value}'")),
+                }),
+                "--bitrate" => result.bitrate = Some(match next_value(&mut args, &arg)?.as_str() {
+                    "auto" => Bitrate::Auto,
+                    "max" => Bitrate::Max,
+                    value => Bitrate::Value(parse_number(value, &arg)?),
+                }),
+                "--vbr" => result.vbr = Some(parse_switch(&next_value(&mut args, &arg)?, &arg)?),
+                "--vbr-constraint" => result.vbr_constraint = Some(parse_switch(&next_value(&mut args, &arg)?, &arg)?),
+                "--complexity" => result.complexity = Some(parse_number(&next_value(&mut args, &arg)?, &arg)?),
+                "--signal" => result.signal = Some(match next_value(&mut args, &arg)?.as_str() {
+                    "auto" => Signal::Auto,
+                    "voice" => Signal::Voice,
+                    "music" => Signal::Music,
+                    value => return Err(format!("{arg}: invalid signal '{value}'")),
+                }),
+                "--bandwidth" => result.bandwidth = Some(parse_bandwidth(&next_value(&mut args, &arg)?, &arg)?),
+                "--max-bandwidth" => result.max_bandwidth = Some(parse_bandwidth(&next_value(&mut args, &arg)?, &arg)?),
+                "--force-channels" => result.force_channels = Some(match next_value(&mut args, &arg)?.as_str() {
+                    "auto" => None,
+                    "mono" => Some(Channels::Mono),
+                    "stereo" => Some(Channels::Stereo),
+                    value => return Err(format!("{arg}: invalid channels '{value}'")),
+                }),
+                "--inband-fec" => result.inband_fec = Some(match next_value(&mut args, &arg)?.as_str() {
+                    "off" => InbandFec::Off,
+                    "1" => InbandFec::Mode1,
+                    "2" => InbandFec::Mode2,
+                    value => return Err(format!("{arg}: invalid mode '{value}'")),
+                }),
+                "--packet-loss" => result.packet_loss = Some(parse_number(&next_value(&mut args, &arg)?, &arg)?),
+                "--prediction-disabled" => result.prediction_disabled = Some(parse_switch(&next_value(&mut args, &arg)?, &arg)?),
+                "--lsb-depth" => result.lsb_depth = Some(parse_number(&next_value(&mut args, &arg)?, &arg)?),
+                "--framesize" => result.frame_duration = Some(parse_frame_duration(&next_value(&mut args, &arg)?)?),
+                "--dtx" => result.dtx = Some(parse_switch(&next_value(&mut args, &arg)?, &arg)?),
+                "--phase-inversion-disabled" => result.phase_inversion_disabled = Some(parse_switch(&next_value(&mut args, &arg)?, &arg)?),
+                #[cfg(feature = "dred")]
+                "--dred-duration" => result.dred_duration = Some(parse_number(&next_value(&mut args, &arg)?, &arg)?),
+                #[cfg(not(feature = "dred"))]
+                "--dred-duration" => return Err(format!("{arg} requires `dred` feature")),
+                "--serial" => result.serial = Some(parse_number(&next_value(&mut args, &arg)?, &arg)?),
+                "--comment" => {
+                    let comment = next_value(&mut args, &arg)?;
+                    if !comment.contains('=') {
+                        return Err(format!("{arg}: expected NAME=value, got '{comment}'"));
+                    }
+                    result.comments.push(comment);
+                },
+                option if option.starts_with("--") => return Err(format!("Unknown option {option}")),
+                _ => result.files.push(arg),
+            }
+        }
+        Ok(result)
+    }
+
+    fn apply(&self, encoder: &mut Encoder) -> core::result::Result<(), ErrorCode> {
+        if let Some(value) = self.bitrate {
+            encoder.set_bitrate(value)?;
+        }
+        if let Some(value) = self.vbr {
+            encoder.set_vbr(value)?;
+        }
+        if let Some(value) = self.vbr_constraint {
+            encoder.set_vbr_constraint(value)?;
+        }
+        if let Some(value) = self.complexity {
+            encoder.set_complexity(value)?;
+        }
+        if let Some(value) = self.signal {
+            encoder.set_signal(value)?;
+        }
+        if let Some(value) = self.bandwidth {
+            encoder.set_bandwidth(value)?;
+        }
+        if let Some(value) = self.max_bandwidth {
+            encoder.set_max_bandwidth(value)?;
+        }
+        if let Some(value) = self.force_channels {
+            encoder.set_force_channels(value)?;
+        }
+        if let Some(value) = self.inband_fec {
+            encoder.set_inband_fec(value)?;
+        }
+        if let Some(value) = self.packet_loss {
+            encoder.set_packet_loss(value)?;
+        }
+        if let Some(value) = self.prediction_disabled {
+            encoder.set_prediction_disabled(value)?;
+        }
+        if let Some(value) = self.lsb_depth {
+            encoder.set_lsb_depth(value)?;
+        }
+        if let Some((value, _)) = self.frame_duration {
+            encoder.set_frame_duration(value)?;
+        }
+        if let Some(value) = self.dtx {
+            encoder.set_dtx(value)?;
+        }
+        if let Some(value) = self.phase_inversion_disabled {
+            encoder.set_phase_inversion_disabled(value)?;
+        }
+        #[cfg(feature = "dred")]
+        if let Some(value) = self.dred_duration {
+            encoder.set_dred_duration(value)?;
+        }
+        Ok(())
+    }
+}
+
+fn encode(args: impl Iterator<Item = String>) -> Result<()> {
+    let options = EncodeOptions::parse(args)?;
+    let (input, output) = match options.files.as_slice() {
+        [input, output] => (input, output),
+        _ => return Err("encode requires input and output files".to_owned()),
+    };
+    let is_raw = options.raw.unwrap_or_else(|| output.ends_with(".bit"));
+
+    let data = fs::read(input).map_err(|error| format!("{input}: {error}"))?;
+    let reader = wav::WavReader::new(&data).map_err(opus_error("Invalid WAV file"))?;
+    let channels = channels(reader.channels())?;
+    let mut pcm = Vec::new();
+    reader.read_float_to_vec(&mut pcm);
+
+    //Input at rate not supported by Opus is resampled to 48kHz
+    let rate = match sample_rate(reader.sample_rate()) {
+        Some(rate) => rate,
+        None => {
+            let mut resampler = resample::Resampler::new(reader.sample_rate(), SampleRate::Hz48000 as _, channels as _, resample::Quality::High).map_err(opus_error("Unable to resample input"))?;
+            let mut resampled = Vec::new();
+            resampler.resample_float_to_vec(&pcm, &mut resampled);
+            resampler.flush_float_to_vec(&mut resampled);
+            pcm = resampled;
+            SampleRate::Hz48000
+        }
+    };
+    let scale = SampleRate::Hz48000 as usize / rate as usize;
+    let input_len = pcm.len() / channels as usize;
+
+    let mut encoder = Encoder::new(channels, rate, options.application.unwrap_or(Application::Audio)).map_err(opus_error("Unable to create encoder"))?;
+    options.apply(&mut encoder).map_err(opus_error("Invalid encoder setting"))?;
+    let mut head = OpusHead::from_encoder(&mut encoder).map_err(opus_error("Unable to create header"))?;
+    head.input_sample_rate = reader.sample_rate();
+
+    //Flush encoder's look ahead and pad the last frame with silence
+    let frame_size = options.frame_duration.map_or(960, |(_, size)| size) / scale;
+    let padded_len = (input_len + head.pre_skip as usize / scale).div_ceil(frame_size) * frame_size;
+    pcm.resize(padded_len * channels as usize, 0.0);
+
+    let mut packet = Vec::with_capacity(MAX_FRAME_SIZE);
+    let mut packets = 0;
+    let mut bytes = 0;
+    let output_data = if is_raw {
+        let mut writer = demo_format::Writer::new();
+        for frame in pcm.chunks_exact(frame_size * channels as usize) {
+            packet.clear();
+            encoder.encode_float_to_vec(frame, &mut packet).map_err(opus_error("Unable to encode"))?;
+            let range = encoder.get_final_range().map_err(opus_error("Unable to get final range"))?;
+            writer.add_packet(&packet, range).map_err(opus_error("Unable to write packet"))?;
+            packets += 1;
+            bytes += packet.len();
+        }
+        writer.finish()
+    } else {
+        let mut tags = OpusTags::new();
+        tags.comments.extend(options.comments.iter().cloned());
+        let mut writer = ogg::Writer::new(&head, &tags, options.serial.unwrap_or_else(std::process::id));
+        writer.set_end_trim(((padded_len - input_len) * scale - head.pre_skip as usize) as u64);
+        for frame in pcm.chunks_exact(frame_size * channels as usize) {
+            packet.clear();
+            encoder.encode_float_to_vec(frame, &mut packet).map_err(opus_error("Unable to encode"))?;
+            writer.add_packet(&packet).map_err(opus_error("Unable to write packet"))?;
+            packets += 1;
+            bytes += packet.len();
+        }
+        writer.finish()
+    };
+
+    fs::write(output, output_data).map_err(|error| format!("{output}: {error}"))?;
+    let duration = (padded_len as f64) / rate as u32 as f64;
+    println!("Encoded {packets} packets, {bytes} bytes, average bitrate {:.1} kbps", bytes as f64 * 8.0 / duration / 1000.0);
+    Ok(())
+}
+
+struct DecodeOptions {
+    rate: SampleRate,
+    channels: Option<Channels>,
+    float: bool,
+    files: Vec<String>,
+}
+
+impl DecodeOptions {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self> {
+        let mut result = Self {
+            rate: SampleRate::Hz48000,
+            channels: None,
+            float: false,
+            files: Vec::new(),
+        };
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--rate" => {
+                    let value = next_value(&mut args, &arg)?;
+                    result.rate = sample_rate(parse_number(&value, &arg)?).ok_or_else(|| format!("{arg}: unsupported rate '{value}'"))?;
+                },
+                "--channels" => result.channels = Some(channels(parse_number(&next_value(&mut args, &arg)?, &arg)?)?),
+                "--float" => result.float = true,
+                option if option.starts_with("--") => return Err(format!("Unknown option {option}")),
+                _ => result.files.push(arg),
+            }
+        }
+        Ok(result)
+    }
+}
+
+fn decode(args: impl Iterator<Item = String>) -> Result<()> {
+    let options = DecodeOptions::parse(args)?;
+    let (input, output) = match options.files.as_slice() {
+        [input, output] => (input, output),
+        _ => return Err("decode requires input and output files".to_owned()),
+    };
+    let data = fs::read(input).map_err(|error| format!("{input}: {error}"))?;
+    let rate = options.rate;
+    let scale = SampleRate::Hz48000 as usize / rate as usize;
+    let mut buffer = vec![0f32; MAX_FRAME_SIZE / scale * 2];
+    let mut pcm = Vec::new();
+
+    let channels = if data.starts_with(&ogg::CAPTURE_PATTERN) {
+        let mut reader = ogg::Reader::new(&data).map_err(opus_error("Invalid Ogg Opus stream"))?;
+        let head = reader.head().clone();
+        if head.mapping_family != 0 {
+            return Err(format!("Unsupported channel mapping family {}: only mono and stereo are supported", head.mapping_family));
+        }
+        let channels = match options.channels {
+            Some(channels) => channels,
+            None => self::channels(head.channels as u16)?,
+        };
+        let frame_len = buffer.len() / 2 * channels as usize;
+
+        let mut decoder = Decoder::new(channels, rate).map_err(opus_error("Unable to create decoder"))?;
+        decoder.set_gain(head.output_gain as i32).map_err(opus_error("Invalid output gain"))?;
+        let mut granule = None;
+        while let Some(packet) = reader.next_packet().map_err(opus_error("Invalid Ogg page"))? {
+            //Conceal audio of damaged pages, in chunks that are multiple of 2.5ms
+            let mut lost = packet.lost_samples as usize / scale;
+            while lost > 0 {
+                let len = lost.min(frame_len / channels as usize);
+                let unit = rate as usize / 400;
+                let conceal_len = len.div_ceil(unit) * unit * channels as usize;
+                decoder.decode_float_to_slice(&[], &mut buffer[..conceal_len], false).map_err(opus_error("Unable to conceal lost packet"))?;
+                pcm.extend_from_slice(&buffer[..len * channels as usize]);
+                lost -= len;
+            }
+
+            let len = decoder.decode_float_to_slice(packet.data, &mut buffer[..frame_len], false).map_err(opus_error("Unable to decode"))?;
+            pcm.extend_from_slice(&buffer[..len * channels as usize]);
+            if packet.granule_position.is_some() {
+                granule = packet.granule_position;
+            }
+        }
+
+        let pre_skip = head.pre_skip as usize / scale * channels as usize;
+        pcm.drain(..pre_skip.min(pcm.len()));
+        if let Some(granule) = granule {
+            let len = (granule as usize).saturating_sub(head.pre_skip as usize) / scale * channels as usize;
+            pcm.truncate(len);
+        }
+        channels
+    } else {
+        let channels = options.channels.unwrap_or(Channels::Stereo);
+        let mut decoder = Decoder::new(channels, rate).map_err(opus_error("Unable to create decoder"))?;
+        let mut reader = demo_format::Reader::new(&data);
+        while let Some(frame) = reader.next_frame().map_err(opus_error("Invalid opus_demo bitstream"))? {
+            let frame_len = match frame.is_lost() {
+                true => decoder.get_last_packet_duration().map_err(opus_error("Unable to conceal lost packet"))? as usize * channels as usize,
+                false => buffer.len() / 2 * channels as usize,
+            };
+            let len = decoder.decode_float_to_slice(frame.data, &mut buffer[..frame_len], false).map_err(opus_error("Unable to decode"))?;
+            pcm.extend_from_slice(&buffer[..len * channels as usize]);
+        }
+        channels
+    };
+
+    let format = match options.float {
+        true => wav::SampleFormat::Float32,
+        false => wav::SampleFormat::Int16,
+    };
+    let mut writer = wav::WavWriter::new(rate, channels, format);
+    writer.write_float(&pcm);
+    fs::write(output, writer.finish()).map_err(|error| format!("{output}: {error}"))?;
+    println!("Decoded {} samples per channel at {} Hz", pcm.len() / channels as usize, rate as u32);
+    Ok(())
+}
+
+fn packet_info(idx: usize, packet: &[u8]) -> String {
+    let toc = match utils::Toc::parse(packet) {
+        Ok(toc) => toc,
+        Err(_) => return format!("{idx:>6} {:>6} {:<70}", 0, "lost"),
+    };
+    let frames = utils::get_nb_frames(packet);
+    let samples = utils::get_nb_samples(packet, SampleRate::Hz48000);
+    let padding = utils::get_padding(packet).map(|padding| padding.len());
+    match (frames, samples, padding) {
+        (Ok(frames), Ok(samples), Ok(padding)) => {
+            let lbrr = utils::has_lbrr(packet).unwrap_or(false);
+            let dred = utils::has_dred(packet).unwrap_or(false);
+            format!(
+                "{idx:>6} {:>6} {:>6} {:>6} {:>9} {:>6} {:>6} {:>8.1} {:>7} {:>4} {:>4}",
+                packet.len(), toc.config(), format!("{:?}", toc.mode()), format!("{:?}", toc.bandwidth()),
+                format!("{:?}", toc.channels()), frames, samples as f32 / 48.0, padding,
+                if lbrr { "yes" } else { "no" }, if dred { "yes" } else { "no" },
+            )
+        },
+        _ => format!("{idx:>6} {:>6} {:<70}", packet.len(), "invalid"),
+    }
+}
+
+fn inspect(mut args: impl Iterator<Item = String>) -> Result<()> {
+    let input = match (args.next(), args.next()) {
+        (Some(input), None) => input,
+        _ => return Err("inspect requires single input file".to_owned()),
+    };
+    let data = fs::read(&input).map_err(|error| format!("{input}: {error}"))?;
+    const HEADER: &str = "packet   size config   mode bandwidth    ch. frames  dur(ms) padding lbrr dred";
+    let mut out = std::io::stdout().lock();
+
+    if data.starts_with(&ogg::CAPTURE_PATTERN) {
+        let mut reader = ogg::Reader::new(&data).map_err(opus_error("Invalid Ogg Opus stream"))?;
+        let head = reader.head().clone();
+        writeln!(out, "Ogg Opus stream {:08x}", reader.serial()).map_err(output_error)?;
+        writeln!(out, "  channels: {}", head.channels).map_err(output_error)?;
+        writeln!(out, "  pre-skip: {}", head.pre_skip).map_err(output_error)?;
+        writeln!(out, "  input sample rate: {}", head.input_sample_rate).map_err(output_error)?;
+        writeln!(out, "  output gain: {}", head.output_gain).map_err(output_error)?;
+        writeln!(out, "  mapping family: {}", head.mapping_family).map_err(output_error)?;
+        if let Some(mapping) = head.stream_mapping.as_ref() {
+            writeln!(out, "  streams: {} ({} coupled), mapping {:?} (packet info refers to the first stream)", mapping.streams, mapping.coupled_streams, mapping.mapping).map_err(output_error)?;
+        }
+        writeln!(out, "  vendor: {}", reader.tags().vendor).map_err(output_error)?;
+        for comment in reader.tags().comments.iter() {
+            writeln!(out, "  comment: {comment}").map_err(output_error)?;
+        }
+
+        writeln!(out, "{HEADER}  granule").map_err(output_error)?;
+        let mut idx = 0;
+        while let Some(packet) = reader.next_packet().map_err(opus_error("Invalid Ogg page"))? {
+            let info = packet_info(idx, packet.data);
+            match packet.granule_position {
+                Some(granule) => writeln!(out, "{info} {granule:>8}"),
+                None => writeln!(out, "{info}"),
+            }.map_err(output_error)?;
+            idx += 1;
+        }
+    } else {
+        let mut reader = demo_format::Reader::new(&data);
+        writeln!(out, "opus_demo bitstream").map_err(output_error)?;
+        writeln!(out, "{HEADER}  final range").map_err(output_error)?;
+        let mut idx = 0;
+        while let Some(frame) = reader.next_frame().map_err(opus_error("Invalid opus_demo bitstream"))? {
+            writeln!(out, "{} {:>12x}", packet_info(idx, frame.data), frame.final_range).map_err(output_error)?;
+            idx += 1;
+        }
+    }
+
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let mut args = std::env::args().skip(1);
+    let result = match args.next().as_deref() {
+        Some("encode") => encode(args),
+        Some("decode") => decode(args),
+        Some("inspect") => inspect(args),
+        Some("-h" | "--help" | "help") => {
+            println!("{USAGE}");
+            return ExitCode::SUCCESS;
+        },
+        Some(command) => Err(format!("Unknown command '{command}'\n\n{USAGE}")),
+        None => Err(USAGE.to_owned()),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("{error}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,238 @@
+//! Length prefixed framing for stream transports
+//!
+//![OpusCodec](struct.OpusCodec.html) implements [tokio_util::codec](https://docs.rs/tokio-util/0.7/tokio_util/codec/index.html)
+//!traits to send Opus packets over TCP, WebSocket or any other byte stream.
+//!
+//!## Format
+//!
+//!Each frame is stored as:
+//!
+//!- Packet length as unsigned LEB128 varint;
+//!- Optional timestamp, in 48 kHz samples, as unsigned LEB128 varint;
+//!- Packet.
+//!
+//!Zero length packet indicates lost packet.
+//!
+//!When timestamps are not transmitted, decoder derives them from duration of received packets,
+//!assuming lost packet to be as long as the previous one (20ms if there is none).
+
+extern crate std;
+
+use core::fmt;
+use std::io;
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use tokio_util::codec;
+
+use crate::{utils, ErrorCode, SampleRate};
+
+///Maximum size of varint encoded `u64`
+const MAX_VARINT_SIZE: usize = 10;
+//Duration of lost packet, when there is no previous packet (20ms)
+const DEFAULT_LOSS_DURATION: u64 = 960;
+///Maximum size of packet accepted by codec (120ms of 1275 bytes frames plus framing)
+pub const MAX_PACKET_SIZE: usize = 1275 * 48 + 7;
+
+#[derive(Debug)]
+///Codec error
+pub enum CodecError {
+    ///Transport failure
+    Io(io::Error),
+    ///Malformed frame or packet
+    Opus(ErrorCode),
+}
+
+impl fmt::Display for CodecError {
+    #[inline]
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => fmt::Display::fmt(error, fmt),
+            Self::Opus(error) => fmt.write_str(error.message()),
+        }
+    }
+}
+
+impl std::error::Error for CodecError {
+    #[inline]
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            Self::Opus(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for CodecError {
+    #[inline(always)]
+    fn from(value: io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+impl From<ErrorCode> for CodecError {
+    #[inline(always)]
+    fn from(value: ErrorCode) -> Self {
+        Self::Opus(value)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+///Opus packet with its timestamp
+pub struct Frame {
+    ///Timestamp of the first sample in 48 kHz samples
+    pub timestamp: u64,
+    ///Packet data, empty if packet is lost
+    pub packet: Bytes,
+}
+
+impl Frame {
+    #[inline(always)]
+    ///Returns whether packet is lost
+    pub fn is_lost(&self) -> bool {
+        self.packet.is_empty()
+    }
+}
+
+fn put_varint(dst: &mut BytesMut, mut value: u64) {
+    while value >= 0x80 {
+        dst.put_u8(value as u8 | 0x80);
+        value >>= 7;
+    }
+    dst.put_u8(value as u8);
+}
+
+//Returns value and its size, if there is enough data
+fn get_varint(src: &[u8]) -> Result<Option<(u64, usize)>, ErrorCode> {
+    let mut value = 0u64;
+    for (idx, byte) in src.iter().take(MAX_VARINT_SIZE).enumerate() {
+        let bits = (*byte & 0x7f) as u64;
+        if idx == MAX_VARINT_SIZE - 1 && bits > 1 {
+            return Err(ErrorCode::invalid_packet());
+        }
+
+        value |= bits << (idx * 7);
+        if *byte & 0x80 == 0 {
+            return Ok(Some((value, idx + 1)));
+        }
+    }
+
+    match src.len() >= MAX_VARINT_SIZE {
+        true => Err(ErrorCode::invalid_packet()),
+        false => Ok(None),
+    }
+}
+
+//Parses whole packet structure, returning its duration in 48 kHz samples
+fn validate_packet(packet: &[u8]) -> Result<u64, ErrorCode> {
+    let samples = utils::get_nb_samples(packet, SampleRate::Hz48000)?;
+    utils::get_padding(packet)?;
+    Ok(samples as u64)
+}
+
+#[derive(Debug, Clone, Copy)]
+///Length prefixed Opus packets codec
+///
+///Every decoded packet is validated, hence can be safely passed to `Decoder`.
+pub struct OpusCodec {
+    timestamps: bool,
+    next_timestamp: u64,
+    //Duration of the last received packet
+    last_duration: u64,
+}
+
+impl OpusCodec {
+    #[inline(always)]
+    ///Creates new instance, with `timestamps` indicating whether to transmit timestamps.
+    pub const fn new(timestamps: bool) -> Self {
+        Self {
+            timestamps,
+            next_timestamp: 0,
+            last_duration: DEFAULT_LOSS_DURATION,
+        }
+    }
+
+    #[inline(always)]
+    ///Returns timestamp of the next frame, as derived from previously decoded packets
+    pub fn next_timestamp(&self) -> u64 {
+        self.next_timestamp
+    }
+}
+
+impl Default for OpusCodec {
+    #[inline(always)]
+    fn default() -> Self {
+        Self::new(false)
+    }
+}
+
+impl codec::Decoder for OpusCodec {
+    type Item = Frame;
+    type Error = CodecError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        let (len, mut header_len) = match get_varint(src)? {
+            Some(len) => len,
+            None => return Ok(None),
+        };
+        let len = match usize::try_from(len) {
+            Ok(len) if len <= MAX_PACKET_SIZE => len,
+            _ => return Err(ErrorCode::invalid_packet().into()),
+        };
+
+        let timestamp = match self.timestamps {
+            true => match get_varint(&src[header_len..])? {
+                Some((timestamp, size)) => {
+                    header_len += size;
+                    timestamp
+                },
+                None => return Ok(None),
+            },
+            false => self.next_timestamp,
+        };
+
+        if src.len() < header_len + len {
+            src.reserve(header_len + len - src.len());
+            return Ok(None);
+        }
+
+        let duration = match len {
+            0 => self.last_duration,
+            len => validate_packet(&src[header_len..header_len + len])?,
+        };
+        self.last_duration = duration;
+        src.advance(header_len);
+        self.next_timestamp = timestamp.wrapping_add(duration);
+
+        Ok(Some(Frame {
+            timestamp,
+            packet: src.split_to(len).freeze(),
+        }))
+    }
+}
+
+impl codec::Encoder<Frame> for OpusCodec {
+    type Error = CodecError;
+
+    #[inline(always)]
+    fn encode(&mut self, item: Frame, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        codec::Encoder::<&Frame>::encode(self, &item, dst)
+    }
+}
+
+impl codec::Encoder<&Frame> for OpusCodec {
+    type Error = CodecError;
+
+    fn encode(&mut self, item: &Frame, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        if item.packet.len() > MAX_PACKET_SIZE {
+            return Err(ErrorCode::bad_arg().into());
+        }
+
+        dst.reserve(MAX_VARINT_SIZE * 2 + item.packet.len());
+        put_varint(dst, item.packet.len() as u64);
+        if self.timestamps {
+            put_varint(dst, item.timestamp);
+        }
+        dst.extend_from_slice(&item.packet);
+        Ok(())
+    }
+}
@@ -0,0 +1,276 @@
+//! Objective quality comparison
+//!
+//!Port of `opus_compare` tool from libopus, which is used to judge conformance of decoder output against reference.
+//!
+//!It computes pseudo noise-to-mask ratio over Bark-derived bands, producing quality metric where
+//!negative value means that decoded output fails conformance.
+//!
+//!Note that reference is expected to be output of reference decoder for the same stream (e.g. decoded at 48 kHz),
+//!rather than original encoder input: the metric is too strict to judge lossy coding itself.
+
+use crate::{mem, Channels, ErrorCode, SampleRate};
+
+use core::f32::consts::PI;
+use mem::alloc::vec::Vec;
+
+const NBANDS: usize = 21;
+const NFREQS: usize = 240;
+
+//Bands on which we compute the pseudo-NMR (Bark-derived CELT bands).
+const BANDS: [usize; NBANDS + 1] = [
+    0, 2, 4, 6, 8, 10, 12, 14, 16, 20, 24, 28, 32, 40, 48, 56, 68, 80, 96, 120, 156, 200
+];
+
+const TEST_WIN_SIZE: usize = 480;
+const TEST_WIN_STEP: usize = 120;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+///Result of comparison
+pub struct Comparison {
+    ///Opus quality metric in percents.
+    ///
+    ///Negative value means failure.
+    pub quality: f32,
+    ///Internal weighted error
+    pub error: f64,
+}
+
+impl Comparison {
+    #[inline(always)]
+    ///Returns whether decoded output passes conformance threshold
+    pub fn is_pass(&self) -> bool {
+        self.quality >= 0.0
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn band_energy(mut out: Option<&mut [f32]>, ps: &mut [f32], bands: &[usize], nbands: usize, input: &[f32], nchannels: usize, nframes: usize, window_sz: usize, step: usize, downsample: usize) {
+    let mut window = Vec::with_capacity(window_sz);
+    let mut c = Vec::with_capacity(window_sz);
+    let mut s = Vec::with_capacity(window_sz);
+    let mut x = Vec::new();
+    x.resize(nchannels * window_sz, 0.0f32);
+    let ps_sz = window_sz / 2;
+
+    for xj in 0..window_sz {
+        window.push(0.5 - 0.5 * libm::cos(((2.0 * PI / (window_sz - 1) as f32) * xj as f32) as f64) as f32);
+        c.push(libm::cos(((2.0 * PI / window_sz as f32) * xj as f32) as f64) as f32);
+        s.push(libm::sin(((2.0 * PI / window_sz as f32) * xj as f32) as f64) as f32);
+    }
+
+    for xi in 0..nframes {
+        for ci in 0..nchannels {
+            for xk in 0..window_sz {
+                x[ci * window_sz + xk] = window[xk] * input[(xi * step + xk) * nchannels + ci];
+            }
+        }
+
+        let mut xj = 0;
+        for bi in 0..nbands {
+            let mut p = [0f32; 2];
+            while xj < bands[bi + 1] {
+                for ci in 0..nchannels {
+                    let mut re = 0f32;
+                    let mut im = 0f32;
+                    let mut ti = 0;
+                    for xk in 0..window_sz {
+                        re += c[ti] * x[ci * window_sz + xk];
+                        im -= s[ti] * x[ci * window_sz + xk];
+                        ti += xj;
+                        if ti >= window_sz {
+                            ti -= window_sz;
+                        }
+                    }
+                    re *= downsample as f32;
+                    im *= downsample as f32;
+                    let value = re * re + im * im + 100000.0;
+                    ps[(xi * ps_sz + xj) * nchannels + ci] = value;
+                    p[ci] += value;
+                }
+                xj += 1;
+            }
+
+            if let Some(ref mut out) = out {
+                let width = (bands[bi + 1] - bands[bi]) as f32;
+                out[(xi * nbands + bi) * nchannels] = p[0] / width;
+                if nchannels == 2 {
+                    out[(xi * nbands + bi) * nchannels + 1] = p[1] / width;
+                }
+            }
+        }
+    }
+}
+
+///Compares `decoded` output against `reference`.
+///
+///- `reference` - 48 kHz PCM with `reference_channels` channels (interleaved);
+///- `decoded` - PCM at `rate` with `channels` channels (interleaved).
+///
+///When `channels` is mono and reference is stereo, reference is downmixed.
+///
+///Returns `ErrorCode::BadArg` if sample counts do not match, there is less than 10ms of audio or reference is mono while decoded output is stereo.
+pub fn compare(reference: &[u16], reference_channels: Channels, decoded: &[u16], channels: Channels, rate: SampleRate) -> Result<Comparison, ErrorCode> {
+    let reference = reference.iter().map(|sample| *sample as i16 as f32);
+    let x: Vec<f32> = match (reference_channels, channels) {
+        (Channels::Mono, Channels::Stereo) => return Err(ErrorCode::bad_arg()),
+        (Channels::Stereo, Channels::Mono) => {
+            let reference: Vec<f32> = reference.collect();
+            reference.chunks_exact(2).map(|frame| 0.5 * (frame[0] + frame[1])).collect()
+        },
+        _ => reference.collect(),
+    };
+    let y: Vec<f32> = decoded.iter().map(|sample| *sample as i16 as f32).collect();
+    compare_samples(&x, &y, channels as usize, rate)
+}
+
+///Compares `decoded` output against `reference`, both in float format.
+///
+///Refer to [compare](fn.compare.html) for details
+pub fn compare_float(reference: &[f32], reference_channels: Channels, decoded: &[f32], channels: Channels, rate: SampleRate) -> Result<Comparison, ErrorCode> {
+    let reference = reference.iter().map(|sample| *sample * 32768.0);
+    let x: Vec<f32> = match (reference_channels, channels) {
+        (Channels::Mono, Channels::Stereo) => return Err(ErrorCode::bad_arg()),
+        (Channels::Stereo, Channels::Mono) => {
+            let reference: Vec<f32> = reference.collect();
+            reference.chunks_exact(2).map(|frame| 0.5 * (frame[0] + frame[1])).collect()
+        },
+        _ => reference.collect(),
+    };
+    let y: Vec<f32> = decoded.iter().map(|sample| *sample * 32768.0).collect();
+    compare_samples(&x, &y, channels as usize, rate)
+}
+
+fn compare_samples(x: &[f32], y: &[f32], nchannels: usize, rate: SampleRate) -> Result<Comparison, ErrorCode> {
+    let downsample = SampleRate::Hz48000 as usize / rate as usize;
+    let ybands = match rate {
+        SampleRate::Hz8000 => 13,
+        SampleRate::Hz12000 => 15,
+        SampleRate::Hz16000 => 17,
+        SampleRate::Hz24000 => 19,
+        SampleRate::Hz48000 => NBANDS,
+    };
+    let yfreqs = NFREQS / downsample;
+
+    let xlength = x.len() / nchannels;
+    let ylength = y.len() / nchannels;
+    if xlength != ylength * downsample || xlength < TEST_WIN_SIZE {
+        return Err(ErrorCode::bad_arg());
+    }
+
+    let nframes = (xlength - TEST_WIN_SIZE + TEST_WIN_STEP) / TEST_WIN_STEP;
+    let mut xb = Vec::new();
+    xb.resize(nframes * NBANDS * nchannels, 0f32);
+    let mut big_x = Vec::new();
+    big_x.resize(nframes * NFREQS * nchannels, 0f32);
+    let mut big_y = Vec::new();
+    big_y.resize(nframes * yfreqs * nchannels, 0f32);
+
+    //Compute the per-band spectral energy of the original signal and the error.
+    band_energy(Some(&mut xb), &mut big_x, &BANDS, NBANDS, x, nchannels, nframes, TEST_WIN_SIZE, TEST_WIN_STEP, 1);
+    band_energy(None, &mut big_y, &BANDS, ybands, y, nchannels, nframes, TEST_WIN_SIZE / downsample, TEST_WIN_STEP / downsample, downsample);
+
+    for xi in 0..nframes {
+        //Frequency masking (low to high): 10 dB/Bark slope.
+        for bi in 1..NBANDS {
+            for ci in 0..nchannels {
+                xb[(xi * NBANDS + bi) * nchannels + ci] += 0.1 * xb[(xi * NBANDS + bi - 1) * nchannels + ci];
+            }
+        }
+        //Frequency masking (high to low): 15 dB/Bark slope.
+        for bi in (0..NBANDS - 1).rev() {
+            for ci in 0..nchannels {
+                xb[(xi * NBANDS + bi) * nchannels + ci] += 0.03 * xb[(xi * NBANDS + bi + 1) * nchannels + ci];
+            }
+        }
+        if xi > 0 {
+            //Temporal masking: -3 dB/2.5ms slope.
+            for bi in 0..NBANDS {
+                for ci in 0..nchannels {
+                    xb[(xi * NBANDS + bi) * nchannels + ci] += 0.5 * xb[((xi - 1) * NBANDS + bi) * nchannels + ci];
+                }
+            }
+        }
+        //Allowing some cross-talk
+        if nchannels == 2 {
+            for bi in 0..NBANDS {
+                let l = xb[(xi * NBANDS + bi) * nchannels];
+                let r = xb[(xi * NBANDS + bi) * nchannels + 1];
+                xb[(xi * NBANDS + bi) * nchannels] += 0.01 * r;
+                xb[(xi * NBANDS + bi) * nchannels + 1] += 0.01 * l;
+            }
+        }
+
+        //Apply masking
+        for bi in 0..ybands {
+            for xj in BANDS[bi]..BANDS[bi + 1] {
+                for ci in 0..nchannels {
+                    big_x[(xi * NFREQS + xj) * nchannels + ci] += 0.1 * xb[(xi * NBANDS + bi) * nchannels + ci];
+                    big_y[(xi * yfreqs + xj) * nchannels + ci] += 0.1 * xb[(xi * NBANDS + bi) * nchannels + ci];
+                }
+            }
+        }
+    }
+
+    //Average of consecutive frames to make comparison slightly less sensitive
+    for bi in 0..ybands {
+        for xj in BANDS[bi]..BANDS[bi + 1] {
+            for ci in 0..nchannels {
+                let mut xtmp = big_x[xj * nchannels + ci];
+                let mut ytmp = big_y[xj * nchannels + ci];
+                for xi in 1..nframes {
+                    let xtmp2 = big_x[(xi * NFREQS + xj) * nchannels + ci];
+                    let ytmp2 = big_y[(xi * yfreqs + xj) * nchannels + ci];
+                    big_x[(xi * NFREQS + xj) * nchannels + ci] += xtmp;
+                    big_y[(xi * yfreqs + xj) * nchannels + ci] += ytmp;
+                    xtmp = xtmp2;
+                    ytmp = ytmp2;
+                }
+            }
+        }
+    }
+
+    //If working at a lower sampling rate, don't take into account the last 300 Hz to allow for different transition bands.
+    //For 12 kHz, we don't skip anything, because the last band already skips 400 Hz.
+    let max_compare = match rate {
+        SampleRate::Hz48000 => BANDS[NBANDS],
+        SampleRate::Hz12000 => BANDS[ybands],
+        _ => BANDS[ybands] - 3,
+    };
+
+    let mut err = 0f64;
+    for xi in 0..nframes {
+        let mut ef = 0f64;
+        for bi in 0..ybands {
+            let mut eb = 0f64;
+            let mut xj = BANDS[bi];
+            while xj < BANDS[bi + 1] && xj < max_compare {
+                for ci in 0..nchannels {
+                    let re = big_y[(xi * yfreqs + xj) * nchannels + ci] / big_x[(xi * NFREQS + xj) * nchannels + ci];
+                    let mut im = (re as f64 - libm::log(re as f64) - 1.0) as f32;
+                    //Make comparison less sensitive around the SILK/CELT cross-over to allow for mode freedom in the filters.
+                    if (79..=81).contains(&xj) {
+                        im *= 0.1;
+                    }
+                    if xj == 80 {
+                        im *= 0.1;
+                    }
+                    eb += im as f64;
+                }
+                xj += 1;
+            }
+            eb /= ((BANDS[bi + 1] - BANDS[bi]) * nchannels) as f64;
+            ef += eb * eb;
+        }
+        //Using a fixed normalization value means we're willing to accept slightly lower quality for lower sampling rates.
+        ef /= NBANDS as f64;
+        ef *= ef;
+        err += ef * ef;
+    }
+
+    let err = libm::pow(err / nframes as f64, 1.0 / 16.0);
+    let quality = (100.0 * (1.0 - 0.5 * libm::log(1.0 + err) / libm::log(1.13))) as f32;
+    Ok(Comparison {
+        quality,
+        error: err,
+    })
+}
@@ -0,0 +1,137 @@
+//! `opus_demo` bitstream format
+//!
+//!Format used by libopus tooling (`opus_demo`, test vectors) to store raw Opus packets.
+//!Each packet is stored as:
+//!
+//!- 4 bytes of big endian payload length;
+//!- 4 bytes of big endian encoder's final range (refer to `Encoder::get_final_range`);
+//!- payload.
+//!
+//!Packet with zero length indicates lost packet.
+
+use crate::{mem, ErrorCode};
+
+use mem::alloc::vec::Vec;
+
+///Size of frame header
+pub const HEADER_SIZE: usize = 8;
+///Maximum payload size accepted by `opus_demo`
+pub const MAX_PACKET_SIZE: usize = 1500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+///Packet read from `opus_demo` bitstream
+pub struct Frame<'a> {
+    ///Packet data, empty if packet is lost
+    pub data: &'a [u8],
+    ///Encoder's final range after encoding this packet.
+    ///
+    ///Zero if unknown.
+    pub final_range: u32,
+}
+
+impl<'a> Frame<'a> {
+    #[inline(always)]
+    ///Returns whether packet is lost
+    pub fn is_lost(&self) -> bool {
+        self.data.is_empty()
+    }
+}
+
+///`opus_demo` bitstream reader
+pub struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    #[inline(always)]
+    ///Creates new reader over `data`
+    pub const fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            pos: 0,
+        }
+    }
+
+    #[inline(always)]
+    ///Returns number of bytes consumed so far
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    ///Reads next frame, returning `None` at the end of data
+    ///
+    ///Returns `ErrorCode::InvalidPacket` if frame is truncated or its length is negative.
+    pub fn next_frame(&mut self) -> Result<Option<Frame<'a>>, ErrorCode> {
+        let data = &self.data[self.pos..];
+        if data.is_empty() {
+            return Ok(None);
+        } else if data.len() < HEADER_SIZE {
+            return Err(ErrorCode::invalid_packet());
+        }
+
+        let len = i32::from_be_bytes([data[0], data[1], data[2], data[3]]);
+        let final_range = u32::from_be_bytes([data[4], data[5], data[6], data[7]]);
+        let len = match usize::try_from(len) {
+            Ok(len) if data.len() - HEADER_SIZE >= len => len,
+            _ => return Err(ErrorCode::invalid_packet()),
+        };
+
+        self.pos += HEADER_SIZE + len;
+        Ok(Some(Frame {
+            data: &data[HEADER_SIZE..HEADER_SIZE + len],
+            final_range,
+        }))
+    }
+}
+
+#[derive(Default)]
+///`opus_demo` bitstream writer
+///
+///Note that `opus_demo` rejects packets larger than [MAX_PACKET_SIZE](constant.MAX_PACKET_SIZE.html)
+pub struct Writer {
+    out: Vec<u8>,
+}
+
+impl Writer {
+    #[inline(always)]
+    ///Creates new empty writer
+    pub const fn new() -> Self {
+        Self {
+            out: Vec::new(),
+        }
+    }
+
+    ///Adds packet with encoder's `final_range`.
+    ///
+    ///Returns `ErrorCode::BadArg` if packet length cannot be represented
+    pub fn add_packet(&mut self, packet: &[u8], final_range: u32) -> Result<(), ErrorCode> {
+        let len = match i32::try_from(packet.len()) {
+            Ok(len) => len,
+            Err(_) => return Err(ErrorCode::bad_arg()),
+        };
+
+        self.out.extend_from_slice(&len.to_be_bytes());
+        self.out.extend_from_slice(&final_range.to_be_bytes());
+        self.out.extend_from_slice(packet);
+        Ok(())
+    }
+
+    #[inline]
+    ///Adds lost packet
+    pub fn add_lost(&mut self) {
+        self.out.extend_from_slice(&[0; HEADER_SIZE]);
+    }
+
+    #[inline]
+    ///Takes output written so far, leaving internal buffer empty
+    pub fn take_output(&mut self) -> Vec<u8> {
+        core::mem::take(&mut self.out)
+    }
+
+    #[inline(always)]
+    ///Returns written output
+    pub fn finish(self) -> Vec<u8> {
+        self.out
+    }
+}
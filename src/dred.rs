@@ -117,7 +117,10 @@ impl Dred {
 
         let mut _dred_end = 0;
         let input_ptr = input.as_ptr();
-        let input_len = input.len() as _;
+        let input_len = match input.len().try_into() {
+            Ok(len) => len,
+            Err(_) => return Err(ErrorCode::bad_arg()),
+        };
 
         let frame_size = (output.len() / self.decoder.channels() as usize) as _;
 
@@ -162,7 +165,10 @@ impl Dred {
 
         let mut _dred_end = 0;
         let input_ptr = input.as_ptr();
-        let input_len = input.len() as _;
+        let input_len = match input.len().try_into() {
+            Ok(len) => len,
+            Err(_) => return Err(ErrorCode::bad_arg()),
+        };
 
         let frame_size = (output.len() / self.decoder.channels() as usize) as _;
 
@@ -0,0 +1,216 @@
+//! Lossless editing of Opus streams
+//!
+//!Streams are cut and joined in packet domain, avoiding generation loss of decoding and re-encoding.
+//!
+//!Cuts are performed with frame precision, using [Repacketizer](../repacketizer/struct.Repacketizer.html)
+//!to drop frames of multi-frame packets, while exact sample positions are achieved via pre-skip and end trimming.
+//!Packets of multistream are cut at packet boundaries.
+
+use crate::{mem, ogg, utils, ErrorCode, SampleRate};
+use crate::header::{OpusHead, OpusTags};
+use crate::repacketizer::Repacketizer;
+
+use mem::alloc::vec::Vec;
+
+///Number of samples (at 48 kHz) preceding cut to keep for decoder to converge, as recommended by RFC 7845
+pub const PRE_ROLL: u64 = 3840;
+
+//Packets overlapping requested range alongside with absolute positions of the first and past the last kept sample
+struct Cut {
+    packets: Vec<Vec<u8>>,
+    start: u64,
+    end: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+///Sequence of packets making up Opus stream
+pub struct Clip {
+    ///Identification header
+    pub head: OpusHead,
+    ///Audio packets
+    pub packets: Vec<Vec<u8>>,
+    ///Number of samples (at 48 kHz) to discard at the end of the last packet
+    pub end_trim: u64,
+}
+
+impl Clip {
+    #[inline]
+    ///Creates empty clip
+    pub const fn new(head: OpusHead) -> Self {
+        Self {
+            head,
+            packets: Vec::new(),
+            end_trim: 0,
+        }
+    }
+
+    ///Reads clip from Ogg Opus stream, deriving end trimming from the final granule position
+    pub fn from_ogg(data: &[u8]) -> Result<Self, ErrorCode> {
+        let mut reader = ogg::Reader::new(data)?;
+        let mut this = Self::new(reader.head().clone());
+        let mut samples = 0;
+        let mut granule = None;
+        while let Some(packet) = reader.next_packet()? {
+            samples += utils::get_nb_samples(packet.data, SampleRate::Hz48000)? as u64;
+            if packet.granule_position.is_some() {
+                granule = packet.granule_position;
+            }
+            this.packets.push(packet.data.to_vec());
+        }
+
+        if let Some(granule) = granule {
+            this.end_trim = samples.saturating_sub(granule);
+        }
+        Ok(this)
+    }
+
+    ///Writes clip as Ogg Opus stream
+    pub fn to_ogg(&self, tags: &OpusTags, serial: u32) -> Result<Vec<u8>, ErrorCode> {
+        let mut writer = ogg::Writer::new(&self.head, tags, serial);
+        for packet in self.packets.iter() {
+            writer.add_packet(packet)?;
+        }
+        writer.set_end_trim(self.end_trim);
+        Ok(writer.finish())
+    }
+
+    ///Returns total duration of packets in samples at 48 kHz
+    pub fn samples(&self) -> Result<u64, ErrorCode> {
+        let mut samples = 0;
+        for packet in self.packets.iter() {
+            samples += utils::get_nb_samples(packet, SampleRate::Hz48000)? as u64;
+        }
+        Ok(samples)
+    }
+
+    #[inline]
+    ///Returns playable duration in samples at 48 kHz, excluding pre-skip and end trimming
+    pub fn duration(&self) -> Result<u64, ErrorCode> {
+        Ok(self.samples()?.saturating_sub(self.head.pre_skip as u64 + self.end_trim))
+    }
+
+    #[inline]
+    fn is_multistream(&self) -> bool {
+        self.head.stream_mapping.as_ref().is_some_and(|mapping| mapping.streams > 1)
+    }
+
+    //Keeps frames overlapping absolute range `from..to`
+    fn cut(&self, from: u64, to: u64) -> Result<Cut, ErrorCode> {
+        let is_multistream = self.is_multistream();
+        let mut repacketizer = None;
+        let mut result = Cut {
+            packets: Vec::new(),
+            start: from,
+            end: from,
+        };
+        let mut position = 0;
+
+        for packet in self.packets.iter() {
+            if position >= to {
+                break;
+            }
+            let samples = utils::get_nb_samples(packet, SampleRate::Hz48000)? as u64;
+            let packet_start = position;
+            position += samples;
+            if position <= from {
+                continue;
+            }
+
+            let frames = match is_multistream {
+                true => 1,
+                false => utils::get_nb_frames(packet)? as u64,
+            };
+            let frame_size = samples / frames;
+            let first = from.saturating_sub(packet_start) / frame_size;
+            let last = core::cmp::min(frames, (to - packet_start).div_ceil(frame_size));
+            if result.packets.is_empty() {
+                result.start = packet_start + first * frame_size;
+            }
+            result.end = packet_start + last * frame_size;
+
+            if first == 0 && last == frames {
+                result.packets.push(packet.clone());
+                continue;
+            }
+
+            let repacketizer = match repacketizer.as_mut() {
+                Some(repacketizer) => repacketizer,
+                None => repacketizer.insert(Repacketizer::new()?),
+            };
+            let state = repacketizer.start().with_packet(packet)?;
+            let mut output = Vec::new();
+            if output.try_reserve(packet.len() + (last - first) as usize).is_err() {
+                return Err(ErrorCode::alloc_fail());
+            }
+            let len = state.create_packet((first as u32, last as u32), output.spare_capacity_mut())?;
+            unsafe {
+                output.set_len(len);
+            }
+            result.packets.push(output);
+        }
+
+        Ok(result)
+    }
+
+    ///Cuts range `start..end` of playable samples (at 48 kHz) into new clip.
+    ///
+    ///Up to [PRE_ROLL](constant.PRE_ROLL.html) samples preceding `start` are kept and discarded via pre-skip,
+    ///so that decoder output converges by the beginning of the clip.
+    ///
+    ///Returns `ErrorCode::BadArg` if range is empty or exceeds clip's duration.
+    pub fn trim(&self, start: u64, end: u64) -> Result<Self, ErrorCode> {
+        if start >= end || end > self.duration()? {
+            return Err(ErrorCode::bad_arg());
+        }
+
+        let pre_skip = self.head.pre_skip as u64;
+        let start = start + pre_skip;
+        let end = end + pre_skip;
+        let cut = self.cut(start.saturating_sub(PRE_ROLL), end)?;
+
+        let mut head = self.head.clone();
+        head.pre_skip = match (start - cut.start).try_into() {
+            Ok(pre_skip) => pre_skip,
+            Err(_) => return Err(ErrorCode::bad_arg()),
+        };
+        Ok(Self {
+            head,
+            packets: cut.packets,
+            end_trim: cut.end - end,
+        })
+    }
+
+    ///Appends `other` clip, which must have the same channel layout and output gain.
+    ///
+    ///Ogg Opus can only discard samples at the beginning and at the end of stream,
+    ///hence end trimming of this clip and pre-skip of `other` are reduced to whole frames,
+    ///while remaining samples (less than a frame on either side) become part of the joined clip.
+    ///
+    ///Returns `ErrorCode::BadArg` if clips are incompatible.
+    pub fn append(&mut self, other: &Self) -> Result<(), ErrorCode> {
+        if self.head.channels != other.head.channels
+            || self.head.mapping_family != other.head.mapping_family
+            || self.head.stream_mapping != other.head.stream_mapping
+            || self.head.output_gain != other.head.output_gain
+        {
+            return Err(ErrorCode::bad_arg());
+        }
+        if self.packets.is_empty() {
+            self.head.pre_skip = other.head.pre_skip;
+            self.packets = other.packets.clone();
+            self.end_trim = other.end_trim;
+            return Ok(());
+        }
+
+        let samples = self.samples()?;
+        let head = self.cut(0, samples.saturating_sub(self.end_trim))?;
+        let other_samples = other.samples()?;
+        let other_end = other_samples.saturating_sub(other.end_trim);
+        let tail = other.cut(other.head.pre_skip as u64, other_end)?;
+
+        self.packets = head.packets;
+        self.packets.extend(tail.packets);
+        self.end_trim = tail.end.saturating_sub(other_end);
+        Ok(())
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{sys, mem, ErrorCode, Application, Channels, SampleRate, Bandwidth, Bitrate, Signal, InbandFec, FrameDuration};
+use crate::{sys, mem, utils, ErrorCode, Application, Channels, SampleRate, Bandwidth, Bitrate, Signal, InbandFec, FrameDuration};
 
 use mem::alloc::vec::Vec;
 
@@ -6,6 +6,7 @@ use mem::alloc::vec::Vec;
 pub struct Encoder {
     inner: mem::Unique<sys::OpusEncoder>,
     channels: Channels,
+    scratch: Vec<f32>,
 }
 
 impl Encoder {
@@ -23,6 +24,7 @@ impl Encoder {
             Some(inner) => Encoder {
                 inner,
                 channels,
+                scratch: Vec::new(),
             },
             None => return Err(ErrorCode::AllocFail)
         };
@@ -133,6 +135,53 @@ impl Encoder {
         Ok(result)
     }
 
+    ///Encodes an Opus frame from planar input, returning number of bytes written.
+    ///
+    ///`input` must contain slice per channel, all of the same length.
+    ///Samples are interleaved into internal buffer, which is reused between calls.
+    ///
+    ///Returns `ErrorCode::BadArg` if number of slices doesn't match number of channels or their lengths differ.
+    ///
+    ///Refer to `encode_float_to` for details
+    pub fn encode_float_planar_to(&mut self, input: &[&[f32]], output: &mut [mem::MaybeUninit<u8>]) -> Result<usize, ErrorCode> {
+        if input.len() != self.channels as usize {
+            return Err(ErrorCode::bad_arg());
+        }
+
+        let mut scratch = core::mem::take(&mut self.scratch);
+        let result = match utils::interleave(input, &mut scratch) {
+            Ok(_) => self.encode_float_to(&scratch, output),
+            Err(error) => Err(error),
+        };
+        self.scratch = scratch;
+        result
+    }
+
+    #[inline(always)]
+    ///Encodes an Opus frame from planar input, returning number of bytes written.
+    ///
+    ///Refer to `encode_float_planar_to` for details
+    pub fn encode_float_planar_to_slice(&mut self, input: &[&[f32]], output: &mut [u8]) -> Result<usize, ErrorCode> {
+        self.encode_float_planar_to(input, unsafe { mem::transmute(output) })
+    }
+
+    #[inline(always)]
+    ///Encodes an Opus frame from planar input, returning number of bytes written.
+    ///
+    ///Vector will be written into spare capacity, modifying its length on success.
+    ///
+    ///It is user responsibility to reserve correct amount of space
+    ///
+    ///Refer to `encode_float_planar_to` for details
+    pub fn encode_float_planar_to_vec(&mut self, input: &[&[f32]], output: &mut Vec<u8>) -> Result<usize, ErrorCode> {
+        let initial_len = output.len();
+        let result = self.encode_float_planar_to(input, output.spare_capacity_mut())?;
+        unsafe {
+            output.set_len(initial_len + result);
+        }
+        Ok(result)
+    }
+
     #[inline]
     ///Resets state to initial state
     pub fn reset(&mut self) -> Result<(), ErrorCode> {
@@ -159,6 +208,20 @@ impl Encoder {
         })
     }
 
+    #[inline]
+    ///Gets the final state of the codec's entropy coder.
+    ///
+    ///This is used for testing purposes: the encoder and decoder state should be identical after coding a payload
+    ///(assuming no data corruption or software bugs).
+    pub fn get_final_range(&mut self) -> Result<u32, ErrorCode> {
+        let mut value: u32 = 0;
+        let result = unsafe {
+            sys::opus_encoder_ctl(self.inner.as_mut(), sys::OPUS_GET_FINAL_RANGE_REQUEST, &mut value)
+        };
+
+        map_sys_error!(result => value)
+    }
+
     #[inline]
     ///Gets the encoder's bitrate configuration.
     pub fn get_bitrate(&mut self) -> Result<Bitrate, ErrorCode> {
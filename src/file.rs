@@ -0,0 +1,960 @@
+//! Ogg Opus file adapters
+//!
+//![OpusFileReader](struct.OpusFileReader.html) decodes Ogg Opus stream from `std::io::Read` source,
+//!taking care of channel mapping, output gain, pre-skip and end trimming,
+//!so that output contains exactly the audio that was originally encoded.
+//!Chained and multiplexed streams are supported.
+//!
+//![OpusFileWriter](struct.OpusFileWriter.html) encodes PCM into Ogg Opus stream written into `std::io::Write` sink.
+//!
+//!Decoded audio is always produced at 48 kHz.
+
+extern crate std;
+
+use std::io::{self, Read, Write, Seek, SeekFrom};
+
+use crate::header::{OpusHead, OpusTags, StreamMapping, OPUS_HEAD_MAGIC};
+use crate::ogg::{self, Assembler, Page, PacketInfo, NO_GRANULE};
+use crate::{mem, multistream, utils, Encoder, Decoder, ErrorCode, SampleRate, Channels};
+
+use mem::alloc::vec::Vec;
+
+//Header with full segment table and maximum body
+const MAX_PAGE_SIZE: usize = ogg::HEADER_SIZE + 255 + 255 * 255;
+//120ms at 48kHz
+const MAX_FRAME_SIZE: usize = 5760;
+//2.5ms at 48kHz
+const LOSS_UNIT: usize = 120;
+const MAX_PACKET_SIZE: usize = 1275 * 6 + 7;
+///Minimum number of samples (at 48 kHz) decoded and discarded before seek target
+pub const SEEK_PRE_ROLL: u64 = 3840;
+
+fn opus_error(error: ErrorCode) -> io::Error {
+    let kind = match error {
+        ErrorCode::BadArg => io::ErrorKind::InvalidInput,
+        _ => io::ErrorKind::InvalidData,
+    };
+    io::Error::new(kind, error.message())
+}
+
+//Reads into buffer until it is full, returning number of bytes read
+fn read_full<R: Read>(reader: &mut R, mut buf: &mut [u8]) -> io::Result<usize> {
+    let mut total = 0;
+    while !buf.is_empty() {
+        match reader.read(buf) {
+            Ok(0) => break,
+            Ok(size) => {
+                total += size;
+                buf = &mut buf[size..];
+            },
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+            Err(error) => return Err(error),
+        }
+    }
+    Ok(total)
+}
+
+//Finds first intact page of stream `serial` with granule position, returning its offset, size and granule position
+fn find_page(chunk: &[u8], serial: u32) -> Option<(usize, usize, u64)> {
+    let mut pos = 0;
+    while let Some(offset) = chunk[pos..].windows(4).position(|window| window == ogg::CAPTURE_PATTERN) {
+        pos += offset;
+        if let Ok((page, size)) = Page::parse(&chunk[pos..]) {
+            if ogg::page_checksum(&chunk[pos..pos + size]) == page.checksum {
+                if page.serial == serial && page.granule_position != NO_GRANULE {
+                    return Some((pos, size, page.granule_position));
+                }
+                pos += size;
+                continue;
+            }
+        }
+        pos += 1;
+    }
+    None
+}
+
+fn create_multistream<const CH: usize>(mapping: &StreamMapping) -> Result<multistream::Decoder, ErrorCode> {
+    let mut channels = [0u8; CH];
+    if mapping.mapping.len() != CH {
+        return Err(ErrorCode::invalid_packet());
+    }
+    channels.copy_from_slice(&mapping.mapping);
+
+    match multistream::Config::try_new(mapping.streams, mapping.coupled_streams, channels) {
+        Some(config) => multistream::Decoder::new(config, SampleRate::Hz48000),
+        None => Err(ErrorCode::invalid_packet()),
+    }
+}
+
+enum StreamDecoder {
+    Single(Decoder),
+    Multi(multistream::Decoder),
+}
+
+impl StreamDecoder {
+    fn new(head: &OpusHead) -> Result<Self, ErrorCode> {
+        let mut decoder = match (head.mapping_family, head.stream_mapping.as_ref()) {
+            (0, _) => match head.channels {
+                1 => Self::Single(Decoder::new(Channels::Mono, SampleRate::Hz48000)?),
+                2 => Self::Single(Decoder::new(Channels::Stereo, SampleRate::Hz48000)?),
+                _ => return Err(ErrorCode::invalid_packet()),
+            },
+            (_, Some(mapping)) => Self::Multi(match head.channels {
+                1 => create_multistream::<1>(mapping)?,
+                2 => create_multistream::<2>(mapping)?,
+                3 => create_multistream::<3>(mapping)?,
+                4 => create_multistream::<4>(mapping)?,
+                5 => create_multistream::<5>(mapping)?,
+                6 => create_multistream::<6>(mapping)?,
+                7 => create_multistream::<7>(mapping)?,
+                8 => create_multistream::<8>(mapping)?,
+                _ => return Err(ErrorCode::Unimplemented),
+            }),
+            (_, None) => return Err(ErrorCode::invalid_packet()),
+        };
+
+        match decoder {
+            Self::Single(ref mut decoder) => decoder.set_gain(head.output_gain as _)?,
+            Self::Multi(ref mut decoder) => decoder.set_gain(head.output_gain as _)?,
+        }
+        Ok(decoder)
+    }
+
+    fn decode(&mut self, input: &[u8], output: &mut [f32]) -> Result<usize, ErrorCode> {
+        match self {
+            Self::Single(decoder) => decoder.decode_float_to_slice(input, output, false),
+            Self::Multi(decoder) => decoder.decode_float_to_slice(input, output, false),
+        }
+    }
+
+    fn reset(&mut self) -> Result<(), ErrorCode> {
+        match self {
+            Self::Single(decoder) => decoder.reset(),
+            Self::Multi(decoder) => decoder.reset(),
+        }
+    }
+
+    fn last_packet_duration(&mut self) -> Result<u32, ErrorCode> {
+        match self {
+            Self::Single(decoder) => decoder.get_last_packet_duration(),
+            Self::Multi(decoder) => decoder.get_last_packet_duration(),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct PageHeader {
+    header_type: u8,
+    granule_position: u64,
+    serial: u32,
+    sequence: u32,
+}
+
+impl PageHeader {
+    #[inline(always)]
+    fn is_bos(&self) -> bool {
+        self.header_type & ogg::FLAG_BOS != 0
+    }
+}
+
+//Reads from `leftover` first, then from `inner`
+fn read_buffered<R: Read>(inner: &mut R, leftover: &mut Vec<u8>, buf: &mut [u8]) -> io::Result<usize> {
+    let len = core::cmp::min(leftover.len(), buf.len());
+    buf[..len].copy_from_slice(&leftover[..len]);
+    leftover.drain(..len);
+    Ok(len + read_full(inner, &mut buf[len..])?)
+}
+
+//Packet reader over pages of single logical stream
+//
+//Pages with invalid checksum are skipped, resynchronizing on the next capture pattern.
+//Packets are assembled from pages of the stream by `ogg::Assembler`.
+struct OggStream<R> {
+    inner: R,
+    //Bytes read ahead while resynchronizing
+    leftover: Vec<u8>,
+    //Offset of the next byte to read
+    offset: u64,
+    //Offset of the last read page
+    page_offset: u64,
+    serial: u32,
+    page: Vec<u8>,
+    header_type: u8,
+    granule_position: u64,
+    //Page buffer holds page that is to be returned again by `read_page`
+    is_unread: bool,
+    //Within leading BOS pages of the link
+    is_headers: bool,
+    assembler: Assembler,
+}
+
+impl<R: Read> OggStream<R> {
+    fn new(inner: R, offset: u64) -> Self {
+        Self {
+            inner,
+            leftover: Vec::new(),
+            offset,
+            page_offset: offset,
+            serial: 0,
+            page: Vec::new(),
+            header_type: 0,
+            granule_position: NO_GRANULE,
+            is_unread: false,
+            is_headers: false,
+            assembler: Assembler::new(None),
+        }
+    }
+
+    #[inline(always)]
+    fn segments(&self) -> &[u8] {
+        &self.page[ogg::HEADER_SIZE..ogg::HEADER_SIZE + self.page[26] as usize]
+    }
+
+    #[inline(always)]
+    fn body(&self) -> &[u8] {
+        &self.page[ogg::HEADER_SIZE + self.page[26] as usize..]
+    }
+
+    fn page_header(&self) -> PageHeader {
+        let mut granule = [0; 8];
+        granule.copy_from_slice(&self.page[6..14]);
+        PageHeader {
+            header_type: self.page[5],
+            granule_position: u64::from_le_bytes(granule),
+            serial: u32::from_le_bytes([self.page[14], self.page[15], self.page[16], self.page[17]]),
+            sequence: u32::from_le_bytes([self.page[18], self.page[19], self.page[20], self.page[21]]),
+        }
+    }
+
+    //Reads next intact page of any stream into internal buffer, returning `None` at the end of input
+    fn read_page(&mut self) -> io::Result<Option<PageHeader>> {
+        if self.is_unread {
+            self.is_unread = false;
+            return Ok(Some(self.page_header()));
+        }
+
+        loop {
+            self.page_offset = self.offset;
+            self.page.resize(ogg::HEADER_SIZE, 0);
+            let mut filled = read_buffered(&mut self.inner, &mut self.leftover, &mut self.page)?;
+            self.offset += filled as u64;
+            if filled < ogg::HEADER_SIZE {
+                //Truncated page at the end of input is dropped
+                return Ok(None);
+            }
+
+            if self.page[..4] == ogg::CAPTURE_PATTERN {
+                let segments_len = self.page[26] as usize;
+                self.page.resize(ogg::HEADER_SIZE + segments_len, 0);
+                let len = read_buffered(&mut self.inner, &mut self.leftover, &mut self.page[ogg::HEADER_SIZE..])?;
+                self.offset += len as u64;
+                filled += len;
+
+                if filled == self.page.len() {
+                    let body_len = self.segments().iter().map(|len| *len as usize).sum::<usize>();
+                    self.page.resize(filled + body_len, 0);
+                    let len = read_buffered(&mut self.inner, &mut self.leftover, &mut self.page[filled..])?;
+                    self.offset += len as u64;
+                    filled += len;
+
+                    if filled == self.page.len() && Page::parse_checked(&self.page).is_ok() {
+                        return Ok(Some(self.page_header()));
+                    }
+                }
+            }
+
+            //Resynchronize on the next capture pattern, keeping bytes after it for the next attempt.
+            //If there is none, keep the tail as it might be the beginning of capture pattern
+            let data = &self.page[1..filled];
+            let start = match data.windows(4).position(|window| window == ogg::CAPTURE_PATTERN) {
+                Some(idx) => idx + 1,
+                None => core::cmp::max(filled.saturating_sub(3), 1),
+            };
+            let mut leftover = self.page[start..filled].to_vec();
+            leftover.extend_from_slice(&self.leftover);
+            self.offset -= (filled - start) as u64;
+            self.leftover = leftover;
+        }
+    }
+
+    //Reads pages until page of this stream is found, returning false once stream ends
+    //
+    //Stream ends with its EOS page or with the beginning of the next chain link
+    fn next_page(&mut self) -> io::Result<bool> {
+        loop {
+            if self.header_type & ogg::FLAG_EOS != 0 {
+                return Ok(false);
+            }
+
+            let header = match self.read_page()? {
+                Some(header) => header,
+                None => return Ok(false),
+            };
+
+            if !header.is_bos() {
+                self.is_headers = false;
+            } else if !self.is_headers && header.serial != self.serial {
+                //Next link started without previous one being properly terminated
+                self.is_unread = true;
+                return Ok(false);
+            }
+            if header.serial != self.serial {
+                continue;
+            }
+
+            let (page, _) = Page::parse(&self.page).map_err(opus_error)?;
+            self.assembler.add_page(&page);
+            self.header_type = header.header_type;
+            self.granule_position = header.granule_position;
+            return Ok(true);
+        }
+    }
+
+    //Moves to the page boundary at `offset`, discarding current page
+    fn seek(&mut self, offset: u64) -> io::Result<()> where R: Seek {
+        self.inner.seek(SeekFrom::Start(offset))?;
+        self.leftover.clear();
+        self.offset = offset;
+        self.header_type = 0;
+        self.is_unread = false;
+        self.is_headers = false;
+        self.assembler = Assembler::new(None);
+        Ok(())
+    }
+
+    //Reads until first page of the next Opus stream, returning its identification header
+    fn find_head(&mut self) -> io::Result<Option<OpusHead>> {
+        loop {
+            let header = match self.read_page()? {
+                Some(header) if header.is_bos() && self.body().starts_with(&OPUS_HEAD_MAGIC) => header,
+                Some(_) => continue,
+                None => return Ok(None),
+            };
+
+            self.serial = header.serial;
+            self.header_type = 0;
+            self.is_headers = true;
+            self.assembler = Assembler::new(Some(header.sequence.wrapping_add(1)));
+            return OpusHead::parse(self.body()).map(Some).map_err(opus_error);
+        }
+    }
+
+    //Reads stream headers of the next Opus stream
+    fn read_headers(&mut self) -> io::Result<Option<(OpusHead, OpusTags)>> {
+        let head = match self.find_head()? {
+            Some(head) => head,
+            None => return Ok(None),
+        };
+        if self.next_packet()?.is_none() {
+            return Err(opus_error(ErrorCode::invalid_packet()));
+        }
+        self.assembler.end_headers().map_err(opus_error)?;
+        let tags = OpusTags::parse(self.assembler.packet()).map_err(opus_error)?;
+        Ok(Some((head, tags)))
+    }
+
+    #[inline(always)]
+    //Returns data of the last read packet
+    fn packet(&self) -> &[u8] {
+        self.assembler.packet()
+    }
+
+    //Reads next packet into internal buffer
+    fn next_packet(&mut self) -> io::Result<Option<PacketInfo>> {
+        loop {
+            //Assembler has no segments left unless page buffer holds the page last passed to it
+            if let Ok((page, _)) = Page::parse(&self.page) {
+                if let Some(info) = self.assembler.next_packet(&page) {
+                    return Ok(Some(info));
+                }
+            }
+            if !self.next_page()? {
+                return Ok(None);
+            }
+        }
+    }
+}
+
+///Information about link of chained stream
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Link {
+    ///Serial number of Opus logical stream
+    pub serial: u32,
+    ///Offset of the link's first page
+    pub offset: u64,
+    ///Identification header
+    pub head: OpusHead,
+    ///Comment header
+    pub tags: OpusTags,
+    ///Duration in samples per channel, excluding pre-skip
+    pub duration: u64,
+}
+
+///Ogg Opus file reader
+///
+///Supports mono/stereo streams as well as multistream up to 8 channels.
+///
+///Chained streams (e.g. internet radio recordings) are read link after link, re-creating decoder for each link.
+///Within link only the first Opus logical stream is decoded, while pages of other multiplexed streams (e.g. Skeleton) are skipped.
+///
+///It is recommended to wrap `R` into `std::io::BufReader` as pages are read in small chunks.
+pub struct OpusFileReader<R> {
+    stream: OggStream<R>,
+    link: usize,
+    head: OpusHead,
+    tags: OpusTags,
+    decoder: StreamDecoder,
+    channels: usize,
+    //Offset of the first audio page
+    data_start: u64,
+    //Granule position of the next decoded sample
+    position: u64,
+    //Granule position of the first sample to output
+    start_position: u64,
+    //Granule position of the first sample in pcm buffer, or of the next sample to output if it is empty
+    pcm_position: u64,
+    pcm: Vec<f32>,
+    pcm_pos: usize,
+    pcm_end: usize,
+    is_finished: bool,
+    //Number of samples to conceal before decoding pending packet
+    lost: u64,
+    pending: Option<PacketInfo>,
+}
+
+impl<R: Read + Seek> OpusFileReader<R> {
+    ///Creates new reader, parsing stream headers
+    ///
+    ///Returns `io::ErrorKind::InvalidData` if input contains no valid Opus stream
+    pub fn new(mut inner: R) -> io::Result<Self> {
+        let offset = inner.stream_position()?;
+        let mut stream = OggStream::new(inner, offset);
+        let (head, tags) = match stream.read_headers()? {
+            Some(headers) => headers,
+            None => return Err(opus_error(ErrorCode::invalid_packet())),
+        };
+        //Comment header always ends its page, hence audio starts at the page boundary
+        let data_start = stream.offset;
+        let decoder = StreamDecoder::new(&head).map_err(opus_error)?;
+        let channels = head.channels as usize;
+
+        Ok(Self {
+            stream,
+            link: 0,
+            tags,
+            decoder,
+            channels,
+            data_start,
+            position: 0,
+            start_position: head.pre_skip as u64,
+            pcm_position: head.pre_skip as u64,
+            pcm: mem::alloc::vec![0.0; MAX_FRAME_SIZE * channels],
+            pcm_pos: 0,
+            pcm_end: 0,
+            is_finished: false,
+            lost: 0,
+            pending: None,
+            head,
+        })
+    }
+
+    //Moves to the next link of chained stream, returning false if there is none
+    fn next_link(&mut self) -> io::Result<bool> {
+        let (head, tags) = match self.stream.read_headers()? {
+            Some(headers) => headers,
+            None => return Ok(false),
+        };
+        self.decoder = StreamDecoder::new(&head).map_err(opus_error)?;
+        self.data_start = self.stream.offset;
+        self.channels = head.channels as usize;
+        self.pcm.resize(MAX_FRAME_SIZE * self.channels, 0.0);
+        self.position = 0;
+        self.start_position = head.pre_skip as u64;
+        self.pcm_position = head.pre_skip as u64;
+        self.pcm_pos = 0;
+        self.pcm_end = 0;
+        self.is_finished = false;
+        self.lost = 0;
+        self.pending = None;
+        self.link += 1;
+        self.head = head;
+        self.tags = tags;
+        Ok(true)
+    }
+
+    #[inline(always)]
+    ///Returns index of the current link within chained stream
+    pub fn link(&self) -> usize {
+        self.link
+    }
+
+    ///Scans whole input, returning information about every link of chained stream.
+    ///
+    ///Reading position is restored afterwards.
+    pub fn links(&mut self) -> io::Result<Vec<Link>> {
+        let current = self.stream.inner.stream_position()?;
+        self.stream.inner.seek(SeekFrom::Start(0))?;
+
+        let mut result = Vec::new();
+        let mut link: Option<Link> = None;
+        let mut tags = Vec::new();
+        let mut is_tags_done = false;
+        let mut is_headers = false;
+        let mut link_offset = 0;
+        let mut scan = OggStream::new(&mut self.stream.inner, 0);
+        while let Some(page) = scan.read_page()? {
+            if page.is_bos() {
+                if !is_headers {
+                    is_headers = true;
+                    link_offset = scan.page_offset;
+                    result.extend(link.take());
+                }
+                if link.is_none() && scan.body().starts_with(&OPUS_HEAD_MAGIC) {
+                    link = Some(Link {
+                        serial: page.serial,
+                        offset: link_offset,
+                        head: OpusHead::parse(scan.body()).map_err(opus_error)?,
+                        tags: OpusTags::new(),
+                        duration: 0,
+                    });
+                    tags.clear();
+                    is_tags_done = false;
+                }
+            } else {
+                is_headers = false;
+                if let Some(link) = link.as_mut().filter(|link| link.serial == page.serial) {
+                    let mut body = scan.body();
+                    for len in scan.segments().iter().map(|len| *len as usize) {
+                        if is_tags_done {
+                            break;
+                        }
+                        tags.extend_from_slice(&body[..len]);
+                        body = &body[len..];
+                        if len < 255 {
+                            link.tags = OpusTags::parse(&tags).map_err(opus_error)?;
+                            is_tags_done = true;
+                        }
+                    }
+
+                    if page.granule_position != NO_GRANULE {
+                        link.duration = page.granule_position.saturating_sub(link.head.pre_skip as u64);
+                    }
+                }
+            }
+        }
+        result.extend(link);
+
+        self.stream.inner.seek(SeekFrom::Start(current))?;
+        Ok(result)
+    }
+
+    #[inline(always)]
+    ///Returns identification header of the current link
+    pub fn head(&self) -> &OpusHead {
+        &self.head
+    }
+
+    #[inline(always)]
+    ///Returns comment header of the current link
+    pub fn tags(&self) -> &OpusTags {
+        &self.tags
+    }
+
+    #[inline(always)]
+    ///Returns number of output channels of the current link
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    #[inline(always)]
+    ///Returns output sample rate
+    pub fn sample_rate(&self) -> SampleRate {
+        SampleRate::Hz48000
+    }
+
+    ///Returns duration of the current link in samples per channel, excluding pre-skip.
+    ///
+    ///Determined by granule position of the last page, hence requires seeking to the end of input.
+    ///Reading position is restored afterwards.
+    pub fn duration(&mut self) -> io::Result<u64> {
+        let inner = &mut self.stream.inner;
+        let current = inner.stream_position()?;
+        let file_end = inner.seek(SeekFrom::End(0))?;
+
+        let mut result = None;
+        let mut end = file_end;
+        let mut chunk = Vec::new();
+        while result.is_none() && end > self.data_start {
+            //Each chunk overlaps previous one by maximum page size, so that no page is cut
+            let start = end.saturating_sub(MAX_PAGE_SIZE as u64 * 2);
+            let chunk_end = core::cmp::min(end + MAX_PAGE_SIZE as u64, file_end);
+            chunk.resize((chunk_end - start) as usize, 0);
+            inner.seek(SeekFrom::Start(start))?;
+            inner.read_exact(&mut chunk)?;
+
+            let mut pos = 0;
+            while let Some((offset, size, granule)) = find_page(&chunk[pos..], self.stream.serial) {
+                if start + ((pos + offset) as u64) < end {
+                    result = Some(granule);
+                }
+                pos += offset + size;
+            }
+            end = start;
+        }
+
+        inner.seek(SeekFrom::Start(current))?;
+        match result {
+            Some(granule) => Ok(granule.saturating_sub(self.head.pre_skip as u64)),
+            None => Err(opus_error(ErrorCode::invalid_packet())),
+        }
+    }
+
+    ///Returns position of the next sample to be read within the current link, in samples per channel excluding pre-skip.
+    pub fn position(&self) -> u64 {
+        let position = self.pcm_position + (self.pcm_pos / self.channels) as u64;
+        position.saturating_sub(self.head.pre_skip as u64)
+    }
+
+    ///Seeks to the `sample` within the current link, specified in samples per channel excluding pre-skip.
+    ///
+    ///Pages are located by bisection over granule positions.
+    ///Decoding starts at least [SEEK_PRE_ROLL](constant.SEEK_PRE_ROLL.html) samples before target to let decoder converge,
+    ///and pre-roll output is discarded, so that next read returns exactly the sample at requested position.
+    ///
+    ///Seeking beyond the end of stream results in subsequent reads returning 0.
+    pub fn seek(&mut self, sample: u64) -> io::Result<()> {
+        let target = sample.saturating_add(self.head.pre_skip as u64);
+        let goal = target.saturating_sub(SEEK_PRE_ROLL);
+        let serial = self.stream.serial;
+        let file_end = self.stream.inner.seek(SeekFrom::End(0))?;
+
+        //Offset right after the last page whose granule position is not beyond goal, and its granule position
+        let mut best = (self.data_start, 0);
+        let mut low = self.data_start;
+        let mut high = file_end;
+        let mut chunk = Vec::new();
+        while high - low > MAX_PAGE_SIZE as u64 {
+            let middle = low + (high - low) / 2;
+            let chunk_end = core::cmp::min(middle + MAX_PAGE_SIZE as u64 * 2, file_end);
+            chunk.resize((chunk_end - middle) as usize, 0);
+            self.stream.inner.seek(SeekFrom::Start(middle))?;
+            self.stream.inner.read_exact(&mut chunk)?;
+
+            match find_page(&chunk, serial) {
+                Some((offset, size, granule)) if granule <= goal && middle + (offset as u64) < high => {
+                    low = middle + (offset + size) as u64;
+                    best = (low, granule);
+                },
+                _ => high = middle,
+            }
+        }
+
+        //Remaining range is small enough to scan page by page
+        let chunk_end = core::cmp::min(high + MAX_PAGE_SIZE as u64, file_end);
+        chunk.resize((chunk_end - low) as usize, 0);
+        self.stream.inner.seek(SeekFrom::Start(low))?;
+        self.stream.inner.read_exact(&mut chunk)?;
+        let mut pos = 0;
+        while let Some((offset, size, granule)) = find_page(&chunk[pos..], serial) {
+            if granule > goal {
+                break;
+            }
+            pos += offset + size;
+            best = (low + pos as u64, granule);
+        }
+
+        let (offset, mut position) = best;
+        if offset != self.data_start {
+            position = self.page_start_position(offset, position)?;
+        }
+
+        self.stream.seek(offset)?;
+        self.decoder.reset().map_err(opus_error)?;
+        self.position = position;
+        self.start_position = core::cmp::max(target, self.head.pre_skip as u64);
+        self.pcm_position = self.start_position;
+        self.pcm_pos = 0;
+        self.pcm_end = 0;
+        self.is_finished = false;
+        self.lost = 0;
+        self.pending = None;
+        Ok(())
+    }
+
+    //Determines granule position of the first packet to be returned when reading from page boundary at `offset`
+    //
+    //Packet continued from previous page is skipped, so position is derived from granule position of the first page
+    //on which packet ends, minus duration of packets ending on it.
+    fn page_start_position(&mut self, offset: u64, previous_granule: u64) -> io::Result<u64> {
+        self.stream.seek(offset)?;
+        let mut duration = 0;
+        while let Some(info) = self.stream.next_packet()? {
+            if !self.stream.packet().is_empty() {
+                duration += utils::get_nb_samples(self.stream.packet(), SampleRate::Hz48000).map_err(opus_error)? as u64;
+            }
+
+            if let Some(granule) = info.granule_position {
+                //End trimming makes final granule position unreliable
+                if self.stream.header_type & ogg::FLAG_EOS != 0 {
+                    break;
+                }
+                return Ok(granule.saturating_sub(duration));
+            }
+        }
+        Ok(previous_granule)
+    }
+
+    //Decodes next packet, returning false at the end of stream
+    fn fill(&mut self) -> io::Result<bool> {
+        loop {
+            let len = match self.lost {
+                0 => {
+                    let info = match self.pending.take() {
+                        Some(info) => info,
+                        None if self.is_finished => return Ok(false),
+                        None => match self.stream.next_packet()? {
+                            Some(info) if info.lost_samples > 0 => {
+                                //Conceal audio of damaged pages before decoding packet
+                                self.lost = info.lost_samples;
+                                self.pending = Some(info);
+                                continue;
+                            },
+                            Some(info) => info,
+                            None => {
+                                self.is_finished = true;
+                                return Ok(false);
+                            }
+                        },
+                    };
+
+                    let output = match self.stream.packet().len() {
+                        0 => {
+                            let duration = self.decoder.last_packet_duration().map_err(opus_error)? as usize;
+                            &mut self.pcm[..duration * self.channels]
+                        },
+                        _ => &mut self.pcm[..],
+                    };
+                    if info.is_last {
+                        self.is_finished = true;
+                    }
+                    self.decoder.decode(self.stream.packet(), output).map_err(opus_error)?
+                },
+                lost => {
+                    //Decoder conceals only multiple of 2.5ms, hence excess is discarded
+                    let len = core::cmp::min(lost, MAX_FRAME_SIZE as u64) as usize;
+                    let conceal_len = len.div_ceil(LOSS_UNIT) * LOSS_UNIT;
+                    self.decoder.decode(&[], &mut self.pcm[..conceal_len * self.channels]).map_err(opus_error)?;
+                    self.lost -= len as u64;
+                    len
+                },
+            };
+
+            //Discard pre-skip and anything beyond final granule position
+            let start = self.position;
+            self.position += len as u64;
+            let begin = self.start_position.saturating_sub(start).min(len as u64) as usize;
+            //End trimming may span several packets of the final page
+            let end = match self.stream.header_type & ogg::FLAG_EOS {
+                0 => len,
+                _ => self.stream.granule_position.saturating_sub(start).min(len as u64) as usize,
+            };
+
+            if begin < end {
+                self.pcm_position = start;
+                self.pcm_pos = begin * self.channels;
+                self.pcm_end = end * self.channels;
+                return Ok(true);
+            }
+        }
+    }
+
+    //Makes sure there is decoded output, moving to the next link if current one is over
+    fn fill_any(&mut self) -> io::Result<bool> {
+        while self.pcm_pos == self.pcm_end && !self.fill()? {
+            if !self.next_link()? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    fn read_with<T>(&mut self, output: &mut [T], convert: impl Fn(f32) -> T) -> io::Result<usize> {
+        if !self.fill_any()? {
+            return Ok(0);
+        }
+
+        //Samples of different links are never mixed
+        let output_len = output.len() - output.len() % self.channels;
+        let mut written = 0;
+        while written < output_len {
+            if self.pcm_pos == self.pcm_end && !self.fill()? {
+                break;
+            }
+
+            let len = core::cmp::min(output_len - written, self.pcm_end - self.pcm_pos);
+            for (out, sample) in output[written..written + len].iter_mut().zip(&self.pcm[self.pcm_pos..self.pcm_pos + len]) {
+                *out = convert(*sample);
+            }
+            written += len;
+            self.pcm_pos += len;
+        }
+
+        Ok(written / self.channels)
+    }
+
+    #[inline]
+    ///Reads interleaved 16bit PCM, returning number of samples per channel written.
+    ///
+    ///Single call never returns samples of different links, so that change of [channels](#method.channels) can be detected between calls.
+    ///
+    ///Returns 0 at the end of stream.
+    pub fn read(&mut self, output: &mut [u16]) -> io::Result<usize> {
+        self.read_with(output, utils::float_to_i16)
+    }
+
+    #[inline]
+    ///Reads interleaved float PCM, returning number of samples per channel written.
+    ///
+    ///Single call never returns samples of different links, so that change of [channels](#method.channels) can be detected between calls.
+    ///
+    ///Returns 0 at the end of stream.
+    pub fn read_float(&mut self, output: &mut [f32]) -> io::Result<usize> {
+        self.read_with(output, core::convert::identity)
+    }
+
+    ///Reads the rest of the current link as interleaved 16bit PCM, returning number of samples per channel appended.
+    ///
+    ///If current link is already over, reads the next one. Returns 0 at the end of stream.
+    pub fn read_to_vec(&mut self, output: &mut Vec<u16>) -> io::Result<usize> {
+        if !self.fill_any()? {
+            return Ok(0);
+        }
+
+        let mut total = 0;
+        while self.pcm_pos < self.pcm_end || self.fill()? {
+            let start = output.len();
+            output.extend(self.pcm[self.pcm_pos..self.pcm_end].iter().map(|sample| utils::float_to_i16(*sample)));
+            total += (output.len() - start) / self.channels;
+            self.pcm_pos = self.pcm_end;
+        }
+        Ok(total)
+    }
+
+    #[inline(always)]
+    ///Returns underlying reader
+    pub fn into_inner(self) -> R {
+        self.stream.inner
+    }
+}
+
+///Ogg Opus file writer
+///
+///Encodes PCM using provided encoder. Frame duration is taken from encoder's settings (20ms by default).
+///
+///Stream must be completed with [finish](#method.finish), otherwise the last packets are lost and stream lacks end marker.
+pub struct OpusFileWriter<W: Write> {
+    inner: W,
+    encoder: Encoder,
+    writer: ogg::Writer,
+    pre_skip: usize,
+    scale: usize,
+    frame_len: usize,
+    input_len: u64,
+    pending: Vec<f32>,
+    packet: Vec<u8>,
+}
+
+impl<W: Write> OpusFileWriter<W> {
+    ///Creates new writer, writing stream headers into `inner`.
+    ///
+    ///`serial` identifies logical stream and should be random.
+    pub fn new(mut inner: W, mut encoder: Encoder, tags: &OpusTags, serial: u32) -> io::Result<Self> {
+        let head = OpusHead::from_encoder(&mut encoder).map_err(opus_error)?;
+        let frame_len = utils::frame_size(&mut encoder).map_err(opus_error)? * encoder.channels() as usize;
+        let scale = SampleRate::Hz48000 as usize / encoder.get_sample_rate().map_err(opus_error)? as usize;
+        let mut writer = ogg::Writer::new(&head, tags, serial);
+        inner.write_all(&writer.take_output())?;
+
+        Ok(Self {
+            inner,
+            encoder,
+            writer,
+            pre_skip: head.pre_skip as usize,
+            scale,
+            frame_len,
+            input_len: 0,
+            pending: Vec::new(),
+            packet: Vec::with_capacity(MAX_PACKET_SIZE),
+        })
+    }
+
+    #[inline(always)]
+    ///Access underlying encoder
+    pub fn encoder(&mut self) -> &mut Encoder {
+        &mut self.encoder
+    }
+
+    fn encode_pending(&mut self) -> io::Result<()> {
+        let mut offset = 0;
+        while self.pending.len() - offset >= self.frame_len {
+            self.packet.clear();
+            self.encoder.encode_float_to_vec(&self.pending[offset..offset + self.frame_len], &mut self.packet).map_err(opus_error)?;
+            self.writer.add_packet(&self.packet).map_err(opus_error)?;
+            offset += self.frame_len;
+        }
+        self.pending.drain(..offset);
+
+        let output = self.writer.take_output();
+        if !output.is_empty() {
+            self.inner.write_all(&output)?;
+        }
+        Ok(())
+    }
+
+    //Accounts `len` interleaved samples of input, which must hold whole number of samples per channel
+    fn add_input_len(&mut self, len: usize) -> io::Result<()> {
+        let channels = self.encoder.channels() as usize;
+        if len % channels != 0 {
+            return Err(opus_error(ErrorCode::bad_arg()));
+        }
+        self.input_len += (len / channels) as u64;
+        Ok(())
+    }
+
+    ///Writes interleaved 16bit PCM at encoder's sample rate
+    ///
+    ///Returns `io::ErrorKind::InvalidInput` if `input` length is not multiple of number of channels.
+    pub fn write(&mut self, input: &[u16]) -> io::Result<()> {
+        self.add_input_len(input.len())?;
+        self.pending.extend(input.iter().map(|sample| utils::i16_to_float(*sample)));
+        self.encode_pending()
+    }
+
+    ///Writes interleaved float PCM at encoder's sample rate
+    ///
+    ///Returns `io::ErrorKind::InvalidInput` if `input` length is not multiple of number of channels.
+    pub fn write_float(&mut self, input: &[f32]) -> io::Result<()> {
+        self.add_input_len(input.len())?;
+        self.pending.extend_from_slice(input);
+        self.encode_pending()
+    }
+
+    ///Flushes encoder's look ahead and completes stream, returning underlying writer
+    pub fn finish(mut self) -> io::Result<W> {
+        let channels = self.encoder.channels() as usize;
+        let frame_size = self.frame_len / channels;
+        let input_len = self.input_len as usize;
+        let padded_len = (input_len + self.pre_skip / self.scale).div_ceil(frame_size) * frame_size;
+
+        let pending_len = self.pending.len() / channels;
+        self.pending.resize(self.pending.len() + (padded_len - input_len) * channels, 0.0);
+        debug_assert_eq!((pending_len + padded_len - input_len) % frame_size, 0);
+        self.writer.set_end_trim(((padded_len - input_len) * self.scale - self.pre_skip) as u64);
+        self.encode_pending()?;
+
+        let mut inner = self.inner;
+        inner.write_all(&self.writer.finish())?;
+        inner.flush()?;
+        Ok(inner)
+    }
+}
@@ -0,0 +1,316 @@
+//! Async adapters
+//!
+//!Provides [EncodeSink](struct.EncodeSink.html) that accepts PCM and forwards encoded packets into underlying
+//![Sink](https://docs.rs/futures-sink/0.3/futures_sink/trait.Sink.html), and [DecodeStream](struct.DecodeStream.html)
+//!that decodes [Stream](https://docs.rs/futures-core/0.3/futures_core/stream/trait.Stream.html) of packets into PCM.
+//!
+//!Both are runtime agnostic and rely only on underlying sink/stream for backpressure.
+//!
+//!## Offloading
+//!
+//!Codec work is performed within poll methods, which is cheap for individual frame (in order of tens to hundreds of microseconds),
+//!but can add up with large chunks of PCM. Hence `EncodeSink` encodes at most [MAX_FRAMES_PER_POLL](constant.MAX_FRAMES_PER_POLL.html)
+//!frames before yielding back to executor, while `DecodeStream` decodes single packet per poll.
+//!
+//!If this is still too much for your executor, move adapter into dedicated task (e.g. `spawn_blocking` in case of tokio),
+//!which is possible as both `Encoder` and `Decoder` are `Send`.
+
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use core::fmt;
+
+use futures_core::Stream;
+use futures_sink::Sink;
+use bytes::Bytes;
+
+use crate::{mem, Encoder, Decoder, ErrorCode};
+
+use mem::alloc::vec::Vec;
+
+///Maximum number of frames `EncodeSink` encodes within single poll before yielding
+pub const MAX_FRAMES_PER_POLL: usize = 6;
+const MAX_PACKET_SIZE: usize = 1275 * 6 + 7;
+//Concealment duration used when decoder hasn't seen any packet yet
+const DEFAULT_LOSS_DURATION_MS: usize = 20;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+///Chunk of interleaved 16bit PCM samples
+pub struct PcmChunk(pub Vec<u16>);
+
+impl From<Vec<u16>> for PcmChunk {
+    #[inline(always)]
+    fn from(value: Vec<u16>) -> Self {
+        Self(value)
+    }
+}
+
+impl From<PcmChunk> for Vec<u16> {
+    #[inline(always)]
+    fn from(value: PcmChunk) -> Self {
+        value.0
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+///`EncodeSink` error
+pub enum SinkError<E> {
+    ///Encoder failure
+    Opus(ErrorCode),
+    ///Underlying sink failure
+    Inner(E),
+}
+
+impl<E: fmt::Display> fmt::Display for SinkError<E> {
+    #[inline]
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Opus(error) => fmt.write_str(error.message()),
+            Self::Inner(error) => fmt::Display::fmt(error, fmt),
+        }
+    }
+}
+
+impl<E> From<ErrorCode> for SinkError<E> {
+    #[inline(always)]
+    fn from(value: ErrorCode) -> Self {
+        Self::Opus(value)
+    }
+}
+
+#[inline(always)]
+fn is_valid_frame_size(rate: usize, frame_size: usize) -> bool {
+    //2.5, 5, 10, 20, 40, 60, 80, 100 or 120ms
+    let unit = rate / 400;
+    frame_size % unit == 0 && matches!(frame_size / unit, 1 | 2 | 4 | 8 | 16 | 24 | 32 | 40 | 48)
+}
+
+///Sink encoding PCM into packets, which are forwarded to underlying sink `S`
+///
+///PCM is buffered until there is enough samples for a frame, hence chunks can be of arbitrary size.
+///
+///- `poll_ready` completes only once every complete frame is accepted by underlying sink, propagating its backpressure.
+///- `poll_flush` encodes all complete frames, while incomplete frame remains buffered.
+///- `poll_close` pads incomplete frame with silence before encoding it.
+pub struct EncodeSink<S> {
+    encoder: Encoder,
+    inner: S,
+    frame_len: usize,
+    pending: Vec<u16>,
+    offset: usize,
+    packet: Vec<u8>,
+}
+
+impl<S> EncodeSink<S> {
+    ///Creates new instance, encoding frames of `frame_size` samples per channel.
+    ///
+    ///`frame_size` must correspond to encoder's sample rate.
+    ///For example, at 48 kHz allowed frame sizes are 120, 240, 480, 960, 1920, 2880, 3840, 4800 and 5760.
+    ///
+    ///Returns `ErrorCode::BadArg` if `frame_size` is invalid
+    pub fn new(mut encoder: Encoder, frame_size: usize, inner: S) -> Result<Self, ErrorCode> {
+        let rate = encoder.get_sample_rate()? as usize;
+        if !is_valid_frame_size(rate, frame_size) {
+            return Err(ErrorCode::bad_arg());
+        }
+
+        Ok(Self {
+            frame_len: frame_size * encoder.channels() as usize,
+            encoder,
+            inner,
+            pending: Vec::new(),
+            offset: 0,
+            packet: mem::alloc::vec![0; MAX_PACKET_SIZE],
+        })
+    }
+
+    #[inline(always)]
+    ///Access underlying encoder
+    pub fn encoder(&self) -> &Encoder {
+        &self.encoder
+    }
+
+    #[inline(always)]
+    ///Access underlying encoder
+    pub fn encoder_mut(&mut self) -> &mut Encoder {
+        &mut self.encoder
+    }
+
+    #[inline(always)]
+    ///Access underlying sink
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+
+    #[inline(always)]
+    ///Access underlying sink
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.inner
+    }
+
+    #[inline(always)]
+    ///Returns number of buffered samples per channel, which are not yet encoded
+    pub fn buffered(&self) -> usize {
+        (self.pending.len() - self.offset) / self.encoder.channels() as usize
+    }
+
+    #[inline]
+    ///Returns encoder and underlying sink, discarding buffered samples
+    pub fn into_inner(self) -> (Encoder, S) {
+        (self.encoder, self.inner)
+    }
+}
+
+impl<S: Sink<Bytes> + Unpin> EncodeSink<S> {
+    fn poll_encode(&mut self, cx: &mut Context<'_>, pad: bool) -> Poll<Result<(), SinkError<S::Error>>> {
+        let mut budget = MAX_FRAMES_PER_POLL;
+        loop {
+            let available = self.pending.len() - self.offset;
+            if available < self.frame_len {
+                if !pad || available == 0 {
+                    break;
+                }
+                self.pending.resize(self.offset + self.frame_len, 0);
+            }
+
+            if budget == 0 {
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+
+            match Pin::new(&mut self.inner).poll_ready(cx) {
+                Poll::Ready(Ok(())) => (),
+                Poll::Ready(Err(error)) => return Poll::Ready(Err(SinkError::Inner(error))),
+                Poll::Pending => return Poll::Pending,
+            }
+
+            let frame = &self.pending[self.offset..self.offset + self.frame_len];
+            let len = self.encoder.encode_to_slice(frame, &mut self.packet)?;
+            self.offset += self.frame_len;
+            budget -= 1;
+
+            if let Err(error) = Pin::new(&mut self.inner).start_send(Bytes::copy_from_slice(&self.packet[..len])) {
+                return Poll::Ready(Err(SinkError::Inner(error)));
+            }
+        }
+
+        self.pending.drain(..self.offset);
+        self.offset = 0;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<S: Sink<Bytes> + Unpin> Sink<PcmChunk> for EncodeSink<S> {
+    type Error = SinkError<S::Error>;
+
+    #[inline]
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.get_mut().poll_encode(cx, false)
+    }
+
+    #[inline]
+    fn start_send(self: Pin<&mut Self>, item: PcmChunk) -> Result<(), Self::Error> {
+        self.get_mut().pending.extend_from_slice(&item.0);
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let this = self.get_mut();
+        match this.poll_encode(cx, false) {
+            Poll::Ready(Ok(())) => Pin::new(&mut this.inner).poll_flush(cx).map_err(SinkError::Inner),
+            result => result,
+        }
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let this = self.get_mut();
+        match this.poll_encode(cx, true) {
+            Poll::Ready(Ok(())) => Pin::new(&mut this.inner).poll_close(cx).map_err(SinkError::Inner),
+            result => result,
+        }
+    }
+}
+
+///Stream decoding packets of underlying stream `S` into PCM
+///
+///Empty packet is treated as lost and is concealed using duration of last packet.
+pub struct DecodeStream<S> {
+    decoder: Decoder,
+    inner: S,
+    loss_duration: usize,
+    output: Vec<u16>,
+}
+
+impl<S> DecodeStream<S> {
+    ///Creates new instance
+    pub fn new(mut decoder: Decoder, inner: S) -> Result<Self, ErrorCode> {
+        let rate = decoder.get_sample_rate()? as usize;
+        Ok(Self {
+            output: mem::alloc::vec![0; rate / 1000 * 120 * decoder.channels() as usize],
+            decoder,
+            inner,
+            loss_duration: rate / 1000 * DEFAULT_LOSS_DURATION_MS,
+        })
+    }
+
+    #[inline(always)]
+    ///Access underlying decoder
+    pub fn decoder(&self) -> &Decoder {
+        &self.decoder
+    }
+
+    #[inline(always)]
+    ///Access underlying decoder
+    pub fn decoder_mut(&mut self) -> &mut Decoder {
+        &mut self.decoder
+    }
+
+    #[inline(always)]
+    ///Access underlying stream
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+
+    #[inline(always)]
+    ///Access underlying stream
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.inner
+    }
+
+    #[inline]
+    ///Returns decoder and underlying stream
+    pub fn into_inner(self) -> (Decoder, S) {
+        (self.decoder, self.inner)
+    }
+
+    fn decode(&mut self, packet: &[u8]) -> Result<PcmChunk, ErrorCode> {
+        let channels = self.decoder.channels() as usize;
+        let output = match packet.len() {
+            0 => &mut self.output[..self.loss_duration * channels],
+            _ => &mut self.output[..],
+        };
+
+        let len = self.decoder.decode_to_slice(packet, output, false)?;
+        if !packet.is_empty() {
+            self.loss_duration = len;
+        }
+        Ok(PcmChunk(self.output[..len * channels].to_vec()))
+    }
+}
+
+impl<B: AsRef<[u8]>, S: Stream<Item = B> + Unpin> Stream for DecodeStream<S> {
+    type Item = Result<PcmChunk, ErrorCode>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        match Pin::new(&mut this.inner).poll_next(cx) {
+            Poll::Ready(Some(packet)) => Poll::Ready(Some(this.decode(packet.as_ref()))),
+            Poll::Ready(None) => Poll::Ready(None),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
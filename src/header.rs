@@ -0,0 +1,321 @@
+//! Opus headers
+//!
+//!`OpusHead` is the identification header described by [RFC 7845](https://www.rfc-editor.org/rfc/rfc7845#section-5.1).
+//!Besides Ogg encapsulation, the same structure is used as codec private data by other containers
+//!(e.g. Matroska/WebM).
+//!
+//!`OpusTags` is the comment header described by [RFC 7845](https://www.rfc-editor.org/rfc/rfc7845#section-5.2).
+
+use crate::{mem, multistream, Encoder, Channels, ErrorCode, SampleRate};
+
+use mem::alloc::vec::Vec;
+use mem::alloc::string::String;
+
+///Magic signature of identification header
+pub const OPUS_HEAD_MAGIC: [u8; 8] = *b"OpusHead";
+///Magic signature of comment header
+pub const OPUS_TAGS_MAGIC: [u8; 8] = *b"OpusTags";
+
+const HEAD_FIXED_SIZE: usize = 19;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+///Channel mapping table of the identification header.
+///
+///Only present when mapping family is not 0.
+pub struct StreamMapping {
+    ///Total number of streams encoded in each packet
+    pub streams: u8,
+    ///Number of streams whose decoders are to be configured to produce two channels
+    pub coupled_streams: u8,
+    ///Mapping of output channels to decoded channels.
+    ///
+    ///Its length must be equal to number of channels
+    pub mapping: Vec<u8>,
+}
+
+//Parses and validates channel mapping table, which is only present when mapping family is not 0.
+//
+//Table layout is the same for Ogg identification header and MP4 `dOps` box.
+pub(crate) fn parse_stream_mapping(channels: u8, mapping_family: u8, table: &[u8]) -> Result<Option<StreamMapping>, ErrorCode> {
+    if channels == 0 {
+        return Err(ErrorCode::invalid_packet());
+    }
+
+    match mapping_family {
+        0 => match channels {
+            1 | 2 => Ok(None),
+            _ => Err(ErrorCode::invalid_packet()),
+        },
+        _ => {
+            if table.len() < 2 + channels as usize {
+                return Err(ErrorCode::invalid_packet());
+            }
+
+            let streams = table[0];
+            let coupled_streams = table[1];
+            if streams == 0 || coupled_streams > streams || streams as usize + coupled_streams as usize > 255 {
+                return Err(ErrorCode::invalid_packet());
+            }
+
+            let mapping = &table[2..2 + channels as usize];
+            let max_index = streams as usize + coupled_streams as usize;
+            if mapping.iter().any(|&idx| idx != 255 && idx as usize >= max_index) {
+                return Err(ErrorCode::invalid_packet());
+            }
+
+            Ok(Some(StreamMapping {
+                streams,
+                coupled_streams,
+                mapping: mapping.to_vec(),
+            }))
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+///Opus identification header
+pub struct OpusHead {
+    ///Version number. Only major version 0 is defined, currently always written as 1
+    pub version: u8,
+    ///Number of output channels
+    pub channels: u8,
+    ///Number of samples (at 48 kHz) to discard from the decoder output when starting playback
+    pub pre_skip: u16,
+    ///Sample rate of the original input (before encoding), in Hz.
+    ///
+    ///This is informational only and 0 means unspecified.
+    pub input_sample_rate: u32,
+    ///Gain to be applied when decoding, in Q7.8 dB units.
+    ///
+    ///Corresponds to decoder's [set_gain](../struct.Decoder.html#method.set_gain)
+    pub output_gain: i16,
+    ///Channel mapping family.
+    ///
+    ///- `0` - mono or stereo, single stream;
+    ///- `1` - Vorbis channel order, up to 8 channels;
+    ///- `255` - no defined channel meaning.
+    pub mapping_family: u8,
+    ///Channel mapping table which must be present for any mapping family other than 0
+    pub stream_mapping: Option<StreamMapping>,
+}
+
+impl OpusHead {
+    #[inline]
+    ///Creates header for single stream of mono or stereo
+    pub const fn new(channels: Channels, pre_skip: u16) -> Self {
+        Self {
+            version: 1,
+            channels: channels as _,
+            pre_skip,
+            input_sample_rate: 0,
+            output_gain: 0,
+            mapping_family: 0,
+            stream_mapping: None,
+        }
+    }
+
+    ///Creates header for multistream encoder using provided mapping `family`
+    pub fn multistream<const CH: usize>(family: u8, config: &multistream::Config<CH>, pre_skip: u16) -> Self {
+        Self {
+            version: 1,
+            channels: CH as _,
+            pre_skip,
+            input_sample_rate: 0,
+            output_gain: 0,
+            mapping_family: family,
+            stream_mapping: Some(StreamMapping {
+                streams: config.streams(),
+                coupled_streams: config.coupled_streams(),
+                mapping: config.mapping().to_vec(),
+            }),
+        }
+    }
+
+    ///Creates header describing output of the `encoder`.
+    ///
+    ///Pre-skip is set to encoder's look ahead (scaled to 48 kHz) and input sample rate to the encoder's sample rate.
+    pub fn from_encoder(encoder: &mut Encoder) -> Result<Self, ErrorCode> {
+        let rate = encoder.get_sample_rate()?;
+        let look_ahead = encoder.get_look_ahead()?;
+        let pre_skip = look_ahead as u64 * SampleRate::Hz48000 as u64 / rate as u64;
+        let pre_skip = match pre_skip.try_into() {
+            Ok(pre_skip) => pre_skip,
+            Err(_) => return Err(ErrorCode::unknown()),
+        };
+
+        let mut result = Self::new(encoder.channels(), pre_skip);
+        result.input_sample_rate = rate as _;
+        Ok(result)
+    }
+
+    #[inline]
+    ///Returns size of serialized header
+    pub fn size(&self) -> usize {
+        match self.stream_mapping {
+            Some(ref mapping) => HEAD_FIXED_SIZE + 2 + mapping.mapping.len(),
+            None => HEAD_FIXED_SIZE,
+        }
+    }
+
+    ///Parses header from its binary representation
+    ///
+    ///Returns `ErrorCode::InvalidPacket` if data is not valid identification header
+    pub fn parse(input: &[u8]) -> Result<Self, ErrorCode> {
+        if input.len() < HEAD_FIXED_SIZE || input[..8] != OPUS_HEAD_MAGIC {
+            return Err(ErrorCode::invalid_packet());
+        }
+
+        let version = input[8];
+        //Major version is stored in upper 4 bits and only 0 is defined
+        if version & 0xF0 != 0 {
+            return Err(ErrorCode::invalid_packet());
+        }
+
+        let channels = input[9];
+        let pre_skip = u16::from_le_bytes([input[10], input[11]]);
+        let input_sample_rate = u32::from_le_bytes([input[12], input[13], input[14], input[15]]);
+        let output_gain = i16::from_le_bytes([input[16], input[17]]);
+        let mapping_family = input[18];
+        let stream_mapping = parse_stream_mapping(channels, mapping_family, &input[HEAD_FIXED_SIZE..])?;
+
+        Ok(Self {
+            version,
+            channels,
+            pre_skip,
+            input_sample_rate,
+            output_gain,
+            mapping_family,
+            stream_mapping,
+        })
+    }
+
+    ///Writes binary representation of the header at the end of `out`
+    pub fn write_to(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&OPUS_HEAD_MAGIC);
+        out.push(self.version);
+        out.push(self.channels);
+        out.extend_from_slice(&self.pre_skip.to_le_bytes());
+        out.extend_from_slice(&self.input_sample_rate.to_le_bytes());
+        out.extend_from_slice(&self.output_gain.to_le_bytes());
+        out.push(self.mapping_family);
+        if let Some(ref mapping) = self.stream_mapping {
+            out.push(mapping.streams);
+            out.push(mapping.coupled_streams);
+            out.extend_from_slice(&mapping.mapping);
+        }
+    }
+
+    #[inline]
+    ///Returns binary representation of the header
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.size());
+        self.write_to(&mut out);
+        out
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+///Opus comment header
+pub struct OpusTags {
+    ///Vendor string, identifying encoder
+    pub vendor: String,
+    ///User comments in form of `NAME=value`
+    pub comments: Vec<String>,
+}
+
+impl OpusTags {
+    #[inline]
+    ///Creates comment header with vendor string set to libopus version
+    pub fn new() -> Self {
+        Self {
+            vendor: crate::version().into(),
+            comments: Vec::new(),
+        }
+    }
+
+    ///Returns value of the first comment with specified `name`, if any
+    ///
+    ///Comparison of name is case insensitive as required by specification.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.comments.iter().find_map(|comment| match comment.split_once('=') {
+            Some((key, value)) if key.eq_ignore_ascii_case(name) => Some(value),
+            _ => None,
+        })
+    }
+
+    ///Adds comment `name=value`
+    pub fn add(&mut self, name: &str, value: &str) {
+        let mut comment = String::with_capacity(name.len() + value.len() + 1);
+        comment.push_str(name);
+        comment.push('=');
+        comment.push_str(value);
+        self.comments.push(comment);
+    }
+
+    ///Parses header from its binary representation
+    ///
+    ///Returns `ErrorCode::InvalidPacket` if data is not valid comment header
+    pub fn parse(input: &[u8]) -> Result<Self, ErrorCode> {
+        fn read_string(input: &[u8], pos: &mut usize) -> Result<String, ErrorCode> {
+            let len = match input.get(*pos..*pos + 4) {
+                Some(len) => u32::from_le_bytes([len[0], len[1], len[2], len[3]]) as usize,
+                None => return Err(ErrorCode::invalid_packet()),
+            };
+            *pos += 4;
+
+            let value = match input.get(*pos..).and_then(|rest| rest.get(..len)) {
+                Some(value) => value,
+                None => return Err(ErrorCode::invalid_packet()),
+            };
+            *pos += len;
+
+            match core::str::from_utf8(value) {
+                Ok(value) => Ok(value.into()),
+                Err(_) => Err(ErrorCode::invalid_packet()),
+            }
+        }
+
+        if input.len() < OPUS_TAGS_MAGIC.len() || input[..8] != OPUS_TAGS_MAGIC {
+            return Err(ErrorCode::invalid_packet());
+        }
+
+        let mut pos = OPUS_TAGS_MAGIC.len();
+        let vendor = read_string(input, &mut pos)?;
+        let count = match input.get(pos..pos + 4) {
+            Some(count) => u32::from_le_bytes([count[0], count[1], count[2], count[3]]),
+            None => return Err(ErrorCode::invalid_packet()),
+        };
+        pos += 4;
+
+        //Each comment requires at least 4 bytes so do not trust count blindly
+        let mut comments = Vec::with_capacity(core::cmp::min(count as usize, (input.len() - pos) / 4));
+        for _ in 0..count {
+            comments.push(read_string(input, &mut pos)?);
+        }
+
+        Ok(Self {
+            vendor,
+            comments,
+        })
+    }
+
+    ///Writes binary representation of the header at the end of `out`
+    pub fn write_to(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&OPUS_TAGS_MAGIC);
+        out.extend_from_slice(&(self.vendor.len() as u32).to_le_bytes());
+        out.extend_from_slice(self.vendor.as_bytes());
+        out.extend_from_slice(&(self.comments.len() as u32).to_le_bytes());
+        for comment in self.comments.iter() {
+            out.extend_from_slice(&(comment.len() as u32).to_le_bytes());
+            out.extend_from_slice(comment.as_bytes());
+        }
+    }
+
+    #[inline]
+    ///Returns binary representation of the header
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.write_to(&mut out);
+        out
+    }
+}
@@ -0,0 +1,488 @@
+//! Channel layouts
+//!
+//!Opus multistream with mapping family 1 expects [Vorbis channel order](https://www.xiph.org/vorbis/doc/Vorbis_I_spec.html#x1-810004.3.9),
+//!while WAV files, most audio APIs and other codecs use their own orders.
+//!
+//![Layout](struct.Layout.html) describes channels as named speaker positions,
+//![Remap](struct.Remap.html) converts between orders of the same speakers and
+//![Matrix](struct.Matrix.html) converts between different speaker sets (downmix/upmix).
+//!
+//!Both operate on interleaved samples, so they can be applied to `multistream::Decoder` output or before `Encoder` input.
+
+use crate::{mem, multistream, ErrorCode};
+use crate::utils::{i16_to_float, float_to_i16};
+
+use mem::alloc::vec::Vec;
+
+const MINUS_3DB: f32 = core::f32::consts::FRAC_1_SQRT_2;
+
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+///Speaker position
+///
+///Discriminant corresponds to bit position in WAV channel mask
+pub enum Speaker {
+    ///Front left
+    FrontLeft = 0,
+    ///Front right
+    FrontRight = 1,
+    ///Front center
+    FrontCenter = 2,
+    ///Low frequency effects
+    LowFrequency = 3,
+    ///Back (rear) left
+    BackLeft = 4,
+    ///Back (rear) right
+    BackRight = 5,
+    ///Front left of center
+    FrontLeftOfCenter = 6,
+    ///Front right of center
+    FrontRightOfCenter = 7,
+    ///Back center
+    BackCenter = 8,
+    ///Side left
+    SideLeft = 9,
+    ///Side right
+    SideRight = 10,
+    ///Top center
+    TopCenter = 11,
+    ///Top front left
+    TopFrontLeft = 12,
+    ///Top front center
+    TopFrontCenter = 13,
+    ///Top front right
+    TopFrontRight = 14,
+    ///Top back left
+    TopBackLeft = 15,
+    ///Top back center
+    TopBackCenter = 16,
+    ///Top back right
+    TopBackRight = 17,
+}
+
+impl Speaker {
+    const ALL: [Self; 18] = [
+        Self::FrontLeft, Self::FrontRight, Self::FrontCenter, Self::LowFrequency, Self::BackLeft, Self::BackRight,
+        Self::FrontLeftOfCenter, Self::FrontRightOfCenter, Self::BackCenter, Self::SideLeft, Self::SideRight,
+        Self::TopCenter, Self::TopFrontLeft, Self::TopFrontCenter, Self::TopFrontRight, Self::TopBackLeft, Self::TopBackCenter, Self::TopBackRight,
+    ];
+
+    #[inline(always)]
+    ///Returns bit of WAV channel mask
+    pub const fn mask(self) -> u32 {
+        1 << self as u8
+    }
+
+    #[inline(always)]
+    //Speaker commonly used in place of this one by layouts with the same number of channels
+    const fn equivalent(self) -> Option<Self> {
+        match self {
+            Self::BackLeft => Some(Self::SideLeft),
+            Self::BackRight => Some(Self::SideRight),
+            Self::SideLeft => Some(Self::BackLeft),
+            Self::SideRight => Some(Self::BackRight),
+            _ => None,
+        }
+    }
+
+    //Speakers to fold into when this speaker is not present in output layout
+    const fn fallback(self) -> &'static [(Self, f32)] {
+        match self {
+            Self::FrontLeft => &[(Self::FrontCenter, 0.5)],
+            Self::FrontRight => &[(Self::FrontCenter, 0.5)],
+            Self::FrontCenter => &[(Self::FrontLeft, MINUS_3DB), (Self::FrontRight, MINUS_3DB)],
+            Self::LowFrequency => &[],
+            Self::BackLeft | Self::SideLeft => &[(Self::FrontLeft, MINUS_3DB)],
+            Self::BackRight | Self::SideRight => &[(Self::FrontRight, MINUS_3DB)],
+            Self::FrontLeftOfCenter => &[(Self::FrontLeft, 1.0)],
+            Self::FrontRightOfCenter => &[(Self::FrontRight, 1.0)],
+            Self::BackCenter => &[(Self::BackLeft, MINUS_3DB), (Self::BackRight, MINUS_3DB)],
+            Self::TopCenter => &[(Self::FrontCenter, MINUS_3DB)],
+            Self::TopFrontLeft => &[(Self::FrontLeft, MINUS_3DB)],
+            Self::TopFrontCenter => &[(Self::FrontCenter, MINUS_3DB)],
+            Self::TopFrontRight => &[(Self::FrontRight, MINUS_3DB)],
+            Self::TopBackLeft => &[(Self::BackLeft, MINUS_3DB)],
+            Self::TopBackCenter => &[(Self::BackCenter, MINUS_3DB)],
+            Self::TopBackRight => &[(Self::BackRight, MINUS_3DB)],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+///Channel order convention
+pub enum Order {
+    ///Vorbis order, used by Opus mapping family 1
+    Vorbis,
+    ///WAV/SMPTE order, i.e. ascending bits of WAV channel mask. Used by most audio APIs
+    Wav,
+    ///AAC channel configurations 1 to 7 (11 for 7 channels), center first
+    Aac,
+}
+
+use Speaker::*;
+
+const VORBIS_ORDER: [&[Speaker]; 8] = [
+    &[FrontCenter],
+    &[FrontLeft, FrontRight],
+    &[FrontLeft, FrontCenter, FrontRight],
+    &[FrontLeft, FrontRight, BackLeft, BackRight],
+    &[FrontLeft, FrontCenter, FrontRight, BackLeft, BackRight],
+    &[FrontLeft, FrontCenter, FrontRight, BackLeft, BackRight, LowFrequency],
+    &[FrontLeft, FrontCenter, FrontRight, SideLeft, SideRight, BackCenter, LowFrequency],
+    &[FrontLeft, FrontCenter, FrontRight, SideLeft, SideRight, BackLeft, BackRight, LowFrequency],
+];
+
+const AAC_ORDER: [&[Speaker]; 8] = [
+    &[FrontCenter],
+    &[FrontLeft, FrontRight],
+    &[FrontCenter, FrontLeft, FrontRight],
+    &[FrontCenter, FrontLeft, FrontRight, BackCenter],
+    &[FrontCenter, FrontLeft, FrontRight, BackLeft, BackRight],
+    &[FrontCenter, FrontLeft, FrontRight, BackLeft, BackRight, LowFrequency],
+    &[FrontCenter, FrontLeft, FrontRight, SideLeft, SideRight, BackCenter, LowFrequency],
+    &[FrontCenter, FrontLeft, FrontRight, SideLeft, SideRight, BackLeft, BackRight, LowFrequency],
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+///Channel layout, describing speaker of each channel
+pub struct Layout {
+    speakers: Vec<Speaker>,
+}
+
+impl Layout {
+    #[inline]
+    ///Creates layout from speakers in order of channels
+    ///
+    ///Returns `None` if `speakers` is empty or contains duplicates
+    pub fn from_speakers(speakers: &[Speaker]) -> Option<Self> {
+        if speakers.is_empty() {
+            return None;
+        }
+        for (idx, speaker) in speakers.iter().enumerate() {
+            if speakers[idx + 1..].contains(speaker) {
+                return None;
+            }
+        }
+
+        Some(Self {
+            speakers: speakers.to_vec(),
+        })
+    }
+
+    ///Creates standard layout for specified number of channels
+    ///
+    ///Returns `None` if `channels` is not in range `1..=8`
+    pub fn new(order: Order, channels: usize) -> Option<Self> {
+        if channels == 0 || channels > 8 {
+            return None;
+        }
+
+        let speakers = match order {
+            Order::Vorbis => VORBIS_ORDER[channels - 1].to_vec(),
+            Order::Aac => AAC_ORDER[channels - 1].to_vec(),
+            Order::Wav => {
+                let mut speakers = VORBIS_ORDER[channels - 1].to_vec();
+                speakers.sort_unstable_by_key(|speaker| *speaker as u8);
+                speakers
+            }
+        };
+
+        Some(Self {
+            speakers
+        })
+    }
+
+    #[inline(always)]
+    ///Creates Vorbis layout for specified number of channels
+    pub fn vorbis(channels: usize) -> Option<Self> {
+        Self::new(Order::Vorbis, channels)
+    }
+
+    ///Creates layout from WAV channel mask
+    ///
+    ///Returns `None` if mask is 0 or contains unknown speakers
+    pub fn from_mask(mask: u32) -> Option<Self> {
+        if mask == 0 || mask >> Speaker::ALL.len() != 0 {
+            return None;
+        }
+
+        Some(Self {
+            speakers: Speaker::ALL.iter().copied().filter(|speaker| mask & speaker.mask() != 0).collect(),
+        })
+    }
+
+    #[inline(always)]
+    ///Returns number of channels
+    pub fn channels(&self) -> usize {
+        self.speakers.len()
+    }
+
+    #[inline(always)]
+    ///Returns speakers in order of channels
+    pub fn speakers(&self) -> &[Speaker] {
+        &self.speakers
+    }
+
+    #[inline]
+    ///Returns WAV channel mask
+    pub fn mask(&self) -> u32 {
+        self.speakers.iter().fold(0, |mask, speaker| mask | speaker.mask())
+    }
+
+    #[inline]
+    ///Returns index of channel with specified speaker
+    pub fn position(&self, speaker: Speaker) -> Option<usize> {
+        self.speakers.iter().position(|expected| *expected == speaker)
+    }
+
+    ///Creates multistream configuration for mapping family 1, that accepts (or produces) samples in this layout.
+    ///
+    ///Note that identification header must still use [Config::vorbis](../multistream/struct.Config.html#method.vorbis) as encoded stream is always in Vorbis order.
+    ///
+    ///Returns `ErrorCode::BadArg` if `CH` doesn't match number of channels or layout cannot be mapped onto Vorbis layout
+    pub fn multistream_config<const CH: usize>(&self) -> Result<multistream::Config<CH>, ErrorCode> {
+        if CH != self.channels() {
+            return Err(ErrorCode::bad_arg());
+        }
+        let (vorbis, vorbis_layout) = match (multistream::Config::<CH>::vorbis(), Self::vorbis(CH)) {
+            (Some(vorbis), Some(vorbis_layout)) => (vorbis, vorbis_layout),
+            _ => return Err(ErrorCode::bad_arg()),
+        };
+        let remap = match Remap::new(&vorbis_layout, self) {
+            Some(remap) => remap,
+            None => return Err(ErrorCode::bad_arg()),
+        };
+
+        let mut mapping = [0u8; CH];
+        for (mapping, source) in mapping.iter_mut().zip(remap.table()) {
+            *mapping = vorbis.mapping()[*source];
+        }
+
+        match multistream::Config::try_new(vorbis.streams(), vorbis.coupled_streams(), mapping) {
+            Some(config) => Ok(config),
+            None => Err(ErrorCode::bad_arg()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+///Reordering of channels between two layouts of the same speakers
+pub struct Remap {
+    //Source channel for each output channel
+    table: Vec<usize>,
+}
+
+impl Remap {
+    ///Creates reorder table from `from` layout to `to`.
+    ///
+    ///Back and side speakers are considered interchangeable when layout has only one of the pairs (e.g. 5.1 side vs 5.1 back).
+    ///
+    ///Returns `None` if layouts have different speakers.
+    pub fn new(from: &Layout, to: &Layout) -> Option<Self> {
+        if from.channels() != to.channels() {
+            return None;
+        }
+        if from.channels() == 1 {
+            return Some(Self {
+                table: [0].to_vec(),
+            });
+        }
+
+        let mut table = Vec::with_capacity(to.channels());
+        let mut used = Vec::with_capacity(from.channels());
+        used.resize(from.channels(), false);
+        for speaker in to.speakers() {
+            let source = match from.position(*speaker) {
+                Some(source) => source,
+                None => match speaker.equivalent() {
+                    Some(equivalent) if to.position(equivalent).is_none() => from.position(equivalent)?,
+                    _ => return None,
+                },
+            };
+            if used[source] {
+                return None;
+            }
+            used[source] = true;
+            table.push(source);
+        }
+
+        Some(Self {
+            table
+        })
+    }
+
+    #[inline(always)]
+    ///Returns source channel for each output channel
+    pub fn table(&self) -> &[usize] {
+        &self.table
+    }
+
+    ///Appends reordered interleaved `input` to `output`
+    pub fn apply_to_vec<T: Copy>(&self, input: &[T], output: &mut Vec<T>) {
+        let channels = self.table.len();
+        output.reserve(input.len() - input.len() % channels);
+        for frame in input.chunks_exact(channels) {
+            output.extend(self.table.iter().map(|source| frame[*source]));
+        }
+    }
+
+    ///Reorders interleaved samples in place
+    pub fn apply<T: Copy + Default>(&self, data: &mut [T]) {
+        let channels = self.table.len();
+        let mut scratch = [T::default(); 8];
+        if channels > scratch.len() {
+            let mut output = Vec::with_capacity(data.len());
+            self.apply_to_vec(data, &mut output);
+            data[..output.len()].copy_from_slice(&output);
+            return;
+        }
+
+        for frame in data.chunks_exact_mut(channels) {
+            scratch[..channels].copy_from_slice(frame);
+            for (sample, source) in frame.iter_mut().zip(self.table.iter()) {
+                *sample = scratch[*source];
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+///Mixing matrix between two layouts
+pub struct Matrix {
+    inputs: usize,
+    outputs: usize,
+    //Row per output channel
+    coeffs: Vec<f32>,
+}
+
+impl Matrix {
+    ///Creates standard mixing matrix from `from` layout to `to`.
+    ///
+    ///- Speakers present in both layouts are copied;
+    ///- Missing center is mixed into front left/right at -3 dB;
+    ///- Missing surround speakers are mixed into front speaker of the same side at -3 dB;
+    ///- Missing front left/right are mixed into center at -6 dB (i.e. stereo to mono is average);
+    ///- Mono is copied into front left/right when upmixing;
+    ///- Low frequency channel is dropped when not present in output.
+    ///
+    ///Result may clip when many channels are mixed into one, use [normalize](#method.normalize) to prevent it.
+    pub fn new(from: &Layout, to: &Layout) -> Self {
+        fn route(to: &Layout, column: &mut [f32], speaker: Speaker, gain: f32, depth: u8) {
+            if let Some(idx) = to.position(speaker) {
+                column[idx] += gain;
+                return;
+            }
+            if let Some(idx) = speaker.equivalent().and_then(|equivalent| to.position(equivalent)) {
+                column[idx] += gain;
+                return;
+            }
+
+            //Fallbacks may loop when output has neither front nor center speakers
+            if depth < 4 {
+                for (fallback, fallback_gain) in speaker.fallback() {
+                    route(to, column, *fallback, gain * fallback_gain, depth + 1);
+                }
+            }
+        }
+
+        let inputs = from.channels();
+        let outputs = to.channels();
+        let mut coeffs = Vec::new();
+        coeffs.resize(inputs * outputs, 0.0);
+
+        let mut column = Vec::new();
+        for (input, speaker) in from.speakers().iter().enumerate() {
+            column.clear();
+            column.resize(outputs, 0.0);
+
+            if inputs == 1 && *speaker == Speaker::FrontCenter && to.position(Speaker::FrontCenter).is_none() {
+                route(to, &mut column, Speaker::FrontLeft, 1.0, 0);
+                route(to, &mut column, Speaker::FrontRight, 1.0, 0);
+            } else {
+                route(to, &mut column, *speaker, 1.0, 0);
+            }
+
+            for (output, gain) in column.iter().enumerate() {
+                coeffs[output * inputs + input] = *gain;
+            }
+        }
+
+        Self {
+            inputs,
+            outputs,
+            coeffs,
+        }
+    }
+
+    ///Creates matrix from coefficients, stored as row per output channel
+    ///
+    ///Returns `None` if number of coefficients is not `inputs * outputs` or either is 0
+    pub fn from_coefficients(inputs: usize, outputs: usize, coeffs: &[f32]) -> Option<Self> {
+        if inputs == 0 || outputs == 0 || coeffs.len() != inputs * outputs {
+            return None;
+        }
+
+        Some(Self {
+            inputs,
+            outputs,
+            coeffs: coeffs.to_vec(),
+        })
+    }
+
+    #[inline(always)]
+    ///Returns number of input channels
+    pub fn inputs(&self) -> usize {
+        self.inputs
+    }
+
+    #[inline(always)]
+    ///Returns number of output channels
+    pub fn outputs(&self) -> usize {
+        self.outputs
+    }
+
+    #[inline(always)]
+    ///Returns gain of `input` channel in `output` channel
+    pub fn coefficient(&self, output: usize, input: usize) -> f32 {
+        self.coeffs[output * self.inputs + input]
+    }
+
+    ///Scales matrix down so that sum of gains for any output channel doesn't exceed 1
+    pub fn normalize(&mut self) {
+        let max = self.coeffs.chunks_exact(self.inputs).map(|row| row.iter().map(|gain| gain.abs()).sum::<f32>()).fold(0.0, f32::max);
+        if max > 1.0 {
+            for gain in self.coeffs.iter_mut() {
+                *gain /= max;
+            }
+        }
+    }
+
+    ///Mixes interleaved `input`, appending result to `output`.
+    ///
+    ///Returns number of samples per channel appended
+    pub fn apply_float_to_vec(&self, input: &[f32], output: &mut Vec<f32>) -> usize {
+        let frames = input.len() / self.inputs;
+        output.reserve(frames * self.outputs);
+        for frame in input.chunks_exact(self.inputs) {
+            for row in self.coeffs.chunks_exact(self.inputs) {
+                output.push(row.iter().zip(frame.iter()).map(|(gain, sample)| gain * sample).sum());
+            }
+        }
+        frames
+    }
+
+    ///Mixes interleaved `input`, appending result to `output`.
+    ///
+    ///Returns number of samples per channel appended
+    pub fn apply_to_vec(&self, input: &[u16], output: &mut Vec<u16>) -> usize {
+        let frames = input.len() / self.inputs;
+        output.reserve(frames * self.outputs);
+        for frame in input.chunks_exact(self.inputs) {
+            for row in self.coeffs.chunks_exact(self.inputs) {
+                let sample = row.iter().zip(frame.iter()).map(|(gain, sample)| gain * i16_to_float(*sample)).sum();
+                output.push(float_to_i16(sample));
+            }
+        }
+        frames
+    }
+}
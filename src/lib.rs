@@ -15,6 +15,10 @@
 //!- `no-stack-protector` = disable stack protection. Disabled by default.
 //!- `no-fortify-source` - disable protection against buffer overflows. Disabled by default.
 //!- `no-simd` - disable SIMD optimizations
+//!- `cli` - Builds `opusic` command line tool to encode, decode and inspect Opus streams. Disabled by default.
+//!- `futures` - Enables `futures` module with async `Sink`/`Stream` adapters. Disabled by default.
+//!- `tokio-util` - Enables `codec` module with length prefixed framing for `tokio_util::codec`. Disabled by default.
+//!- `std` - Enables `file` module with Ogg Opus reader/writer over `std::io`. Disabled by default.
 //!
 
 #![no_std]
@@ -47,7 +51,26 @@ pub use decoder::*;
 pub mod dred;
 pub mod repacketizer;
 pub mod multistream;
+pub mod padding;
 pub mod utils;
+pub mod header;
+pub mod webm;
+pub mod mp4;
+pub mod ogg;
+pub mod wav;
+pub mod resample;
+pub mod layout;
+pub mod sim;
+pub mod compare;
+pub mod demo_format;
+pub mod probe;
+pub mod edit;
+#[cfg(feature = "futures")]
+pub mod futures;
+#[cfg(feature = "tokio-util")]
+pub mod codec;
+#[cfg(feature = "std")]
+pub mod file;
 
 ///Computes OPUS frame size in bytes for specified duration
 pub const fn frame_bytes_size(sample_rate: SampleRate, channels: Channels, duration_ms: usize) -> usize {
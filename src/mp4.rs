@@ -0,0 +1,984 @@
+//! ISO Base Media File Format (MP4) encapsulation of Opus track
+//!
+//!Implements [Encapsulation of Opus in ISO Base Media File Format](https://opus-codec.org/docs/opus_in_isobmff.html).
+//!
+//!## Writer
+//!
+//![Writer](struct.Writer.html) produces fragmented MP4: initialization segment (`ftyp` and `moov` with
+//!`Opus` sample entry and its `dOps` box) followed by `moof`/`mdat` fragments.
+//!Pre-skip is signaled via edit list as required by specification.
+//!Media timescale is always 48 kHz.
+//!
+//!## Reader
+//!
+//![Reader](struct.Reader.html) extracts samples of the first Opus track from either progressive
+//!(sample tables) or fragmented files.
+
+use crate::{mem, utils, ErrorCode, SampleRate};
+use crate::header::{self, OpusHead};
+
+use mem::alloc::vec::Vec;
+
+const TIMESCALE: u32 = SampleRate::Hz48000 as _;
+const TRACK_ID: u32 = 1;
+
+const UNITY_MATRIX: [u32; 9] = [0x10000, 0, 0, 0, 0x10000, 0, 0, 0, 0x40000000];
+
+fn begin_box(out: &mut Vec<u8>, kind: &[u8; 4]) -> usize {
+    let pos = out.len();
+    out.extend_from_slice(&[0; 4]);
+    out.extend_from_slice(kind);
+    pos
+}
+
+fn begin_full_box(out: &mut Vec<u8>, kind: &[u8; 4], version: u8, flags: u32) -> usize {
+    let pos = begin_box(out, kind);
+    out.extend_from_slice(&((version as u32) << 24 | flags).to_be_bytes());
+    pos
+}
+
+fn end_box(out: &mut [u8], pos: usize) {
+    let size = (out.len() - pos) as u32;
+    out[pos..pos + 4].copy_from_slice(&size.to_be_bytes());
+}
+
+#[inline(always)]
+fn write_u16(out: &mut Vec<u8>, value: u16) {
+    out.extend_from_slice(&value.to_be_bytes());
+}
+
+#[inline(always)]
+fn write_u32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_be_bytes());
+}
+
+#[inline(always)]
+fn write_u64(out: &mut Vec<u8>, value: u64) {
+    out.extend_from_slice(&value.to_be_bytes());
+}
+
+fn write_matrix(out: &mut Vec<u8>) {
+    for value in UNITY_MATRIX {
+        write_u32(out, value);
+    }
+}
+
+///Writes `Opus` sample entry together with its `dOps` box at the end of `out`
+pub fn write_sample_entry(out: &mut Vec<u8>, head: &OpusHead) {
+    let entry = begin_box(out, b"Opus");
+    //SampleEntry: reserved + data_reference_index
+    out.extend_from_slice(&[0; 6]);
+    write_u16(out, 1);
+    //AudioSampleEntry: reserved
+    out.extend_from_slice(&[0; 8]);
+    write_u16(out, head.channels as _);
+    //samplesize
+    write_u16(out, 16);
+    //pre_defined + reserved
+    out.extend_from_slice(&[0; 4]);
+    write_u32(out, TIMESCALE << 16);
+
+    let dops = begin_box(out, b"dOps");
+    out.push(0);
+    out.push(head.channels);
+    write_u16(out, head.pre_skip);
+    write_u32(out, head.input_sample_rate);
+    out.extend_from_slice(&head.output_gain.to_be_bytes());
+    out.push(head.mapping_family);
+    if let Some(ref mapping) = head.stream_mapping {
+        out.push(mapping.streams);
+        out.push(mapping.coupled_streams);
+        out.extend_from_slice(&mapping.mapping);
+    }
+    end_box(out, dops);
+
+    end_box(out, entry);
+}
+
+fn parse_dops(body: &[u8]) -> Result<OpusHead, ErrorCode> {
+    if body.len() < 11 || body[0] != 0 {
+        return Err(ErrorCode::invalid_packet());
+    }
+
+    let channels = body[1];
+    let mapping_family = body[10];
+    let stream_mapping = header::parse_stream_mapping(channels, mapping_family, &body[11..])?;
+
+    Ok(OpusHead {
+        version: 1,
+        channels,
+        pre_skip: u16::from_be_bytes([body[2], body[3]]),
+        input_sample_rate: u32::from_be_bytes([body[4], body[5], body[6], body[7]]),
+        output_gain: i16::from_be_bytes([body[8], body[9]]),
+        mapping_family,
+        stream_mapping,
+    })
+}
+
+///Fragmented MP4 writer for single Opus track
+///
+///Output is accumulated internally and can be retrieved at any time via [take_output](#method.take_output).
+///Right after creation output contains initialization segment only.
+pub struct Writer {
+    out: Vec<u8>,
+    mdat: Vec<u8>,
+    //Pairs of (duration, size)
+    samples: Vec<(u32, u32)>,
+    fragment_duration: u64,
+    pending_duration: u64,
+    decode_time: u64,
+    sequence: u32,
+}
+
+impl Writer {
+    ///Creates new writer, producing initialization segment
+    ///
+    ///`fragment_duration` specifies minimum duration of each fragment in samples at 48 kHz.
+    ///
+    ///Pre-skip is taken from `head` and should be derived from encoder's look ahead (see [OpusHead::from_encoder](../header/struct.OpusHead.html#method.from_encoder))
+    pub fn new(head: &OpusHead, fragment_duration: u32) -> Self {
+        let mut out = Vec::new();
+        Self::write_init_segment(&mut out, head);
+
+        Self {
+            out,
+            mdat: Vec::new(),
+            samples: Vec::new(),
+            fragment_duration: fragment_duration as _,
+            pending_duration: 0,
+            decode_time: 0,
+            sequence: 0,
+        }
+    }
+
+    fn write_init_segment(out: &mut Vec<u8>, head: &OpusHead) {
+        let ftyp = begin_box(out, b"ftyp");
+        out.extend_from_slice(b"iso6");
+        write_u32(out, 0);
+        for brand in [b"iso6", b"mp41", b"Opus"] {
+            out.extend_from_slice(brand);
+        }
+        end_box(out, ftyp);
+
+        let moov = begin_box(out, b"moov");
+
+        let mvhd = begin_full_box(out, b"mvhd", 0, 0);
+        //creation/modification time
+        write_u32(out, 0);
+        write_u32(out, 0);
+        write_u32(out, TIMESCALE);
+        //duration is unknown for fragmented file
+        write_u32(out, 0);
+        //rate 1.0, volume 1.0
+        write_u32(out, 0x00010000);
+        write_u16(out, 0x0100);
+        out.extend_from_slice(&[0; 10]);
+        write_matrix(out);
+        out.extend_from_slice(&[0; 24]);
+        write_u32(out, TRACK_ID + 1);
+        end_box(out, mvhd);
+
+        let trak = begin_box(out, b"trak");
+
+        //track enabled and in movie
+        let tkhd = begin_full_box(out, b"tkhd", 0, 3);
+        write_u32(out, 0);
+        write_u32(out, 0);
+        write_u32(out, TRACK_ID);
+        write_u32(out, 0);
+        write_u32(out, 0);
+        out.extend_from_slice(&[0; 8]);
+        //layer, alternate group, volume 1.0, reserved
+        write_u16(out, 0);
+        write_u16(out, 0);
+        write_u16(out, 0x0100);
+        write_u16(out, 0);
+        write_matrix(out);
+        //width and height
+        write_u32(out, 0);
+        write_u32(out, 0);
+        end_box(out, tkhd);
+
+        let edts = begin_box(out, b"edts");
+        let elst = begin_full_box(out, b"elst", 0, 0);
+        write_u32(out, 1);
+        //segment_duration of 0 covers whole duration of fragmented media
+        write_u32(out, 0);
+        //media_time skips pre-skip
+        write_u32(out, head.pre_skip as _);
+        write_u16(out, 1);
+        write_u16(out, 0);
+        end_box(out, elst);
+        end_box(out, edts);
+
+        let mdia = begin_box(out, b"mdia");
+
+        let mdhd = begin_full_box(out, b"mdhd", 0, 0);
+        write_u32(out, 0);
+        write_u32(out, 0);
+        write_u32(out, TIMESCALE);
+        write_u32(out, 0);
+        //Packed ISO-639-2 'und'
+        write_u16(out, 0x55C4);
+        write_u16(out, 0);
+        end_box(out, mdhd);
+
+        let hdlr = begin_full_box(out, b"hdlr", 0, 0);
+        write_u32(out, 0);
+        out.extend_from_slice(b"soun");
+        out.extend_from_slice(&[0; 12]);
+        out.extend_from_slice(b"SoundHandler\0");
+        end_box(out, hdlr);
+
+        let minf = begin_box(out, b"minf");
+
+        let smhd = begin_full_box(out, b"smhd", 0, 0);
+        write_u16(out, 0);
+        write_u16(out, 0);
+        end_box(out, smhd);
+
+        let dinf = begin_box(out, b"dinf");
+        let dref = begin_full_box(out, b"dref", 0, 0);
+        write_u32(out, 1);
+        //Media data is in the same file
+        let url = begin_full_box(out, b"url ", 0, 1);
+        end_box(out, url);
+        end_box(out, dref);
+        end_box(out, dinf);
+
+        let stbl = begin_box(out, b"stbl");
+        let stsd = begin_full_box(out, b"stsd", 0, 0);
+        write_u32(out, 1);
+        write_sample_entry(out, head);
+        end_box(out, stsd);
+        for kind in [b"stts", b"stsc", b"stco"] {
+            let table = begin_full_box(out, kind, 0, 0);
+            write_u32(out, 0);
+            end_box(out, table);
+        }
+        let stsz = begin_full_box(out, b"stsz", 0, 0);
+        write_u32(out, 0);
+        write_u32(out, 0);
+        end_box(out, stsz);
+        end_box(out, stbl);
+
+        end_box(out, minf);
+        end_box(out, mdia);
+        end_box(out, trak);
+
+        let mvex = begin_box(out, b"mvex");
+        let trex = begin_full_box(out, b"trex", 0, 0);
+        write_u32(out, TRACK_ID);
+        //default sample description index, duration, size and flags
+        write_u32(out, 1);
+        write_u32(out, 0);
+        write_u32(out, 0);
+        write_u32(out, 0);
+        end_box(out, trex);
+        end_box(out, mvex);
+
+        end_box(out, moov);
+    }
+
+    ///Adds packet to the current fragment
+    ///
+    ///Fragment is written out once its duration reaches configured fragment duration.
+    ///Packet duration is determined from its TOC and returns error if packet is invalid.
+    pub fn add_packet(&mut self, packet: &[u8]) -> Result<(), ErrorCode> {
+        let duration = utils::get_nb_samples(packet, SampleRate::Hz48000)?;
+        let size = match packet.len().try_into() {
+            Ok(size) => size,
+            Err(_) => return Err(ErrorCode::bad_arg()),
+        };
+
+        self.samples.push((duration as u32, size));
+        self.mdat.extend_from_slice(packet);
+        self.pending_duration += duration as u64;
+
+        if self.pending_duration >= self.fragment_duration {
+            self.flush();
+        }
+
+        Ok(())
+    }
+
+    ///Writes out pending packets as new fragment, if any
+    pub fn flush(&mut self) {
+        if self.samples.is_empty() {
+            return;
+        }
+
+        self.sequence = self.sequence.wrapping_add(1);
+        let out = &mut self.out;
+
+        let moof = begin_box(out, b"moof");
+        let mfhd = begin_full_box(out, b"mfhd", 0, 0);
+        write_u32(out, self.sequence);
+        end_box(out, mfhd);
+
+        let traf = begin_box(out, b"traf");
+        //default-base-is-moof
+        let tfhd = begin_full_box(out, b"tfhd", 0, 0x020000);
+        write_u32(out, TRACK_ID);
+        end_box(out, tfhd);
+
+        let tfdt = begin_full_box(out, b"tfdt", 1, 0);
+        write_u64(out, self.decode_time);
+        end_box(out, tfdt);
+
+        //data-offset, sample-duration and sample-size present
+        let trun = begin_full_box(out, b"trun", 0, 0x000001 | 0x000100 | 0x000200);
+        write_u32(out, self.samples.len() as _);
+        let data_offset = out.len();
+        write_u32(out, 0);
+        for (duration, size) in self.samples.iter() {
+            write_u32(out, *duration);
+            write_u32(out, *size);
+        }
+        end_box(out, trun);
+        end_box(out, traf);
+        end_box(out, moof);
+
+        //Data starts right after mdat header
+        let offset = (out.len() - moof + 8) as u32;
+        out[data_offset..data_offset + 4].copy_from_slice(&offset.to_be_bytes());
+
+        let mdat = begin_box(out, b"mdat");
+        out.extend_from_slice(&self.mdat);
+        end_box(out, mdat);
+
+        self.decode_time += self.pending_duration;
+        self.pending_duration = 0;
+        self.samples.clear();
+        self.mdat.clear();
+    }
+
+    #[inline]
+    ///Takes output written so far, leaving internal buffer empty
+    pub fn take_output(&mut self) -> Vec<u8> {
+        core::mem::take(&mut self.out)
+    }
+
+    #[inline]
+    ///Writes out remaining packets, returning the rest of output
+    pub fn finish(mut self) -> Vec<u8> {
+        self.flush();
+        self.out
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Mp4Box<'a> {
+    kind: [u8; 4],
+    //Offset of the box within file
+    start: usize,
+    body: &'a [u8],
+}
+
+struct Boxes<'a> {
+    data: &'a [u8],
+    //Offset of `data` within file
+    offset: usize,
+    pos: usize,
+}
+
+impl<'a> Iterator for Boxes<'a> {
+    type Item = Result<Mp4Box<'a>, ErrorCode>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let remaining = self.data.len() - self.pos;
+        if remaining == 0 {
+            return None;
+        }
+
+        let result = (|| {
+            if remaining < 8 {
+                return Err(ErrorCode::invalid_packet());
+            }
+
+            let header = &self.data[self.pos..];
+            let mut kind = [0; 4];
+            kind.copy_from_slice(&header[4..8]);
+            let (header_len, size) = match u32::from_be_bytes([header[0], header[1], header[2], header[3]]) {
+                0 => (8, remaining as u64),
+                1 => match header.get(8..16) {
+                    Some(size) => {
+                        let mut bytes = [0; 8];
+                        bytes.copy_from_slice(size);
+                        (16, u64::from_be_bytes(bytes))
+                    },
+                    None => return Err(ErrorCode::invalid_packet()),
+                },
+                size => (8, size as u64),
+            };
+
+            if size < header_len as u64 || size > remaining as u64 {
+                return Err(ErrorCode::invalid_packet());
+            }
+
+            let result = Mp4Box {
+                kind,
+                start: self.offset + self.pos,
+                body: &header[header_len..size as usize],
+            };
+            self.pos += size as usize;
+            Ok(result)
+        })();
+
+        if result.is_err() {
+            self.pos = self.data.len();
+        }
+        Some(result)
+    }
+}
+
+impl<'a> Mp4Box<'a> {
+    #[inline(always)]
+    fn body_offset(&self, data: &[u8]) -> usize {
+        self.body.as_ptr() as usize - data.as_ptr() as usize
+    }
+
+    #[inline(always)]
+    fn children(&self, file: &'a [u8]) -> Boxes<'a> {
+        Boxes {
+            data: self.body,
+            offset: self.body_offset(file),
+            pos: 0,
+        }
+    }
+
+    #[inline(always)]
+    fn full_body(&self) -> Result<(u8, u32, ByteReader<'a>), ErrorCode> {
+        let mut reader = ByteReader(self.body);
+        let header = reader.u32()?;
+        Ok(((header >> 24) as u8, header & 0xFFFFFF, reader))
+    }
+}
+
+struct ByteReader<'a>(&'a [u8]);
+
+impl<'a> ByteReader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], ErrorCode> {
+        if self.0.len() < len {
+            return Err(ErrorCode::invalid_packet());
+        }
+        let (result, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(result)
+    }
+
+    fn u32(&mut self) -> Result<u32, ErrorCode> {
+        let bytes = self.bytes(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn u64(&mut self) -> Result<u64, ErrorCode> {
+        let high = self.u32()? as u64;
+        let low = self.u32()? as u64;
+        Ok(high << 32 | low)
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Sample {
+    offset: usize,
+    size: usize,
+    timestamp: u64,
+}
+
+#[derive(Default)]
+struct SampleTables<'a> {
+    stts: Option<&'a [u8]>,
+    stsc: Option<&'a [u8]>,
+    stsz: Option<&'a [u8]>,
+    stco: Option<(&'a [u8], bool)>,
+}
+
+#[derive(Default, Clone, Copy)]
+struct TrackDefaults {
+    duration: u32,
+    size: u32,
+}
+
+struct Track {
+    id: u32,
+    head: OpusHead,
+    timescale: u32,
+    pre_skip: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+///Extracted sample
+pub struct Packet<'a> {
+    ///Decoding timestamp in units of media timescale
+    pub timestamp: u64,
+    ///Opus packet
+    pub data: &'a [u8],
+}
+
+///MP4 reader extracting samples of the first Opus track
+pub struct Reader<'a> {
+    data: &'a [u8],
+    head: OpusHead,
+    timescale: u32,
+    pre_skip: u64,
+    samples: Vec<Sample>,
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    ///Parses whole file, building list of samples
+    ///
+    ///Returns `ErrorCode::InvalidPacket` if data is malformed or contains no Opus track
+    pub fn new(data: &'a [u8]) -> Result<Self, ErrorCode> {
+        let mut track: Option<Track> = None;
+        let mut samples = Vec::new();
+        let mut defaults = Vec::new();
+        let mut next_timestamp = 0;
+
+        let top_level = Boxes {
+            data,
+            offset: 0,
+            pos: 0,
+        };
+        for item in top_level {
+            let item = item?;
+            match &item.kind {
+                b"moov" => for child in item.children(data) {
+                    let child = child?;
+                    match &child.kind {
+                        b"trak" => if track.is_none() {
+                            track = Self::parse_track(data, child, &mut samples)?;
+                        },
+                        b"mvex" => for trex in child.children(data) {
+                            let trex = trex?;
+                            if &trex.kind == b"trex" {
+                                let (_, _, mut reader) = trex.full_body()?;
+                                let track_id = reader.u32()?;
+                                let _description = reader.u32()?;
+                                let duration = reader.u32()?;
+                                let size = reader.u32()?;
+                                defaults.push((track_id, TrackDefaults {
+                                    duration,
+                                    size,
+                                }));
+                            }
+                        },
+                        _ => (),
+                    }
+                },
+                b"moof" => match track {
+                    Some(ref track) => {
+                        let defaults = defaults.iter().find(|(id, _)| *id == track.id).map(|(_, defaults)| *defaults).unwrap_or_default();
+                        Self::parse_fragment(data, item, track.id, defaults, &mut next_timestamp, &mut samples)?
+                    },
+                    None => return Err(ErrorCode::invalid_packet()),
+                },
+                _ => (),
+            }
+        }
+
+        match track {
+            Some(track) => Ok(Self {
+                data,
+                head: track.head,
+                timescale: track.timescale,
+                pre_skip: track.pre_skip,
+                samples,
+                pos: 0,
+            }),
+            None => Err(ErrorCode::invalid_packet()),
+        }
+    }
+
+    fn parse_track(data: &'a [u8], trak: Mp4Box<'a>, samples: &mut Vec<Sample>) -> Result<Option<Track>, ErrorCode> {
+        let mut track_id = 0;
+        let mut media_time = None;
+        let mut timescale = 0;
+        let mut head = None;
+        let mut tables = SampleTables::default();
+
+        for child in trak.children(data) {
+            let child = child?;
+            match &child.kind {
+                b"tkhd" => {
+                    let (version, _, mut reader) = child.full_body()?;
+                    //Skip creation and modification time
+                    reader.bytes(if version == 1 { 16 } else { 8 })?;
+                    track_id = reader.u32()?;
+                },
+                b"edts" => for elst in child.children(data) {
+                    let elst = elst?;
+                    if &elst.kind == b"elst" {
+                        let (version, _, mut reader) = elst.full_body()?;
+                        //Empty edits (media_time of -1) only insert delay, so pre-skip is specified by the first non-empty edit
+                        for _ in 0..reader.u32()? {
+                            let time = match version {
+                                1 => {
+                                    reader.u64()?;
+                                    reader.u64()?
+                                },
+                                _ => {
+                                    reader.u32()?;
+                                    match reader.u32()? {
+                                        u32::MAX => u64::MAX,
+                                        time => time as u64,
+                                    }
+                                }
+                            };
+                            //media_rate
+                            reader.u32()?;
+                            if time != u64::MAX {
+                                media_time = Some(time);
+                                break;
+                            }
+                        }
+                    }
+                },
+                b"mdia" => for child in child.children(data) {
+                    let child = child?;
+                    match &child.kind {
+                        b"mdhd" => {
+                            let (version, _, mut reader) = child.full_body()?;
+                            reader.bytes(if version == 1 { 16 } else { 8 })?;
+                            timescale = reader.u32()?;
+                        },
+                        b"minf" => for stbl in child.children(data) {
+                            let stbl = stbl?;
+                            if &stbl.kind != b"stbl" {
+                                continue;
+                            }
+
+                            for table in stbl.children(data) {
+                                let table = table?;
+                                match &table.kind {
+                                    b"stsd" => head = Self::parse_sample_description(data, table)?,
+                                    b"stts" => tables.stts = Some(table.body),
+                                    b"stsc" => tables.stsc = Some(table.body),
+                                    b"stsz" => tables.stsz = Some(table.body),
+                                    b"stco" => tables.stco = Some((table.body, false)),
+                                    b"co64" => tables.stco = Some((table.body, true)),
+                                    _ => (),
+                                }
+                            }
+                        },
+                        _ => (),
+                    }
+                },
+                _ => (),
+            }
+        }
+
+        let head = match head {
+            Some(head) => head,
+            None => return Ok(None),
+        };
+        if timescale == 0 {
+            return Err(ErrorCode::invalid_packet());
+        }
+
+        Self::parse_sample_tables(data, &tables, samples)?;
+        let pre_skip = media_time.unwrap_or(head.pre_skip as u64 * timescale as u64 / TIMESCALE as u64);
+        Ok(Some(Track {
+            id: track_id,
+            head,
+            timescale,
+            pre_skip,
+        }))
+    }
+
+    fn parse_sample_description(data: &'a [u8], stsd: Mp4Box<'a>) -> Result<Option<OpusHead>, ErrorCode> {
+        let (_, _, mut reader) = stsd.full_body()?;
+        let _count = reader.u32()?;
+        let entries = Boxes {
+            data: reader.0,
+            offset: reader.0.as_ptr() as usize - data.as_ptr() as usize,
+            pos: 0,
+        };
+
+        for entry in entries {
+            let entry = entry?;
+            if &entry.kind != b"Opus" {
+                continue;
+            }
+
+            //SampleEntry + AudioSampleEntry fields
+            const AUDIO_SAMPLE_ENTRY_SIZE: usize = 28;
+            let children = match entry.body.get(AUDIO_SAMPLE_ENTRY_SIZE..) {
+                Some(children) => children,
+                None => return Err(ErrorCode::invalid_packet()),
+            };
+            let children = Boxes {
+                data: children,
+                offset: entry.body_offset(data) + AUDIO_SAMPLE_ENTRY_SIZE,
+                pos: 0,
+            };
+            for child in children {
+                let child = child?;
+                if &child.kind == b"dOps" {
+                    return parse_dops(child.body).map(Some);
+                }
+            }
+            return Err(ErrorCode::invalid_packet());
+        }
+
+        Ok(None)
+    }
+
+    fn parse_sample_tables(data: &'a [u8], tables: &SampleTables<'a>, samples: &mut Vec<Sample>) -> Result<(), ErrorCode> {
+        let (stts, stsc, stsz, (stco, is_co64)) = match (tables.stts, tables.stsc, tables.stsz, tables.stco) {
+            (Some(stts), Some(stsc), Some(stsz), Some(stco)) => (stts, stsc, stsz, stco),
+            _ => return Ok(()),
+        };
+
+        let mut stsz = ByteReader(stsz);
+        stsz.u32()?;
+        let sample_size = stsz.u32()?;
+        let sample_count = stsz.u32()? as usize;
+        //Samples cannot be empty or overlap, hence their number is bounded by size table or by input size
+        let max_count = match sample_size {
+            0 => stsz.0.len() / 4,
+            size => data.len() / size as usize,
+        };
+        if sample_count > max_count {
+            return Err(ErrorCode::invalid_packet());
+        }
+        if samples.try_reserve(sample_count).is_err() {
+            return Err(ErrorCode::alloc_fail());
+        }
+
+        let mut stco = ByteReader(stco);
+        stco.u32()?;
+        let chunk_count = stco.u32()? as usize;
+
+        let mut stsc = ByteReader(stsc);
+        stsc.u32()?;
+        let stsc_count = stsc.u32()? as usize;
+
+        if sample_count == 0 || chunk_count == 0 || stsc_count == 0 {
+            return Ok(());
+        }
+
+        let mut stts = ByteReader(stts);
+        stts.u32()?;
+        let mut stts_count = stts.u32()?;
+        let mut stts_left = 0;
+        let mut stts_delta = 0;
+        let mut timestamp = 0u64;
+
+        let mut run = (stsc.u32()?, stsc.u32()?);
+        stsc.u32()?;
+        let mut runs_left = stsc_count.saturating_sub(1);
+        let mut next_run = match runs_left {
+            0 => None,
+            _ => Some((stsc.u32()?, stsc.u32()?, stsc.u32()?)),
+        };
+
+        let mut sample = 0;
+        for chunk in 1..=chunk_count as u32 {
+            if let Some((first_chunk, per_chunk, _)) = next_run {
+                if chunk >= first_chunk {
+                    run = (first_chunk, per_chunk);
+                    runs_left -= 1;
+                    next_run = match runs_left {
+                        0 => None,
+                        _ => Some((stsc.u32()?, stsc.u32()?, stsc.u32()?)),
+                    };
+                }
+            }
+
+            let mut offset = match is_co64 {
+                true => stco.u64()?,
+                false => stco.u32()? as u64,
+            };
+
+            for _ in 0..run.1 {
+                if sample >= sample_count {
+                    return Ok(());
+                }
+
+                let size = match sample_size {
+                    0 => stsz.u32()? as u64,
+                    size => size as u64,
+                };
+                if size == 0 || offset.saturating_add(size) > data.len() as u64 {
+                    return Err(ErrorCode::invalid_packet());
+                }
+
+                while stts_left == 0 {
+                    if stts_count == 0 {
+                        return Err(ErrorCode::invalid_packet());
+                    }
+                    stts_count -= 1;
+                    stts_left = stts.u32()?;
+                    stts_delta = stts.u32()?;
+                }
+                stts_left -= 1;
+
+                samples.push(Sample {
+                    offset: offset as usize,
+                    size: size as usize,
+                    timestamp,
+                });
+                timestamp += stts_delta as u64;
+                offset += size;
+                sample += 1;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn parse_fragment(data: &'a [u8], moof: Mp4Box<'a>, track_id: u32, defaults: TrackDefaults, next_timestamp: &mut u64, samples: &mut Vec<Sample>) -> Result<(), ErrorCode> {
+        for traf in moof.children(data) {
+            let traf = traf?;
+            if &traf.kind != b"traf" {
+                continue;
+            }
+
+            let mut base_offset = moof.start as u64;
+            let mut defaults = defaults;
+            let mut is_track = false;
+            let mut data_end = None;
+
+            for child in traf.children(data) {
+                let child = child?;
+                match &child.kind {
+                    b"tfhd" => {
+                        let (_, flags, mut reader) = child.full_body()?;
+                        is_track = reader.u32()? == track_id;
+                        if flags & 0x000001 != 0 {
+                            base_offset = reader.u64()?;
+                        }
+                        if flags & 0x000002 != 0 {
+                            reader.u32()?;
+                        }
+                        if flags & 0x000008 != 0 {
+                            defaults.duration = reader.u32()?;
+                        }
+                        if flags & 0x000010 != 0 {
+                            defaults.size = reader.u32()?;
+                        }
+                    },
+                    b"tfdt" if is_track => {
+                        let (version, _, mut reader) = child.full_body()?;
+                        *next_timestamp = match version {
+                            1 => reader.u64()?,
+                            _ => reader.u32()? as u64,
+                        };
+                    },
+                    b"trun" if is_track => {
+                        let (_, flags, mut reader) = child.full_body()?;
+                        let count = reader.u32()? as usize;
+                        let mut offset = match flags & 0x000001 {
+                            0 => data_end.unwrap_or(base_offset),
+                            _ => base_offset.wrapping_add(reader.u32()? as i32 as i64 as u64),
+                        };
+                        if flags & 0x000004 != 0 {
+                            reader.u32()?;
+                        }
+
+                        //Every sample occupies at least one byte of input after `offset` and its fields in `trun`
+                        let sample_fields = [0x000100, 0x000200, 0x000400, 0x000800].iter().filter(|flag| flags & **flag != 0).count();
+                        if count as u64 > (data.len() as u64).saturating_sub(offset) || count.saturating_mul(4 * sample_fields) > reader.0.len() {
+                            return Err(ErrorCode::invalid_packet());
+                        }
+                        if samples.try_reserve(count).is_err() {
+                            return Err(ErrorCode::alloc_fail());
+                        }
+
+                        for _ in 0..count {
+                            let duration = match flags & 0x000100 {
+                                0 => defaults.duration,
+                                _ => reader.u32()?,
+                            };
+                            let size = match flags & 0x000200 {
+                                0 => defaults.size,
+                                _ => reader.u32()?,
+                            };
+                            if flags & 0x000400 != 0 {
+                                reader.u32()?;
+                            }
+                            if flags & 0x000800 != 0 {
+                                reader.u32()?;
+                            }
+
+                            if size == 0 || offset.saturating_add(size as u64) > data.len() as u64 {
+                                return Err(ErrorCode::invalid_packet());
+                            }
+                            samples.push(Sample {
+                                offset: offset as usize,
+                                size: size as usize,
+                                timestamp: *next_timestamp,
+                            });
+                            offset += size as u64;
+                            *next_timestamp += duration as u64;
+                        }
+                        data_end = Some(offset);
+                    },
+                    _ => (),
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    #[inline(always)]
+    ///Returns identification header reconstructed from `dOps` box
+    pub fn head(&self) -> &OpusHead {
+        &self.head
+    }
+
+    #[inline(always)]
+    ///Returns media timescale
+    pub fn timescale(&self) -> u32 {
+        self.timescale
+    }
+
+    #[inline(always)]
+    ///Returns number of samples at the beginning of media to discard in units of media timescale.
+    ///
+    ///Taken from edit list if present, otherwise from `dOps` pre-skip
+    pub fn pre_skip(&self) -> u64 {
+        self.pre_skip
+    }
+
+    #[inline(always)]
+    ///Returns total number of samples (i.e. Opus packets) in track
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    #[inline(always)]
+    ///Returns whether track has no samples
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    #[inline]
+    ///Returns sample by its index
+    pub fn get(&self, idx: usize) -> Option<Packet<'a>> {
+        self.samples.get(idx).map(|sample| Packet {
+            timestamp: sample.timestamp,
+            data: &self.data[sample.offset..sample.offset + sample.size],
+        })
+    }
+
+    #[inline]
+    ///Moves reading position to the sample by its index
+    pub fn set_position(&mut self, idx: usize) {
+        self.pos = idx;
+    }
+}
+
+impl<'a> Iterator for Reader<'a> {
+    type Item = Packet<'a>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        let result = self.get(self.pos)?;
+        self.pos += 1;
+        Some(result)
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{sys, mem, ErrorCode, SampleRate, Bandwidth};
+use crate::{sys, mem, utils, ErrorCode, SampleRate, Bandwidth};
 use super::Config;
 
 use core::ptr;
@@ -10,6 +10,7 @@ use mem::alloc::vec::Vec;
 pub struct Decoder {
     inner: mem::Unique<sys::OpusMSDecoder>,
     channels: u8,
+    scratch: Vec<f32>,
 }
 
 impl Decoder {
@@ -44,6 +45,7 @@ impl Decoder {
             Some(inner) => Self {
                 inner,
                 channels: CH as _,
+                scratch: Vec::new(),
             },
             None => return Err(ErrorCode::AllocFail)
         };
@@ -83,7 +85,10 @@ impl Decoder {
     pub fn decode_to(&mut self, input: &[u8], output: &mut [mem::MaybeUninit<u16>], decode_fec: bool) -> Result<usize, ErrorCode> {
         let (input_ptr, input_len) = match input.len() {
             0 => (ptr::null(), 0),
-            len => (input.as_ptr(), len as _)
+            len => match len.try_into() {
+                Ok(len) => (input.as_ptr(), len),
+                Err(_) => return Err(ErrorCode::bad_arg()),
+            },
         };
 
         let fec = match decode_fec {
@@ -148,7 +153,10 @@ impl Decoder {
     pub fn decode_float_to(&mut self, input: &[u8], output: &mut [mem::MaybeUninit<f32>], decode_fec: bool) -> Result<usize, ErrorCode> {
         let (input_ptr, input_len) = match input.len() {
             0 => (ptr::null(), 0),
-            len => (input.as_ptr(), len as _)
+            len => match len.try_into() {
+                Ok(len) => (input.as_ptr(), len),
+                Err(_) => return Err(ErrorCode::bad_arg()),
+            },
         };
         let fec = match decode_fec {
             true => 1,
@@ -195,6 +203,31 @@ impl Decoder {
         Ok(result)
     }
 
+    ///Decodes input packet into planar output, returning number of decoded samples.
+    ///
+    ///`output` must contain slice per channel and frame size is determined by the shortest of them.
+    ///Samples are decoded into internal buffer, which is reused between calls.
+    ///
+    ///Returns `ErrorCode::BadArg` if number of slices doesn't match number of channels.
+    ///
+    ///Refer to `decode_float_to` for details
+    pub fn decode_float_planar_to(&mut self, input: &[u8], output: &mut [&mut [f32]], decode_fec: bool) -> Result<usize, ErrorCode> {
+        if output.len() != self.channels as usize {
+            return Err(ErrorCode::bad_arg());
+        }
+        let frame_size = output.iter().map(|channel| channel.len()).min().unwrap_or(0);
+
+        let mut scratch = core::mem::take(&mut self.scratch);
+        scratch.clear();
+        scratch.resize(frame_size * output.len(), 0.0);
+        let result = self.decode_float_to_slice(input, &mut scratch, decode_fec);
+        if let Ok(len) = result {
+            utils::deinterleave(&scratch[..len * output.len()], output);
+        }
+        self.scratch = scratch;
+        result
+    }
+
     #[inline]
     ///Gets the duration (in samples) of the last packet successfully decoded or concealed.
     pub fn get_last_packet_duration(&mut self) -> Result<u32, ErrorCode> {
@@ -206,6 +239,20 @@ impl Decoder {
         map_sys_error!(result => value as _)
     }
 
+    #[inline]
+    ///Gets the final state of the codec's entropy coder.
+    ///
+    ///This is used for testing purposes: the encoder and decoder state should be identical after coding a payload
+    ///(assuming no data corruption or software bugs).
+    pub fn get_final_range(&mut self) -> Result<u32, ErrorCode> {
+        let mut value: u32 = 0;
+        let result = unsafe {
+            sys::opus_multistream_decoder_ctl(self.inner.as_mut(), sys::OPUS_GET_FINAL_RANGE_REQUEST, &mut value)
+        };
+
+        map_sys_error!(result => value)
+    }
+
     #[inline]
     ///Gets the decoder's gain configuration
     pub fn get_gain(&mut self) -> Result<i32, ErrorCode> {
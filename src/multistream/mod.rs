@@ -6,16 +6,22 @@
 //!
 //!The output channels specified by the encoder should use the [Vorbis channel ordering](https://www.xiph.org/vorbis/doc/Vorbis_I_spec.html#x1-810004.3.9).
 //!A decoder may wish to apply an additional permutation to the mapping the encoder used to achieve a different output channel order (e.g. for outputting in WAV order).
+//!Refer to [layout](../layout/index.html) module for reorder tables and downmix matrices.
 //!
 //!Each multistream packet contains an Opus packet for each stream, and all of the Opus packets in
 //!a single multistream packet must have the same duration. Therefore the duration of a multistream
 //!packet can be extracted from the TOC sequence of the first stream, which is located at the
 //!beginning of the packet.
+//!
+//!All streams except the last one use self-delimiting framing, hence multistream packets require
+//!dedicated [padding](fn.pad_packet.html) and [repacketization](struct.Repacketizer.html) functions.
 
 mod encoder;
 pub use encoder::Encoder;
 mod decoder;
 pub use decoder::Decoder;
+mod packet;
+pub use packet::{pad_packet, pad_packet_vec, unpad_packet, Repacketizer};
 
 ///Multistream configuration
 ///
@@ -102,6 +108,46 @@ impl<const CH: usize> Config<CH> {
         }
     }
 
+    ///Creates config of channel mapping family 1, as defined by [RFC 7845](https://www.rfc-editor.org/rfc/rfc7845#section-5.1.1.2)
+    ///
+    ///Input/output channels are expected in Vorbis channel order.
+    ///
+    ///Returns `None` if `CH` exceeds 8
+    pub const fn vorbis() -> Option<Self> {
+        let (streams, coupled_streams, table): (u8, u8, &[u8]) = match CH {
+            1 => (1, 0, &[0]),
+            2 => (1, 1, &[0, 1]),
+            3 => (2, 1, &[0, 2, 1]),
+            4 => (2, 2, &[0, 1, 2, 3]),
+            5 => (3, 2, &[0, 4, 1, 2, 3]),
+            6 => (4, 2, &[0, 4, 1, 2, 3, 5]),
+            7 => (4, 3, &[0, 4, 1, 2, 3, 5, 6]),
+            8 => (5, 3, &[0, 6, 1, 2, 3, 4, 5, 7]),
+            _ => return None,
+        };
+
+        let mut mapping = [0; CH];
+        let mut idx = 0;
+        while idx < CH {
+            mapping[idx] = table[idx];
+            idx = idx.saturating_add(1);
+        }
+
+        Self::try_new(streams, coupled_streams, mapping)
+    }
+
+    #[inline(always)]
+    ///Returns total number of streams
+    pub fn streams(&self) -> u8 {
+        self.streams
+    }
+
+    #[inline(always)]
+    ///Returns number of coupled (stereo) streams
+    pub fn coupled_streams(&self) -> u8 {
+        self.coupled_streams
+    }
+
     #[inline(always)]
     ///Accesses mapping
     pub fn mapping(&self) -> &[u8; CH] {
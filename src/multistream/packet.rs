@@ -0,0 +1,343 @@
+use crate::{sys, mem, ErrorCode};
+use crate::repacketizer::OwnedRepacketizer;
+use super::Config;
+
+use core::convert::TryInto;
+use mem::alloc::vec::Vec;
+
+const MAX_FRAME_SIZE: usize = 1275;
+
+//Framing of a single stream packet
+struct Framing {
+    //Position of the self-delimiting length field
+    delimiter_pos: usize,
+    //Size of the self-delimiting length field, 0 if packet is undelimited
+    delimiter_len: usize,
+    //Value of the self-delimiting length field
+    last_size: usize,
+    //Total size of the packet
+    len: usize,
+}
+
+fn parse_size(data: &[u8]) -> Result<(usize, usize), ErrorCode> {
+    match data {
+        [size, ..] if *size < 252 => Ok((*size as usize, 1)),
+        [size, extra, ..] => Ok((4 * *extra as usize + *size as usize, 2)),
+        _ => Err(ErrorCode::invalid_packet()),
+    }
+}
+
+fn write_size(size: usize, out: &mut Vec<u8>) {
+    if size < 252 {
+        out.push(size as u8);
+    } else {
+        let first = 252 + (size & 3);
+        out.push(first as u8);
+        out.push(((size - first) >> 2) as u8);
+    }
+}
+
+//Parses framing of stream packet as per RFC 6716, section 3.2 and appendix B
+fn parse_framing(data: &[u8], is_self_delimited: bool) -> Result<Framing, ErrorCode> {
+    let toc = match data.first() {
+        Some(toc) => *toc,
+        None => return Err(ErrorCode::invalid_packet()),
+    };
+    let mut pos = 1;
+    let mut padding = 0;
+    //Size of frames preceding the last one
+    let mut frames_size = 0;
+    let mut count = 1;
+    let mut is_cbr = false;
+
+    match toc & 0x3 {
+        0 => (),
+        1 => {
+            count = 2;
+            is_cbr = true;
+        },
+        2 => {
+            count = 2;
+            let (size, len) = parse_size(&data[pos..])?;
+            pos += len;
+            frames_size = size;
+        },
+        _ => {
+            let frames = match data.get(pos) {
+                Some(frames) => *frames,
+                None => return Err(ErrorCode::invalid_packet()),
+            };
+            pos += 1;
+            count = (frames & 0x3F) as usize;
+            if count == 0 {
+                return Err(ErrorCode::invalid_packet());
+            }
+            if frames & 0x40 != 0 {
+                loop {
+                    let size = match data.get(pos) {
+                        Some(size) => *size,
+                        None => return Err(ErrorCode::invalid_packet()),
+                    };
+                    pos += 1;
+                    padding += match size {
+                        255 => 254,
+                        size => size as usize,
+                    };
+                    if size != 255 {
+                        break;
+                    }
+                }
+            }
+            is_cbr = frames & 0x80 == 0;
+            if !is_cbr {
+                for _ in 1..count {
+                    let (size, len) = parse_size(&data[pos..])?;
+                    pos += len;
+                    frames_size += size;
+                }
+            }
+        }
+    }
+
+    let delimiter_pos = pos;
+    let (last_size, delimiter_len) = match is_self_delimited {
+        true => parse_size(&data[pos..])?,
+        false => {
+            let remaining = match data.len().checked_sub(pos + frames_size + padding) {
+                Some(remaining) => remaining,
+                None => return Err(ErrorCode::invalid_packet()),
+            };
+            match is_cbr {
+                true if remaining % count != 0 => return Err(ErrorCode::invalid_packet()),
+                true => (remaining / count, 0),
+                false => (remaining, 0),
+            }
+        }
+    };
+    if last_size > MAX_FRAME_SIZE {
+        return Err(ErrorCode::invalid_packet());
+    }
+
+    let payload = match is_cbr {
+        true => last_size * count,
+        false => frames_size + last_size,
+    };
+    let len = pos + delimiter_len + payload + padding;
+    if len > data.len() {
+        return Err(ErrorCode::invalid_packet());
+    }
+
+    Ok(Framing {
+        delimiter_pos,
+        delimiter_len,
+        last_size,
+        len,
+    })
+}
+
+///Pads a given multistream packet to a larger size.
+///
+///Padding is added to the last stream, whose packet occupies the rest of `input`.
+///Therefore `input` can only be padded to its own size, use [pad_packet_vec](fn.pad_packet_vec.html) to grow packet.
+///
+///Returns `ErrorCode::BadArg` if size cannot fit `i32`, new size is less than `input.len()` or greater than `input` can hold
+pub fn pad_packet<const CH: usize>(config: &Config<CH>, input: &mut [u8], new_len: usize) -> Result<(), ErrorCode> {
+    if new_len > input.len() {
+        return Err(ErrorCode::bad_arg());
+    }
+    let len = match input.len().try_into() {
+        Ok(data_len) => data_len,
+        Err(_) => return Err(ErrorCode::bad_arg()),
+    };
+    let new_len = match new_len.try_into() {
+        Ok(data_len) => data_len,
+        Err(_) => return Err(ErrorCode::bad_arg()),
+    };
+
+    let result = unsafe {
+        sys::opus_multistream_packet_pad(input.as_mut_ptr(), len, new_len, config.streams() as _)
+    };
+
+    map_sys_error!(result => ())
+}
+
+///Pads multistream packet stored in `input` to `new_len` bytes.
+///
+///Vector is grown as necessary, leaving it unchanged on error.
+///
+///Returns `ErrorCode::BadArg` if size cannot fit `i32` or new size is less than `input.len()`
+pub fn pad_packet_vec<const CH: usize>(config: &Config<CH>, input: &mut Vec<u8>, new_len: usize) -> Result<(), ErrorCode> {
+    let len = match input.len().try_into() {
+        Ok(data_len) => data_len,
+        Err(_) => return Err(ErrorCode::bad_arg()),
+    };
+    let new_len_i32 = match new_len.try_into() {
+        Ok(data_len) => data_len,
+        Err(_) => return Err(ErrorCode::bad_arg()),
+    };
+    if new_len < input.len() {
+        return Err(ErrorCode::bad_arg());
+    }
+    if input.try_reserve(new_len - input.len()).is_err() {
+        return Err(ErrorCode::alloc_fail());
+    }
+
+    let result = unsafe {
+        sys::opus_multistream_packet_pad(input.as_mut_ptr(), len, new_len_i32, config.streams() as _)
+    };
+
+    map_sys_error!(result => unsafe {
+        input.set_len(new_len);
+    })
+}
+
+///Removes all padding from a given multistream packet and rewrites the TOC sequence of each stream to minimize space usage.
+///
+///Returns `ErrorCode::BadArg` if size cannot fit `i32`
+///
+///On success returns new size of the `input` data
+pub fn unpad_packet<const CH: usize>(config: &Config<CH>, input: &mut [u8]) -> Result<usize, ErrorCode> {
+    let len = match input.len().try_into() {
+        Ok(data_len) => data_len,
+        Err(_) => return Err(ErrorCode::bad_arg()),
+    };
+
+    let result = unsafe {
+        sys::opus_multistream_packet_unpad(input.as_mut_ptr(), len, config.streams() as _)
+    };
+
+    map_sys_error!(result => result as usize)
+}
+
+///Multistream repacketizer, merging multiple multistream packets into a single packet or splitting them.
+///
+///Each stream is repacketized independently, hence packets' configuration is only required to match within the same stream.
+///Submitted packets are copied, refer to [OwnedRepacketizer](../repacketizer/struct.OwnedRepacketizer.html) for details.
+pub struct Repacketizer {
+    streams: Vec<OwnedRepacketizer>,
+    buffer: Vec<u8>,
+}
+
+impl Repacketizer {
+    ///Creates new instance for streams specified by `config`
+    pub fn new<const CH: usize>(config: &Config<CH>) -> Result<Self, ErrorCode> {
+        let mut streams = Vec::new();
+        if streams.try_reserve_exact(config.streams() as usize).is_err() {
+            return Err(ErrorCode::alloc_fail());
+        }
+        for _ in 0..config.streams() {
+            streams.push(OwnedRepacketizer::new()?);
+        }
+
+        Ok(Self {
+            streams,
+            buffer: Vec::new(),
+        })
+    }
+
+    #[inline(always)]
+    ///Re-initializes state, discarding all submitted packets
+    pub fn reset(&mut self) {
+        for stream in self.streams.iter_mut() {
+            stream.reset();
+        }
+    }
+
+    #[inline(always)]
+    ///Return the total number of frames (per stream) contained in packet data submitted
+    ///since the last time `reset` has been called
+    pub fn get_nb_frames(&self) -> u32 {
+        match self.streams.first() {
+            Some(stream) => stream.get_nb_frames(),
+            None => 0,
+        }
+    }
+
+    ///Adds multistream packet to the current state.
+    ///
+    ///Each stream's packet must match configuration of the same stream in packets already submitted,
+    ///while total duration cannot exceed 120 ms.
+    ///On error state is left unchanged.
+    pub fn add_packet(&mut self, input: &[u8]) -> Result<(), ErrorCode> {
+        let last = self.streams.len() - 1;
+        let mut data = input;
+        for idx in 0..self.streams.len() {
+            let result = if idx == last {
+                self.streams[idx].add_packet(data)
+            } else {
+                match parse_framing(data, true) {
+                    Ok(framing) => {
+                        //Stream packet is stored without self-delimiting length
+                        self.buffer.clear();
+                        self.buffer.extend_from_slice(&data[..framing.delimiter_pos]);
+                        self.buffer.extend_from_slice(&data[framing.delimiter_pos + framing.delimiter_len..framing.len]);
+                        data = &data[framing.len..];
+                        self.streams[idx].add_packet(&self.buffer)
+                    },
+                    Err(error) => Err(error),
+                }
+            };
+
+            if let Err(error) = result {
+                for stream in self.streams[..idx].iter_mut() {
+                    stream.remove_last();
+                }
+                return Err(error);
+            }
+        }
+
+        Ok(())
+    }
+
+    ///Constructs a new multistream packet from data previously submitted, appending it to `out`.
+    ///
+    ///`range` should contain range of frames to encode in range `0..=get_nb_frames()`.
+    ///
+    ///Vector is grown as necessary, leaving it unchanged on error.
+    pub fn create_packet_vec(&self, range: (u32, u32), out: &mut Vec<u8>) -> Result<usize, ErrorCode> {
+        let initial_len = out.len();
+        let last = self.streams.len() - 1;
+        let mut packet = Vec::new();
+
+        for (idx, stream) in self.streams.iter().enumerate() {
+            packet.clear();
+            if let Err(error) = stream.create_packet_vec(range, &mut packet) {
+                out.truncate(initial_len);
+                return Err(error);
+            }
+
+            let framing = match idx == last {
+                true => None,
+                false => match parse_framing(&packet, false) {
+                    Ok(framing) => Some(framing),
+                    Err(error) => {
+                        out.truncate(initial_len);
+                        return Err(error);
+                    }
+                },
+            };
+            if out.try_reserve(packet.len() + 2).is_err() {
+                out.truncate(initial_len);
+                return Err(ErrorCode::alloc_fail());
+            }
+            match framing {
+                Some(framing) => {
+                    out.extend_from_slice(&packet[..framing.delimiter_pos]);
+                    write_size(framing.last_size, out);
+                    out.extend_from_slice(&packet[framing.delimiter_pos..]);
+                },
+                None => out.extend_from_slice(&packet),
+            }
+        }
+
+        Ok(out.len() - initial_len)
+    }
+
+    #[inline(always)]
+    ///Constructs a new multistream packet from data previously submitted using all frames available, appending it to `out`.
+    ///
+    ///This is the same as calling `create_packet_vec((0, nb_frames), ...)`
+    pub fn create_full_packet_vec(&self, out: &mut Vec<u8>) -> Result<usize, ErrorCode> {
+        self.create_packet_vec((0, self.get_nb_frames()), out)
+    }
+}
@@ -0,0 +1,602 @@
+//! Ogg encapsulation of Opus stream
+//!
+//!Implements [RFC 7845](https://www.rfc-editor.org/rfc/rfc7845) on top of [Ogg](https://www.rfc-editor.org/rfc/rfc3533) bitstream format.
+//!
+//!## Writer
+//!
+//![Writer](struct.Writer.html) produces single logical Opus stream.
+//!Identification and comment headers are written on their own pages, while audio pages hold up to 1 second of audio.
+//!
+//!## Reader
+//!
+//![Reader](struct.Reader.html) reads packets of the first Opus logical stream found in data,
+//!recovering from damaged pages.
+
+use crate::{mem, utils, ErrorCode, SampleRate};
+use crate::header::{OpusHead, OpusTags};
+
+use mem::alloc::vec::Vec;
+
+///Page capture pattern
+pub const CAPTURE_PATTERN: [u8; 4] = *b"OggS";
+
+///Page header flag: page contains continuation of the previous packet
+pub const FLAG_CONTINUATION: u8 = 0x01;
+///Page header flag: first page of logical stream
+pub const FLAG_BOS: u8 = 0x02;
+///Page header flag: last page of logical stream
+pub const FLAG_EOS: u8 = 0x04;
+
+///Granule position indicating that no packet finishes on the page
+pub const NO_GRANULE: u64 = u64::MAX;
+
+pub(crate) const HEADER_SIZE: usize = 27;
+const MAX_SEGMENTS: usize = 255;
+//Each audio page holds at most 1 second of audio
+const MAX_PAGE_DURATION: u64 = SampleRate::Hz48000 as u64;
+
+const CRC_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut idx = 0;
+    while idx < 256 {
+        let mut value = (idx as u32) << 24;
+        let mut bit = 0;
+        while bit < 8 {
+            value = match value & 0x80000000 {
+                0 => value << 1,
+                _ => (value << 1) ^ 0x04C11DB7,
+            };
+            bit += 1;
+        }
+        table[idx] = value;
+        idx += 1;
+    }
+    table
+};
+
+fn crc_update(mut crc: u32, data: &[u8]) -> u32 {
+    for byte in data {
+        crc = (crc << 8) ^ CRC_TABLE[((crc >> 24) as u8 ^ *byte) as usize];
+    }
+    crc
+}
+
+#[inline]
+///Computes Ogg checksum of the page, treating CRC field as zero
+pub fn page_checksum(page: &[u8]) -> u32 {
+    if page.len() < HEADER_SIZE {
+        return crc_update(0, page);
+    }
+
+    let crc = crc_update(0, &page[..22]);
+    let crc = crc_update(crc, &[0; 4]);
+    crc_update(crc, &page[26..])
+}
+
+#[derive(Debug, Clone, Copy)]
+///Ogg page
+pub struct Page<'a> {
+    ///Header type flags
+    pub header_type: u8,
+    ///Granule position, `NO_GRANULE` if no packet finishes on this page
+    pub granule_position: u64,
+    ///Serial number of logical stream
+    pub serial: u32,
+    ///Page sequence number
+    pub sequence: u32,
+    ///Checksum as stored in header
+    pub checksum: u32,
+    ///Lacing values
+    pub segments: &'a [u8],
+    ///Page body
+    pub body: &'a [u8],
+}
+
+impl<'a> Page<'a> {
+    ///Parses page at the start of `data`, returning it with total size of the page.
+    ///
+    ///Returns `ErrorCode::InvalidPacket` if page is malformed or incomplete.
+    pub fn parse(data: &'a [u8]) -> Result<(Self, usize), ErrorCode> {
+        if data.len() < HEADER_SIZE || data[..4] != CAPTURE_PATTERN || data[4] != 0 {
+            return Err(ErrorCode::invalid_packet());
+        }
+
+        let segments_len = data[26] as usize;
+        let segments = match data.get(HEADER_SIZE..HEADER_SIZE + segments_len) {
+            Some(segments) => segments,
+            None => return Err(ErrorCode::invalid_packet()),
+        };
+        let body_start = HEADER_SIZE + segments_len;
+        let body_len = segments.iter().map(|len| *len as usize).sum::<usize>();
+        let body = match data.get(body_start..body_start + body_len) {
+            Some(body) => body,
+            None => return Err(ErrorCode::invalid_packet()),
+        };
+
+        let mut granule = [0; 8];
+        granule.copy_from_slice(&data[6..14]);
+        let page = Self {
+            header_type: data[5],
+            granule_position: u64::from_le_bytes(granule),
+            serial: u32::from_le_bytes([data[14], data[15], data[16], data[17]]),
+            sequence: u32::from_le_bytes([data[18], data[19], data[20], data[21]]),
+            checksum: u32::from_le_bytes([data[22], data[23], data[24], data[25]]),
+            segments,
+            body,
+        };
+
+        Ok((page, body_start + body_len))
+    }
+
+    ///Parses page at the start of `data`, same as [parse](#method.parse), additionally verifying its checksum.
+    pub fn parse_checked(data: &'a [u8]) -> Result<(Self, usize), ErrorCode> {
+        let (page, size) = Self::parse(data)?;
+        match page_checksum(&data[..size]) == page.checksum {
+            true => Ok((page, size)),
+            false => Err(ErrorCode::invalid_packet()),
+        }
+    }
+
+    ///Returns granule position at the start of the first packet beginning on this page.
+    ///
+    ///Derived from page's granule position by subtracting duration of packets, which begin and end on this page.
+    ///
+    ///Returns `None` if no packet ends on this page or if any of packets is invalid.
+    pub fn start_granule(&self) -> Option<u64> {
+        if self.granule_position == NO_GRANULE {
+            return None;
+        }
+
+        let mut duration = 0u64;
+        let mut is_continued = self.is_continuation();
+        let mut body = self.body;
+        let mut packet_len = 0;
+        for len in self.segments.iter().map(|len| *len as usize) {
+            packet_len += len;
+            if len < 255 {
+                if !is_continued {
+                    duration += utils::get_nb_samples(&body[..packet_len], SampleRate::Hz48000).ok()? as u64;
+                }
+                is_continued = false;
+                body = &body[packet_len..];
+                packet_len = 0;
+            }
+        }
+
+        self.granule_position.checked_sub(duration)
+    }
+
+    #[inline(always)]
+    ///Returns whether page continues packet from the previous page
+    pub fn is_continuation(&self) -> bool {
+        self.header_type & FLAG_CONTINUATION != 0
+    }
+
+    #[inline(always)]
+    ///Returns whether page is the first page of logical stream
+    pub fn is_bos(&self) -> bool {
+        self.header_type & FLAG_BOS != 0
+    }
+
+    #[inline(always)]
+    ///Returns whether page is the last page of logical stream
+    pub fn is_eos(&self) -> bool {
+        self.header_type & FLAG_EOS != 0
+    }
+}
+
+///Ogg Opus stream writer
+///
+///Output is accumulated internally and can be retrieved at any time via [take_output](#method.take_output).
+pub struct Writer {
+    out: Vec<u8>,
+    serial: u32,
+    sequence: u32,
+    //Granule position after the last added packet
+    granule: u64,
+    end_trim: u64,
+    segments: Vec<u8>,
+    body: Vec<u8>,
+    header_type: u8,
+    //Granule position of the last packet finished on the current page
+    page_granule: u64,
+    page_start: u64,
+}
+
+impl Writer {
+    ///Creates new writer, writing identification and comment headers
+    ///
+    ///`serial` identifies logical stream and should be random.
+    pub fn new(head: &OpusHead, tags: &OpusTags, serial: u32) -> Self {
+        let mut this = Self {
+            out: Vec::new(),
+            serial,
+            sequence: 0,
+            granule: 0,
+            end_trim: 0,
+            segments: Vec::with_capacity(MAX_SEGMENTS),
+            body: Vec::new(),
+            header_type: FLAG_BOS,
+            page_granule: NO_GRANULE,
+            page_start: 0,
+        };
+
+        this.push_packet(&head.to_vec(), 0);
+        this.flush_page();
+        this.push_packet(&tags.to_vec(), 0);
+        this.flush_page();
+        this
+    }
+
+    #[inline(always)]
+    ///Returns granule position after the last added packet
+    pub fn granule_position(&self) -> u64 {
+        self.granule
+    }
+
+    #[inline(always)]
+    ///Sets number of samples (at 48 kHz) to trim from the end of stream.
+    ///
+    ///This is used to signal actual length of the stream when the last packet is padded.
+    pub fn set_end_trim(&mut self, samples: u64) {
+        self.end_trim = samples;
+    }
+
+    fn push_packet(&mut self, packet: &[u8], granule: u64) {
+        let mut remaining = packet;
+        loop {
+            if self.segments.len() == MAX_SEGMENTS {
+                self.flush_page();
+                self.header_type |= FLAG_CONTINUATION;
+            }
+
+            let len = core::cmp::min(remaining.len(), 255);
+            self.segments.push(len as u8);
+            self.body.extend_from_slice(&remaining[..len]);
+            remaining = &remaining[len..];
+
+            if len < 255 {
+                break;
+            }
+        }
+        self.page_granule = granule;
+    }
+
+    fn write_page(&mut self, granule: u64) {
+        let start = self.out.len();
+        self.out.extend_from_slice(&CAPTURE_PATTERN);
+        self.out.push(0);
+        self.out.push(self.header_type);
+        self.out.extend_from_slice(&granule.to_le_bytes());
+        self.out.extend_from_slice(&self.serial.to_le_bytes());
+        self.out.extend_from_slice(&self.sequence.to_le_bytes());
+        self.out.extend_from_slice(&[0; 4]);
+        self.out.push(self.segments.len() as u8);
+        self.out.extend_from_slice(&self.segments);
+        self.out.extend_from_slice(&self.body);
+
+        let crc = page_checksum(&self.out[start..]);
+        self.out[start + 22..start + 26].copy_from_slice(&crc.to_le_bytes());
+
+        self.sequence = self.sequence.wrapping_add(1);
+        self.header_type = 0;
+        self.segments.clear();
+        self.body.clear();
+        self.page_granule = NO_GRANULE;
+        self.page_start = self.granule;
+    }
+
+    #[inline(always)]
+    fn flush_page(&mut self) {
+        self.write_page(self.page_granule);
+    }
+
+    ///Adds packet to the stream
+    ///
+    ///Packet duration is determined from its TOC and returns error if packet is invalid.
+    pub fn add_packet(&mut self, packet: &[u8]) -> Result<(), ErrorCode> {
+        let duration = utils::get_nb_samples(packet, SampleRate::Hz48000)?;
+
+        //Page is flushed only once next packet arrives, so that there is always last page to finish
+        if !self.segments.is_empty() && (self.granule - self.page_start >= MAX_PAGE_DURATION || self.segments.len() + packet.len() / 255 >= MAX_SEGMENTS) {
+            self.flush_page();
+        }
+
+        self.granule += duration as u64;
+        self.push_packet(packet, self.granule);
+        Ok(())
+    }
+
+    #[inline]
+    ///Takes output written so far, leaving internal buffer empty
+    pub fn take_output(&mut self) -> Vec<u8> {
+        core::mem::take(&mut self.out)
+    }
+
+    ///Writes the last page of the stream, returning the rest of output
+    pub fn finish(mut self) -> Vec<u8> {
+        self.header_type |= FLAG_EOS;
+        let granule = self.granule.saturating_sub(self.end_trim);
+        self.write_page(granule);
+        self.out
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+///Packet read from Ogg stream
+pub struct Packet<'a> {
+    ///Packet data
+    pub data: &'a [u8],
+    ///Granule position of the page, if this packet is the last one finished on it
+    pub granule_position: Option<u64>,
+    ///Whether this is the last packet of logical stream
+    pub is_last: bool,
+    ///Number of samples (at 48 kHz) lost right before this packet due to damaged or missing pages
+    pub lost_samples: u64,
+}
+
+#[derive(Clone, Copy)]
+//Information about packet assembled by `Assembler`
+pub(crate) struct PacketInfo {
+    pub(crate) granule_position: Option<u64>,
+    pub(crate) is_last: bool,
+    //Samples lost right before this packet
+    pub(crate) lost_samples: u64,
+}
+
+//Assembles packets from pages of single logical stream
+//
+//Missing pages are detected by sequence numbers, in which case partial packet is dropped and position
+//is recovered from the first intact page that has packet ending on it.
+//Source of pages is up to the caller, which feeds each page of the stream via `add_page`
+//and then passes the same page to `next_packet` until it is exhausted.
+pub(crate) struct Assembler {
+    packet: Vec<u8>,
+    //Packet buffer holds complete packet, which is to be discarded on next call
+    is_complete: bool,
+    segments_len: usize,
+    segment: usize,
+    body_pos: usize,
+    //Sequence number of the next page of the stream, if known
+    sequence: Option<u32>,
+    //Granule position after the last returned packet, if known
+    position: Option<u64>,
+    is_damaged: bool,
+    //Lost samples to report with the next packet
+    lost: u64,
+}
+
+impl Assembler {
+    pub(crate) fn new(sequence: Option<u32>) -> Self {
+        Self {
+            packet: Vec::new(),
+            is_complete: false,
+            segments_len: 0,
+            segment: 0,
+            body_pos: 0,
+            sequence,
+            position: None,
+            is_damaged: false,
+            lost: 0,
+        }
+    }
+
+    #[inline(always)]
+    //Returns data of the last packet
+    pub(crate) fn packet(&self) -> &[u8] {
+        &self.packet
+    }
+
+    //Finishes reading of comment header, which must not be damaged
+    pub(crate) fn end_headers(&mut self) -> Result<(), ErrorCode> {
+        if self.is_damaged {
+            return Err(ErrorCode::invalid_packet());
+        }
+        self.position = None;
+        Ok(())
+    }
+
+    fn skip_continued(&mut self, page: &Page<'_>) {
+        while self.segment < self.segments_len {
+            let len = page.segments[self.segment] as usize;
+            self.segment += 1;
+            self.body_pos += len;
+            if len < 255 {
+                break;
+            }
+        }
+    }
+
+    //Starts reading next page of the stream
+    pub(crate) fn add_page(&mut self, page: &Page<'_>) {
+        if self.is_complete {
+            self.is_complete = false;
+            self.packet.clear();
+        }
+        if self.sequence.is_some_and(|sequence| sequence != page.sequence) {
+            self.is_damaged = true;
+        }
+        self.sequence = Some(page.sequence.wrapping_add(1));
+        let is_continued = !self.packet.is_empty();
+        self.segments_len = page.segments.len();
+        self.segment = 0;
+        self.body_pos = 0;
+
+        if self.is_damaged {
+            //Partial packet is lost together with damaged pages,
+            //while position is recovered from the first intact page that has packet ending on it
+            self.packet.clear();
+            match page.start_granule() {
+                Some(start) => {
+                    if let Some(position) = self.position {
+                        self.lost += start.saturating_sub(position);
+                    }
+                    self.position = Some(start);
+                    self.is_damaged = false;
+                    if page.is_continuation() {
+                        self.skip_continued(page);
+                    }
+                },
+                None => self.segment = self.segments_len,
+            }
+            return;
+        }
+
+        if self.position.is_none() && !page.is_eos() {
+            self.position = page.start_granule();
+        }
+        if page.is_continuation() && !is_continued {
+            //Lost beginning of the packet, skip its remains
+            self.skip_continued(page);
+        } else if !page.is_continuation() {
+            //Lost continuation, discard partial packet
+            self.packet.clear();
+        }
+    }
+
+    //Assembles next packet from the page last passed to `add_page`, returning `None` once page is exhausted
+    pub(crate) fn next_packet(&mut self, page: &Page<'_>) -> Option<PacketInfo> {
+        if self.is_complete {
+            self.is_complete = false;
+            self.packet.clear();
+        }
+
+        while self.segment < self.segments_len {
+            let len = page.segments[self.segment] as usize;
+            self.packet.extend_from_slice(&page.body[self.body_pos..self.body_pos + len]);
+            self.segment += 1;
+            self.body_pos += len;
+
+            if len < 255 {
+                let is_last_on_page = page.segments[self.segment..].iter().all(|len| *len == 255);
+                self.position = match is_last_on_page {
+                    true => Some(page.granule_position),
+                    false => self.position.map(|position| position + utils::get_nb_samples(&self.packet, SampleRate::Hz48000).unwrap_or(0) as u64),
+                };
+                self.is_complete = true;
+                return Some(PacketInfo {
+                    granule_position: match is_last_on_page {
+                        true => Some(page.granule_position),
+                        false => None,
+                    },
+                    is_last: page.is_eos() && self.segment == self.segments_len,
+                    lost_samples: core::mem::take(&mut self.lost),
+                });
+            }
+        }
+
+        None
+    }
+}
+
+///Ogg Opus stream reader
+///
+///Reads the first Opus logical stream, skipping pages of other streams.
+///
+///Pages with invalid checksum are skipped and reader resynchronizes on the next capture pattern.
+///Missing pages are detected by page sequence numbers and duration of lost audio is reported via
+///[Packet::lost_samples](struct.Packet.html#structfield.lost_samples).
+pub struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    serial: u32,
+    head: OpusHead,
+    tags: OpusTags,
+    page: Option<Page<'a>>,
+    assembler: Assembler,
+}
+
+impl<'a> Reader<'a> {
+    ///Creates new reader, parsing identification and comment headers
+    ///
+    ///Returns `ErrorCode::InvalidPacket` if data is malformed or contains no Opus stream
+    pub fn new(data: &'a [u8]) -> Result<Self, ErrorCode> {
+        let mut pos = 0;
+        loop {
+            let (page, size) = Page::parse_checked(&data[pos..])?;
+            pos += size;
+
+            if page.is_bos() && page.body.starts_with(&crate::header::OPUS_HEAD_MAGIC) {
+                let mut this = Self {
+                    data,
+                    pos,
+                    serial: page.serial,
+                    head: OpusHead::parse(page.body)?,
+                    tags: OpusTags::default(),
+                    page: None,
+                    assembler: Assembler::new(Some(page.sequence.wrapping_add(1))),
+                };
+
+                this.tags = match this.next_packet()? {
+                    Some(packet) => OpusTags::parse(packet.data)?,
+                    None => return Err(ErrorCode::invalid_packet()),
+                };
+                this.assembler.end_headers()?;
+                return Ok(this);
+            }
+        }
+    }
+
+    #[inline(always)]
+    ///Returns identification header
+    pub fn head(&self) -> &OpusHead {
+        &self.head
+    }
+
+    #[inline(always)]
+    ///Returns comment header
+    pub fn tags(&self) -> &OpusTags {
+        &self.tags
+    }
+
+    #[inline(always)]
+    ///Returns serial number of Opus logical stream
+    pub fn serial(&self) -> u32 {
+        self.serial
+    }
+
+    fn next_page(&mut self) -> Option<Page<'a>> {
+        while self.pos < self.data.len() {
+            let (page, size) = match Page::parse_checked(&self.data[self.pos..]) {
+                Ok(page) => page,
+                Err(_) => {
+                    //Resynchronize on the next capture pattern
+                    self.pos += 1;
+                    self.pos += self.data[self.pos..].windows(4).position(|window| window == CAPTURE_PATTERN).unwrap_or(self.data.len() - self.pos);
+                    continue;
+                }
+            };
+            self.pos += size;
+
+            if page.serial == self.serial {
+                return Some(page);
+            }
+        }
+
+        None
+    }
+
+    ///Reads next packet, returning `None` at the end of stream
+    pub fn next_packet(&mut self) -> Result<Option<Packet<'_>>, ErrorCode> {
+        loop {
+            if let Some(page) = self.page {
+                if let Some(info) = self.assembler.next_packet(&page) {
+                    return Ok(Some(Packet {
+                        data: self.assembler.packet(),
+                        granule_position: info.granule_position,
+                        is_last: info.is_last,
+                        lost_samples: info.lost_samples,
+                    }));
+                }
+            }
+
+            match self.next_page() {
+                Some(page) => {
+                    self.assembler.add_page(&page);
+                    self.page = Some(page);
+                },
+                None => return Ok(None),
+            }
+        }
+    }
+}
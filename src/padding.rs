@@ -0,0 +1,203 @@
+//! Packet size padding to resist traffic analysis
+//!
+//!Size of VBR packets depends on the content, which allows to recognize spoken phrases even in encrypted stream.
+//![Padder](struct.Padder.html) hides it by padding every packet to constant size or to the nearest size bucket.
+//!
+//!Padding is transparent to decoder, but can be removed by receiver via [strip](fn.strip.html) to save memory.
+//!
+//!Padding to constant size is most efficient when paired with constrained VBR (`set_vbr_constraint`),
+//!which limits variation of packet size while keeping most of VBR benefits.
+
+use crate::{mem, multistream, repacketizer, Encoder, ErrorCode};
+
+use mem::alloc::vec::Vec;
+
+//Maximum size of a single stream packet of 120 ms
+const MAX_PACKET_SIZE: usize = 1277 * 6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+///Padding policy
+pub enum Policy {
+    ///Pads every packet to exactly specified number of bytes.
+    ///
+    ///Encoder output is limited to this size, which lowers quality of frames that would not fit.
+    Constant(usize),
+    ///Pads every packet to the nearest multiple of specified number of bytes.
+    Bucket(usize),
+}
+
+///Pads packets according to [Policy](enum.Policy.html), keeping track of padding overhead.
+pub struct Padder {
+    policy: Policy,
+    packets: u64,
+    payload_bytes: u64,
+    padding_bytes: u64,
+}
+
+impl Padder {
+    ///Creates new instance
+    ///
+    ///Returns `ErrorCode::BadArg` if size is zero or cannot fit `i32`
+    pub fn new(policy: Policy) -> Result<Self, ErrorCode> {
+        let size = match policy {
+            Policy::Constant(size) | Policy::Bucket(size) => size,
+        };
+        if size == 0 || i32::try_from(size).is_err() {
+            return Err(ErrorCode::bad_arg());
+        }
+
+        Ok(Self {
+            policy,
+            packets: 0,
+            payload_bytes: 0,
+            padding_bytes: 0,
+        })
+    }
+
+    #[inline(always)]
+    ///Returns padding policy
+    pub fn policy(&self) -> Policy {
+        self.policy
+    }
+
+    #[inline(always)]
+    ///Returns number of padded packets
+    pub fn packets(&self) -> u64 {
+        self.packets
+    }
+
+    #[inline(always)]
+    ///Returns total size of packets before padding
+    pub fn payload_bytes(&self) -> u64 {
+        self.payload_bytes
+    }
+
+    #[inline(always)]
+    ///Returns total number of bytes added as padding
+    pub fn padding_bytes(&self) -> u64 {
+        self.padding_bytes
+    }
+
+    #[inline]
+    ///Returns padding overhead relative to the payload size, e.g. `0.25` means 25% more bytes are sent.
+    pub fn overhead(&self) -> f32 {
+        match self.payload_bytes {
+            0 => 0.0,
+            payload_bytes => self.padding_bytes as f32 / payload_bytes as f32,
+        }
+    }
+
+    #[inline]
+    //Returns size of padded packet
+    fn padded_len(&self, len: usize) -> Result<usize, ErrorCode> {
+        match self.policy {
+            Policy::Constant(size) if len > size => Err(ErrorCode::bad_arg()),
+            Policy::Constant(size) => Ok(size),
+            Policy::Bucket(size) => match len.div_ceil(size).checked_mul(size) {
+                Some(len) => Ok(len),
+                None => Err(ErrorCode::bad_arg()),
+            },
+        }
+    }
+
+    #[inline]
+    //Returns maximum size of encoder output for specified number of streams
+    fn output_limit(&self, streams: usize) -> usize {
+        match self.policy {
+            Policy::Constant(size) => size,
+            Policy::Bucket(_) => MAX_PACKET_SIZE * streams,
+        }
+    }
+
+    #[inline]
+    fn update(&mut self, len: usize, padded_len: usize) {
+        self.packets += 1;
+        self.payload_bytes += len as u64;
+        self.padding_bytes += (padded_len - len) as u64;
+    }
+
+    ///Pads single stream packet, growing vector as necessary.
+    ///
+    ///Returns `ErrorCode::BadArg` if packet exceeds constant size.
+    pub fn pad(&mut self, packet: &mut Vec<u8>) -> Result<(), ErrorCode> {
+        let len = packet.len();
+        let padded_len = self.padded_len(len)?;
+        repacketizer::pad_packet_vec(packet, padded_len)?;
+        self.update(len, padded_len);
+        Ok(())
+    }
+
+    ///Pads multistream packet, growing vector as necessary.
+    ///
+    ///Returns `ErrorCode::BadArg` if packet exceeds constant size.
+    pub fn pad_multistream<const CH: usize>(&mut self, config: &multistream::Config<CH>, packet: &mut Vec<u8>) -> Result<(), ErrorCode> {
+        let len = packet.len();
+        let padded_len = self.padded_len(len)?;
+        multistream::pad_packet_vec(config, packet, padded_len)?;
+        self.update(len, padded_len);
+        Ok(())
+    }
+
+    //Encodes packet of at most `limit` bytes using `encode`, then pads it and appends to `output`
+    fn encode_with(&mut self, limit: usize, output: &mut Vec<u8>, encode: impl FnOnce(&mut [mem::MaybeUninit<u8>]) -> Result<usize, ErrorCode>, pad: impl FnOnce(&mut Vec<u8>, usize) -> Result<(), ErrorCode>) -> Result<usize, ErrorCode> {
+        let mut packet = Vec::new();
+        if packet.try_reserve(limit).is_err() {
+            return Err(ErrorCode::alloc_fail());
+        }
+        let len = encode(&mut packet.spare_capacity_mut()[..limit])?;
+        unsafe {
+            packet.set_len(len);
+        }
+
+        let padded_len = self.padded_len(len)?;
+        pad(&mut packet, padded_len)?;
+        if output.try_reserve(padded_len).is_err() {
+            return Err(ErrorCode::alloc_fail());
+        }
+        output.extend_from_slice(&packet);
+        self.update(len, padded_len);
+        Ok(padded_len)
+    }
+
+    ///Encodes an Opus frame, appending padded packet to `output` and returning its size.
+    ///
+    ///Refer to Encoder's `encode_to` for details
+    pub fn encode(&mut self, encoder: &mut Encoder, input: &[u16], output: &mut Vec<u8>) -> Result<usize, ErrorCode> {
+        self.encode_with(self.output_limit(1), output, |out| encoder.encode_to(input, out), repacketizer::pad_packet_vec)
+    }
+
+    ///Encodes an Opus frame from float input, appending padded packet to `output` and returning its size.
+    ///
+    ///Refer to Encoder's `encode_float_to` for details
+    pub fn encode_float(&mut self, encoder: &mut Encoder, input: &[f32], output: &mut Vec<u8>) -> Result<usize, ErrorCode> {
+        self.encode_with(self.output_limit(1), output, |out| encoder.encode_float_to(input, out), repacketizer::pad_packet_vec)
+    }
+
+    ///Encodes a multistream Opus frame, appending padded packet to `output` and returning its size.
+    ///
+    ///Refer to multistream Encoder's `encode_to` for details
+    pub fn encode_multistream<const CH: usize>(&mut self, encoder: &mut multistream::Encoder, config: &multistream::Config<CH>, input: &[u16], output: &mut Vec<u8>) -> Result<usize, ErrorCode> {
+        self.encode_with(self.output_limit(config.streams() as usize), output, |out| encoder.encode_to(input, out), |packet, len| multistream::pad_packet_vec(config, packet, len))
+    }
+
+    ///Encodes a multistream Opus frame from float input, appending padded packet to `output` and returning its size.
+    ///
+    ///Refer to multistream Encoder's `encode_float_to` for details
+    pub fn encode_multistream_float<const CH: usize>(&mut self, encoder: &mut multistream::Encoder, config: &multistream::Config<CH>, input: &[f32], output: &mut Vec<u8>) -> Result<usize, ErrorCode> {
+        self.encode_with(self.output_limit(config.streams() as usize), output, |out| encoder.encode_float_to(input, out), |packet, len| multistream::pad_packet_vec(config, packet, len))
+    }
+}
+
+///Removes padding from single stream packet, shrinking vector to the new size.
+pub fn strip(packet: &mut Vec<u8>) -> Result<(), ErrorCode> {
+    let len = repacketizer::unpad_packet(packet)?;
+    packet.truncate(len);
+    Ok(())
+}
+
+///Removes padding from multistream packet, shrinking vector to the new size.
+pub fn strip_multistream<const CH: usize>(config: &multistream::Config<CH>, packet: &mut Vec<u8>) -> Result<(), ErrorCode> {
+    let len = multistream::unpad_packet(config, packet)?;
+    packet.truncate(len);
+    Ok(())
+}
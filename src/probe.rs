@@ -0,0 +1,219 @@
+//! Stream probing without decoding
+//!
+//!Computes duration, bitrate and coding statistics of Opus stream using only packets' TOC,
+//!which is orders of magnitude faster than decoding and suitable for indexing large media libraries.
+//!
+//!For multistream packets, coding statistics refer to the first stream only.
+
+use crate::{mem, ogg, webm, utils, ErrorCode, SampleRate, Channels, Bandwidth};
+use crate::header::OpusHead;
+use crate::utils::{Mode, Toc};
+
+use mem::alloc::collections::VecDeque;
+
+//1 second at 48kHz
+const PEAK_WINDOW: u64 = SampleRate::Hz48000 as u64;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+///Duration of audio (in samples at 48 kHz) per coding mode
+pub struct ModeDistribution {
+    ///SILK only
+    pub silk: u64,
+    ///Hybrid
+    pub hybrid: u64,
+    ///CELT only
+    pub celt: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+///Duration of audio (in samples at 48 kHz) per audio bandwidth
+pub struct BandwidthDistribution {
+    ///4 kHz
+    pub narrow: u64,
+    ///6 kHz
+    pub medium: u64,
+    ///8 kHz
+    pub wide: u64,
+    ///12 kHz
+    pub superwide: u64,
+    ///20 kHz
+    pub full: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+///Result of probing
+pub struct Probe {
+    ///Identification header, if stored by container
+    pub head: Option<OpusHead>,
+    ///Number of packets, excluding empty ones
+    pub packets: u64,
+    ///Total size of packets in bytes
+    pub bytes: u64,
+    ///Total duration of packets in samples at 48 kHz
+    pub samples: u64,
+    ///Playable duration in samples at 48 kHz, i.e. excluding pre-skip and end trimming
+    pub duration: u64,
+    ///Number of packets coded in stereo
+    pub stereo_packets: u64,
+    ///Coding mode distribution
+    pub modes: ModeDistribution,
+    ///Audio bandwidth distribution
+    pub bandwidths: BandwidthDistribution,
+    ///Peak bitrate over 1 second window in bits per second.
+    ///
+    ///Equals to average bitrate if stream is shorter than 1 second.
+    pub peak_bitrate: u32,
+}
+
+impl Probe {
+    #[inline]
+    ///Returns average bitrate in bits per second
+    pub fn average_bitrate(&self) -> u32 {
+        bitrate(self.bytes, self.samples)
+    }
+
+    #[inline]
+    ///Returns number of output channels, as specified by identification header or derived from packets otherwise.
+    pub fn channels(&self) -> u8 {
+        match (self.head.as_ref(), self.stereo_packets) {
+            (Some(head), _) => head.channels,
+            (None, 0) => 1,
+            (None, _) => 2,
+        }
+    }
+}
+
+#[inline]
+fn bitrate(bytes: u64, samples: u64) -> u32 {
+    match samples {
+        0 => 0,
+        samples => (bytes * 8 * SampleRate::Hz48000 as u64 / samples) as u32,
+    }
+}
+
+struct Prober {
+    result: Probe,
+    //Duration and size of packets within peak window
+    window: VecDeque<(u64, u64)>,
+    window_samples: u64,
+    window_bytes: u64,
+}
+
+impl Prober {
+    fn new(head: Option<OpusHead>) -> Self {
+        Self {
+            result: Probe {
+                head,
+                ..Probe::default()
+            },
+            window: VecDeque::new(),
+            window_samples: 0,
+            window_bytes: 0,
+        }
+    }
+
+    fn add_packet(&mut self, packet: &[u8]) -> Result<u64, ErrorCode> {
+        if packet.is_empty() {
+            return Ok(0);
+        }
+
+        let toc = Toc::parse(packet)?;
+        let samples = utils::get_nb_samples(packet, SampleRate::Hz48000)? as u64;
+        let bytes = packet.len() as u64;
+        let result = &mut self.result;
+        result.packets += 1;
+        result.bytes += bytes;
+        result.samples += samples;
+        if toc.channels() == Channels::Stereo {
+            result.stereo_packets += 1;
+        }
+
+        *match toc.mode() {
+            Mode::Silk => &mut result.modes.silk,
+            Mode::Hybrid => &mut result.modes.hybrid,
+            Mode::Celt => &mut result.modes.celt,
+        } += samples;
+        *match toc.bandwidth() {
+            Bandwidth::Narrow => &mut result.bandwidths.narrow,
+            Bandwidth::Medium => &mut result.bandwidths.medium,
+            Bandwidth::Wide => &mut result.bandwidths.wide,
+            Bandwidth::Superwide => &mut result.bandwidths.superwide,
+            _ => &mut result.bandwidths.full,
+        } += samples;
+
+        self.window.push_back((samples, bytes));
+        self.window_samples += samples;
+        self.window_bytes += bytes;
+        while let Some((samples, bytes)) = self.window.front().copied() {
+            if self.window_samples - samples < PEAK_WINDOW {
+                break;
+            }
+            self.window.pop_front();
+            self.window_samples -= samples;
+            self.window_bytes -= bytes;
+        }
+        if self.window_samples >= PEAK_WINDOW {
+            result.peak_bitrate = core::cmp::max(result.peak_bitrate, bitrate(self.window_bytes, self.window_samples));
+        }
+
+        Ok(samples)
+    }
+
+    fn finish(mut self, duration: u64) -> Probe {
+        self.result.duration = duration;
+        if self.result.peak_bitrate == 0 {
+            self.result.peak_bitrate = self.result.average_bitrate();
+        }
+        self.result
+    }
+}
+
+///Probes sequence of raw packets.
+///
+///Empty packets are treated as lost and ignored.
+///As there is no identification header, duration is total duration of packets.
+pub fn probe_packets<P: AsRef<[u8]>>(packets: impl IntoIterator<Item = P>) -> Result<Probe, ErrorCode> {
+    let mut prober = Prober::new(None);
+    for packet in packets {
+        prober.add_packet(packet.as_ref())?;
+    }
+    let duration = prober.result.samples;
+    Ok(prober.finish(duration))
+}
+
+///Probes Ogg Opus stream.
+///
+///Duration is derived from the final granule position, hence it is exact even if there are damaged pages.
+pub fn probe_ogg(data: &[u8]) -> Result<Probe, ErrorCode> {
+    let mut reader = ogg::Reader::new(data)?;
+    let pre_skip = reader.head().pre_skip as u64;
+    let mut prober = Prober::new(Some(reader.head().clone()));
+    let mut granule = None;
+    while let Some(packet) = reader.next_packet()? {
+        prober.add_packet(packet.data)?;
+        if packet.granule_position.is_some() {
+            granule = packet.granule_position;
+        }
+    }
+
+    let duration = match granule {
+        Some(granule) => granule.saturating_sub(pre_skip),
+        None => prober.result.samples.saturating_sub(pre_skip),
+    };
+    Ok(prober.finish(duration))
+}
+
+///Probes the first Opus track of WebM file.
+///
+///Duration is total duration of packets excluding codec delay.
+pub fn probe_webm(data: &[u8]) -> Result<Probe, ErrorCode> {
+    let demuxer = webm::Demuxer::new(data)?;
+    let pre_skip = demuxer.head().pre_skip as u64;
+    let mut prober = Prober::new(Some(demuxer.head().clone()));
+    for packet in demuxer {
+        prober.add_packet(packet?.data)?;
+    }
+
+    let duration = prober.result.samples.saturating_sub(pre_skip);
+    Ok(prober.finish(duration))
+}
@@ -1,13 +1,22 @@
 //! Opus packet manipulation
 use crate::{sys, mem, ErrorCode};
+use crate::utils::Toc;
 
 use core::marker;
 use core::convert::TryInto;
+use mem::alloc::vec::Vec;
+use mem::alloc::collections::VecDeque;
 
 ///Pads a given Opus packet to a larger size (possibly changing the TOC sequence).
 ///
-///Returns `ErrorCode::BadArg` if size cannot fit `u32` or new size is less than `input.len()`
+///Packet is expected to occupy whole `input`, while padding requires space for `new_len` bytes.
+///Therefore `input` can only be padded to its own size, use [pad_packet_vec](fn.pad_packet_vec.html) to grow packet.
+///
+///Returns `ErrorCode::BadArg` if size cannot fit `i32`, new size is less than `input.len()` or greater than `input` can hold
 pub fn pad_packet(input: &mut [u8], new_len: usize) -> Result<(), ErrorCode> {
+    if new_len > input.len() {
+        return Err(ErrorCode::bad_arg());
+    }
     let len = match input.len().try_into() {
         Ok(data_len) => data_len,
         Err(_) => return Err(ErrorCode::bad_arg()),
@@ -25,9 +34,39 @@ pub fn pad_packet(input: &mut [u8], new_len: usize) -> Result<(), ErrorCode> {
     map_sys_error!(result => ())
 }
 
+///Pads Opus packet stored in `input` to `new_len` bytes (possibly changing the TOC sequence).
+///
+///Vector is grown as necessary, leaving it unchanged on error.
+///
+///Returns `ErrorCode::BadArg` if size cannot fit `i32` or new size is less than `input.len()`
+pub fn pad_packet_vec(input: &mut Vec<u8>, new_len: usize) -> Result<(), ErrorCode> {
+    let len = match input.len().try_into() {
+        Ok(data_len) => data_len,
+        Err(_) => return Err(ErrorCode::bad_arg()),
+    };
+    let new_len_i32 = match new_len.try_into() {
+        Ok(data_len) => data_len,
+        Err(_) => return Err(ErrorCode::bad_arg()),
+    };
+    if new_len < input.len() {
+        return Err(ErrorCode::bad_arg());
+    }
+    if input.try_reserve(new_len - input.len()).is_err() {
+        return Err(ErrorCode::alloc_fail());
+    }
+
+    let result = unsafe {
+        sys::opus_packet_pad(input.as_mut_ptr(), len, new_len_i32)
+    };
+
+    map_sys_error!(result => unsafe {
+        input.set_len(new_len);
+    })
+}
+
 ///Remove all padding from a given Opus packet and rewrite the TOC sequence to minimize space usage.
 ///
-///Returns `ErrorCode::BadArg` if size cannot fit `u32`
+///Returns `ErrorCode::BadArg` if size cannot fit `i32`
 ///
 ///On success returns new size of the `input` data
 pub fn unpad_packet(input: &mut [u8]) -> Result<usize, ErrorCode> {
@@ -44,6 +83,63 @@ pub fn unpad_packet(input: &mut [u8]) -> Result<usize, ErrorCode> {
     map_sys_error!(result => result as usize)
 }
 
+#[inline(always)]
+fn get_nb_frames(state: &mem::Unique<sys::OpusRepacketizer>) -> u32 {
+    unsafe {
+        sys::opus_repacketizer_get_nb_frames(state.as_pseudo_mut()) as _
+    }
+}
+
+//Caller must guarantee that `input` outlives its use by `state`
+unsafe fn add_packet(state: &mut mem::Unique<sys::OpusRepacketizer>, input: &[u8]) -> Result<(), ErrorCode> {
+    let len = match input.len().try_into() {
+        Ok(data_len) => data_len,
+        Err(_) => return Err(ErrorCode::bad_arg()),
+    };
+    let data = input.as_ptr();
+
+    let result = unsafe {
+        sys::opus_repacketizer_cat(state.as_mut(), data, len)
+    };
+
+    map_sys_error!(result => ())
+}
+
+fn create_packet(state: &mem::Unique<sys::OpusRepacketizer>, range: (u32, u32), out: &mut [mem::MaybeUninit<u8>]) -> Result<usize, ErrorCode> {
+    let begin = match range.0.try_into() {
+        Ok(begin) => begin,
+        Err(_) => return Err(ErrorCode::bad_arg()),
+    };
+    let end = match range.1.try_into() {
+        Ok(end) => end,
+        Err(_) => return Err(ErrorCode::bad_arg()),
+    };
+
+    let out_len = match out.len().try_into() {
+        Ok(out_len) => out_len,
+        Err(_) => return Err(ErrorCode::bad_arg()),
+    };
+
+    let result = unsafe {
+        sys::opus_repacketizer_out_range(state.as_pseudo_mut(), begin, end, out.as_mut_ptr() as _, out_len)
+    };
+
+    map_sys_error!(result => result as _)
+}
+
+fn create_full_packet(state: &mem::Unique<sys::OpusRepacketizer>, out: &mut [mem::MaybeUninit<u8>]) -> Result<usize, ErrorCode> {
+    let out_len = match out.len().try_into() {
+        Ok(out_len) => out_len,
+        Err(_) => return Err(ErrorCode::bad_arg()),
+    };
+
+    let result = unsafe {
+        sys::opus_repacketizer_out(state.as_pseudo_mut(), out.as_mut_ptr() as _, out_len)
+    };
+
+    map_sys_error!(result => result as _)
+}
+
 #[repr(transparent)]
 ///Repacketizer can be used to merge multiple Opus packets into a single packet or alternatively to split Opus packets that have previously been merged
 pub struct Repacketizer {
@@ -139,9 +235,7 @@ impl<'a, 'buf> OngoingRepacketizer<'a, 'buf> {
     ///Return the total number of frames contained in packet data submitted to the repacketizer state
     ///since the last time `reset` has been called
     pub fn get_nb_frames(&self) -> u32 {
-        unsafe {
-            sys::opus_repacketizer_get_nb_frames(self.as_state().as_pseudo_mut()) as _
-        }
+        get_nb_frames(self.as_state())
     }
 
     ///Add a packet to the current repacketizer state.
@@ -161,17 +255,9 @@ impl<'a, 'buf> OngoingRepacketizer<'a, 'buf> {
     ///such packets, you should first use another repacketizer to split the packet into pieces and
     ///add them individually.
     pub fn add_packet(&mut self, input: &'buf [u8]) -> Result<(), ErrorCode> {
-        let len = match input.len().try_into() {
-            Ok(data_len) => data_len,
-            Err(_) => return Err(ErrorCode::bad_arg()),
-        };
-        let data = input.as_ptr();
-
-        let result = unsafe {
-            sys::opus_repacketizer_cat(self.as_state_mut().as_mut(), data, len)
-        };
-
-        map_sys_error!(result => ())
+        unsafe {
+            add_packet(self.as_state_mut(), input)
+        }
     }
 
     #[inline(always)]
@@ -195,48 +281,291 @@ impl<'a, 'buf> OngoingRepacketizer<'a, 'buf> {
     ///
     ///Number of bytes written in `out` buffer
     pub fn create_packet(&self, range: (u32, u32), out: &mut [mem::MaybeUninit<u8>]) -> Result<usize, ErrorCode> {
-        let begin = match range.0.try_into() {
-            Ok(begin) => begin,
-            Err(_) => return Err(ErrorCode::bad_arg()),
-        };
-        let end = match range.1.try_into() {
-            Ok(end) => end,
-            Err(_) => return Err(ErrorCode::bad_arg()),
-        };
+        create_packet(self.as_state(), range, out)
+    }
 
-        let out_len = match out.len().try_into() {
-            Ok(out_len) => out_len,
-            Err(_) => return Err(ErrorCode::bad_arg()),
-        };
+    #[inline(always)]
+    ///Construct a new packet from data previously submitted to the repacketizer state using all frames available
+    ///
+    ///This is the same as calling `create_packet((0, nb_frames), ...)`
+    pub fn create_full_packet(&self, out: &mut [mem::MaybeUninit<u8>]) -> Result<usize, ErrorCode> {
+        create_full_packet(self.as_state(), out)
+    }
+}
+
+impl<'a, 'buf> Drop for OngoingRepacketizer<'a, 'buf> {
+    #[inline(always)]
+    fn drop(&mut self) {
+        self.inner.reset();
+    }
+}
+
+///Repacketizer that owns submitted packets
+///
+///Unlike [OngoingRepacketizer](struct.OngoingRepacketizer.html), packets are copied into internal buffer,
+///so there is no lifetime tied to input and instance can be stored or moved across threads and `.await` points.
+pub struct OwnedRepacketizer {
+    inner: Repacketizer,
+    buffer: Vec<u8>,
+    //End offsets of submitted packets within buffer
+    packets: Vec<usize>,
+}
+
+impl OwnedRepacketizer {
+    ///Creates new instance, allocating necessary memory
+    pub fn new() -> Result<Self, ErrorCode> {
+        Ok(Self {
+            inner: Repacketizer::new()?,
+            buffer: Vec::new(),
+            packets: Vec::new(),
+        })
+    }
+
+    #[inline(always)]
+    ///Re-initializes state, discarding all submitted packets
+    pub fn reset(&mut self) {
+        self.inner.reset();
+        self.buffer.clear();
+        self.packets.clear();
+    }
+
+    #[inline(always)]
+    ///Return the total number of frames contained in packet data submitted
+    ///since the last time `reset` has been called
+    pub fn get_nb_frames(&self) -> u32 {
+        get_nb_frames(&self.inner.inner)
+    }
+
+    ///Copies packet into the current state.
+    ///
+    ///Refer to [OngoingRepacketizer::add_packet](struct.OngoingRepacketizer.html#method.add_packet) for requirements.
+    ///On error state is left unchanged.
+    pub fn add_packet(&mut self, input: &[u8]) -> Result<(), ErrorCode> {
+        if self.packets.try_reserve(1).is_err() {
+            return Err(ErrorCode::alloc_fail());
+        }
+        let buffer = self.buffer.as_ptr();
+        if self.buffer.try_reserve(input.len()).is_err() {
+            return Err(ErrorCode::alloc_fail());
+        }
+
+        let start = self.buffer.len();
+        self.buffer.extend_from_slice(input);
+
+        //Repacketizer refers to packets' data, which has to be re-submitted if buffer got relocated
+        if buffer != self.buffer.as_ptr() {
+            self.resubmit();
+        }
 
         let result = unsafe {
-            sys::opus_repacketizer_out_range(self.as_state().as_pseudo_mut(), begin, end, out.as_mut_ptr() as _, out_len)
+            add_packet(&mut self.inner.inner, &self.buffer[start..])
         };
+        match result {
+            Ok(()) => {
+                self.packets.push(self.buffer.len());
+                Ok(())
+            }
+            Err(error) => {
+                self.buffer.truncate(start);
+                Err(error)
+            }
+        }
+    }
 
-        map_sys_error!(result => result as _)
+    //Re-submits all stored packets to the reset state
+    fn resubmit(&mut self) {
+        self.inner.reset();
+        let mut packet_start = 0;
+        for packet_end in self.packets.iter() {
+            let result = unsafe {
+                add_packet(&mut self.inner.inner, &self.buffer[packet_start..*packet_end])
+            };
+            //Packets were accepted before, hence they must be accepted again
+            debug_assert!(result.is_ok());
+            packet_start = *packet_end;
+        }
+    }
+
+    //Removes the last submitted packet
+    pub(crate) fn remove_last(&mut self) {
+        if self.packets.pop().is_some() {
+            self.buffer.truncate(self.packets.last().copied().unwrap_or(0));
+            self.resubmit();
+        }
     }
 
     #[inline(always)]
-    ///Construct a new packet from data previously submitted to the repacketizer state using all frames available
+    ///Construct a new packet from data previously submitted
+    ///
+    ///Refer to [OngoingRepacketizer::create_packet](struct.OngoingRepacketizer.html#method.create_packet) for details
+    pub fn create_packet(&self, range: (u32, u32), out: &mut [mem::MaybeUninit<u8>]) -> Result<usize, ErrorCode> {
+        create_packet(&self.inner.inner, range, out)
+    }
+
+    #[inline(always)]
+    ///Construct a new packet from data previously submitted using all frames available
     ///
     ///This is the same as calling `create_packet((0, nb_frames), ...)`
     pub fn create_full_packet(&self, out: &mut [mem::MaybeUninit<u8>]) -> Result<usize, ErrorCode> {
-        let out_len = match out.len().try_into() {
-            Ok(out_len) => out_len,
-            Err(_) => return Err(ErrorCode::bad_arg()),
-        };
+        create_full_packet(&self.inner.inner, out)
+    }
 
-        let result = unsafe {
-            sys::opus_repacketizer_out(self.as_state().as_pseudo_mut(), out.as_mut_ptr() as _, out_len)
-        };
+    ///Construct a new packet from data previously submitted, appending it to `out`.
+    ///
+    ///Vector is grown as necessary, leaving it unchanged on error.
+    pub fn create_packet_vec(&self, range: (u32, u32), out: &mut Vec<u8>) -> Result<usize, ErrorCode> {
+        //Frames are never larger than submitted data, while each frame requires at most 2 bytes for its length
+        let required = self.buffer.len() + 2 * range.1.saturating_sub(range.0) as usize + 2;
+        if out.try_reserve(required).is_err() {
+            return Err(ErrorCode::alloc_fail());
+        }
 
-        map_sys_error!(result => result as _)
+        let initial_len = out.len();
+        let result = self.create_packet(range, out.spare_capacity_mut())?;
+        unsafe {
+            out.set_len(initial_len + result);
+        }
+        Ok(result)
     }
 }
 
-impl<'a, 'buf> Drop for OngoingRepacketizer<'a, 'buf> {
+//Maximum duration of a single packet in samples at 48 kHz
+const MAX_PACKET_DURATION: usize = 5760;
+
+///Streaming re-framer, combining or splitting packets to match target packet duration.
+///
+///Packets are split into individual frames, which are then combined into packets of target duration.
+///As frames cannot be split, output packets contain as many frames as fits target duration, but at least one.
+///
+///Pending frames are flushed automatically whenever configuration (top 6 bits of TOC) changes,
+///in which case output packet can be shorter than target.
+///Empty packets (signalling loss) flush pending frames as well and are passed through as it is.
+pub struct Reframer {
+    repacketizer: Repacketizer,
+    duration: usize,
+    //Top 6 bits of TOC of pending frames
+    config: Option<u8>,
+    frame_size: usize,
+    frames: Vec<Vec<u8>>,
+    output: VecDeque<Vec<u8>>,
+}
+
+impl Reframer {
+    ///Creates new instance with target packet `duration` in samples at 48 kHz.
+    ///
+    ///Returns `ErrorCode::BadArg` if duration is zero or exceeds 120 ms.
+    pub fn new(duration: usize) -> Result<Self, ErrorCode> {
+        if duration == 0 || duration > MAX_PACKET_DURATION {
+            return Err(ErrorCode::bad_arg());
+        }
+
+        Ok(Self {
+            repacketizer: Repacketizer::new()?,
+            duration,
+            config: None,
+            frame_size: 0,
+            frames: Vec::new(),
+            output: VecDeque::new(),
+        })
+    }
+
     #[inline(always)]
-    fn drop(&mut self) {
-        self.inner.reset();
+    ///Returns target packet duration in samples at 48 kHz
+    pub fn duration(&self) -> usize {
+        self.duration
+    }
+
+    #[inline]
+    //Number of frames per output packet for current configuration, which never exceeds 120 ms as duration is limited
+    fn frames_per_packet(&self) -> usize {
+        core::cmp::max(1, self.duration / self.frame_size)
+    }
+
+    ///Adds packet, making complete packets of target duration available via [next_packet](#method.next_packet)
+    pub fn add_packet(&mut self, input: &[u8]) -> Result<(), ErrorCode> {
+        let toc = match input.first() {
+            Some(toc) => Toc(*toc),
+            None => {
+                self.flush()?;
+                self.output.push_back(Vec::new());
+                return Ok(());
+            }
+        };
+        //Packet is split before touching pending frames, so that invalid packet leaves state unchanged
+        let state = self.repacketizer.start().with_packet(input)?;
+        let mut frames = Vec::new();
+        if frames.try_reserve(state.get_nb_frames() as usize).is_err() {
+            return Err(ErrorCode::alloc_fail());
+        }
+        for frame in 0..state.get_nb_frames() {
+            let mut packet = Vec::new();
+            if packet.try_reserve(input.len()).is_err() {
+                return Err(ErrorCode::alloc_fail());
+            }
+            let len = state.create_packet((frame, frame + 1), packet.spare_capacity_mut())?;
+            unsafe {
+                packet.set_len(len);
+            }
+            frames.push(packet);
+        }
+        drop(state);
+
+        let config = toc.0 & 0xFC;
+        if self.config != Some(config) {
+            self.flush()?;
+            self.config = Some(config);
+            self.frame_size = toc.frame_size();
+        }
+        if self.frames.try_reserve(frames.len()).is_err() {
+            return Err(ErrorCode::alloc_fail());
+        }
+        self.frames.append(&mut frames);
+
+        let frames_per_packet = self.frames_per_packet();
+        while self.frames.len() >= frames_per_packet {
+            self.combine(frames_per_packet)?;
+        }
+        Ok(())
+    }
+
+    //Combines first `count` pending frames into output packet
+    fn combine(&mut self, count: usize) -> Result<(), ErrorCode> {
+        let mut state = self.repacketizer.start();
+        let mut size = 2;
+        for frame in self.frames[..count].iter() {
+            state.add_packet(frame)?;
+            size += frame.len() + 2;
+        }
+
+        let mut packet = Vec::new();
+        if packet.try_reserve(size).is_err() {
+            return Err(ErrorCode::alloc_fail());
+        }
+        let len = state.create_full_packet(packet.spare_capacity_mut())?;
+        unsafe {
+            packet.set_len(len);
+        }
+        drop(state);
+
+        self.frames.drain(..count);
+        self.output.push_back(packet);
+        Ok(())
+    }
+
+    ///Combines all pending frames into output packet, even if it is shorter than target duration.
+    ///
+    ///This should be called at the end of stream.
+    pub fn flush(&mut self) -> Result<(), ErrorCode> {
+        if !self.frames.is_empty() {
+            self.combine(self.frames.len())?;
+        }
+        self.config = None;
+        Ok(())
+    }
+
+    #[inline(always)]
+    ///Returns next complete packet, if any
+    pub fn next_packet(&mut self) -> Option<Vec<u8>> {
+        self.output.pop_front()
     }
 }
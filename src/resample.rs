@@ -0,0 +1,476 @@
+//! Sample rate conversion
+//!
+//!Opus operates only at 8, 12, 16, 24 and 48 kHz, while a lot of audio is captured at 44.1 kHz or 96 kHz.
+//!
+//![Resampler](struct.Resampler.html) is windowed-sinc polyphase resampler for rational ratios,
+//!which is used by [ResamplingEncoder](struct.ResamplingEncoder.html) to feed arbitrary rate input into encoder and
+//!by [ResamplingDecoder](struct.ResamplingDecoder.html) to produce decoder output at arbitrary rate.
+//!
+//!Resampler has no group delay: first output sample corresponds to the first input sample,
+//!but output lags behind input until [flush](struct.Resampler.html#method.flush_float_to_vec) is called.
+
+use crate::{mem, utils, header, Encoder, Decoder, ErrorCode};
+use crate::utils::{i16_to_float, float_to_i16};
+
+use core::f64::consts::PI;
+use mem::alloc::vec::Vec;
+
+const MAX_PHASES: u32 = 1024;
+const MAX_PACKET_DURATION_MS: usize = 120;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+///Resampling quality
+pub enum Quality {
+    ///16 taps filter, suitable for voice
+    Low,
+    ///32 taps filter. Default
+    Medium,
+    ///64 taps filter with narrow transition band
+    High,
+}
+
+impl Quality {
+    #[inline(always)]
+    const fn half_taps(self) -> usize {
+        match self {
+            Self::Low => 8,
+            Self::Medium => 16,
+            Self::High => 32,
+        }
+    }
+
+    #[inline(always)]
+    const fn rolloff(self) -> f64 {
+        match self {
+            Self::Low => 0.85,
+            Self::Medium => 0.91,
+            Self::High => 0.95,
+        }
+    }
+}
+
+impl Default for Quality {
+    #[inline(always)]
+    fn default() -> Self {
+        Self::Medium
+    }
+}
+
+const fn gcd(mut a: u32, mut b: u32) -> u32 {
+    while b != 0 {
+        let rem = a % b;
+        a = b;
+        b = rem;
+    }
+    a
+}
+
+#[inline(always)]
+fn sinc(value: f64) -> f64 {
+    if value.abs() < 1e-9 {
+        1.0
+    } else {
+        libm::sin(PI * value) / (PI * value)
+    }
+}
+
+#[inline(always)]
+fn blackman(value: f64) -> f64 {
+    if value.abs() >= 1.0 {
+        0.0
+    } else {
+        0.42 + 0.5 * libm::cos(PI * value) + 0.08 * libm::cos(2.0 * PI * value)
+    }
+}
+
+///Windowed-sinc polyphase resampler
+///
+///Operates on interleaved samples with any number of channels.
+pub struct Resampler {
+    input_rate: u32,
+    output_rate: u32,
+    channels: usize,
+    //Interpolation factor
+    up: u32,
+    //Decimation factor
+    down: u32,
+    half_taps: usize,
+    taps: usize,
+    filter: Vec<f32>,
+    buffer: Vec<f32>,
+    //Float output of integer conversions
+    scratch: Vec<f32>,
+    pos: usize,
+    phase: u32,
+    total_input: u64,
+    total_output: u64,
+}
+
+impl Resampler {
+    ///Creates new resampler
+    ///
+    ///Returns `ErrorCode::BadArg` if any argument is 0 or ratio between rates requires more than 1024 filter phases
+    ///(i.e. greatest common divisor of rates is too small).
+    pub fn new(input_rate: u32, output_rate: u32, channels: usize, quality: Quality) -> Result<Self, ErrorCode> {
+        if input_rate == 0 || output_rate == 0 || channels == 0 {
+            return Err(ErrorCode::bad_arg());
+        }
+
+        let divisor = gcd(input_rate, output_rate);
+        let up = output_rate / divisor;
+        let down = input_rate / divisor;
+        if up > MAX_PHASES {
+            return Err(ErrorCode::bad_arg());
+        }
+
+        //When downsampling, cutoff must be lowered to output's Nyquist frequency and filter widened to keep transition band
+        let ratio = if up < down {
+            up as f64 / down as f64
+        } else {
+            1.0
+        };
+        let cutoff = quality.rolloff() * ratio;
+        let half_taps = libm::ceil(quality.half_taps() as f64 / ratio) as usize;
+        let taps = half_taps * 2;
+
+        let mut filter = Vec::new();
+        if filter.try_reserve_exact(taps * up as usize).is_err() {
+            return Err(ErrorCode::alloc_fail());
+        }
+        for phase in 0..up {
+            let frac = phase as f64 / up as f64;
+            let start = filter.len();
+            let mut sum = 0.0;
+            for tap in 0..taps {
+                let distance = tap as f64 - (half_taps - 1) as f64 - frac;
+                let coeff = cutoff * sinc(cutoff * distance) * blackman(distance / half_taps as f64);
+                sum += coeff;
+                filter.push(coeff as f32);
+            }
+
+            //Normalize to unity gain
+            let sum = sum as f32;
+            for coeff in filter[start..].iter_mut() {
+                *coeff /= sum;
+            }
+        }
+
+        let mut this = Self {
+            input_rate,
+            output_rate,
+            channels,
+            up,
+            down,
+            half_taps,
+            taps,
+            filter,
+            buffer: Vec::new(),
+            scratch: Vec::new(),
+            pos: 0,
+            phase: 0,
+            total_input: 0,
+            total_output: 0,
+        };
+        this.reset();
+        Ok(this)
+    }
+
+    #[inline(always)]
+    ///Returns input sample rate
+    pub fn input_rate(&self) -> u32 {
+        self.input_rate
+    }
+
+    #[inline(always)]
+    ///Returns output sample rate
+    pub fn output_rate(&self) -> u32 {
+        self.output_rate
+    }
+
+    #[inline(always)]
+    ///Returns number of channels
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    ///Resets state, dropping any buffered input
+    pub fn reset(&mut self) {
+        //History is pre-filled so that first output is centered on first input sample
+        self.buffer.clear();
+        self.buffer.resize((self.half_taps - 1) * self.channels, 0.0);
+        self.pos = 0;
+        self.phase = 0;
+        self.total_input = 0;
+        self.total_output = 0;
+    }
+
+    ///Returns number of output samples per channel, that are expected for `input` samples per channel.
+    pub fn output_len(&self, input: usize) -> usize {
+        let input = input as u64 * self.up as u64;
+        input.div_ceil(self.down as u64) as usize
+    }
+
+    fn run(&mut self, output: &mut Vec<f32>, limit: u64) -> usize {
+        let frames = self.buffer.len() / self.channels;
+        let initial_len = output.len();
+
+        while self.pos + self.taps <= frames && self.total_output < limit {
+            let coeffs = &self.filter[self.phase as usize * self.taps..][..self.taps];
+            let window = &self.buffer[self.pos * self.channels..][..self.taps * self.channels];
+            for channel in 0..self.channels {
+                let mut sum = 0.0;
+                for (coeff, sample) in coeffs.iter().zip(window[channel..].iter().step_by(self.channels)) {
+                    sum += coeff * sample;
+                }
+                output.push(sum);
+            }
+
+            self.total_output += 1;
+            self.phase += self.down;
+            self.pos += (self.phase / self.up) as usize;
+            self.phase %= self.up;
+        }
+
+        let consumed = core::cmp::min(self.pos, frames);
+        self.buffer.drain(..consumed * self.channels);
+        self.pos -= consumed;
+
+        (output.len() - initial_len) / self.channels
+    }
+
+    ///Resamples interleaved `input`, appending output to `output`.
+    ///
+    ///Returns number of samples per channel appended.
+    ///Trailing partial frame of `input` is ignored.
+    pub fn resample_float_to_vec(&mut self, input: &[f32], output: &mut Vec<f32>) -> usize {
+        let len = input.len() - input.len() % self.channels;
+        self.buffer.extend_from_slice(&input[..len]);
+        self.total_input += (len / self.channels) as u64;
+        self.run(output, u64::MAX)
+    }
+
+    ///Resamples interleaved `input`, appending output to `output`.
+    ///
+    ///Returns number of samples per channel appended.
+    ///Trailing partial frame of `input` is ignored.
+    pub fn resample_to_vec(&mut self, input: &[u16], output: &mut Vec<u16>) -> usize {
+        let len = input.len() - input.len() % self.channels;
+        self.buffer.extend(input[..len].iter().map(|sample| i16_to_float(*sample)));
+        self.total_input += (len / self.channels) as u64;
+
+        let mut scratch = core::mem::take(&mut self.scratch);
+        scratch.clear();
+        let result = self.run(&mut scratch, u64::MAX);
+        output.extend(scratch.iter().map(|sample| float_to_i16(*sample)));
+        self.scratch = scratch;
+        result
+    }
+
+    ///Produces remaining output, appending it to `output` and resets state.
+    ///
+    ///Returns number of samples per channel appended.
+    pub fn flush_float_to_vec(&mut self, output: &mut Vec<f32>) -> usize {
+        let expected = (self.total_input * self.up as u64).div_ceil(self.down as u64);
+        self.buffer.resize(self.buffer.len() + self.half_taps * self.channels, 0.0);
+        let result = self.run(output, expected);
+        self.reset();
+        result
+    }
+
+    ///Produces remaining output, appending it to `output` and resets state.
+    ///
+    ///Returns number of samples per channel appended.
+    pub fn flush_to_vec(&mut self, output: &mut Vec<u16>) -> usize {
+        let mut scratch = core::mem::take(&mut self.scratch);
+        scratch.clear();
+        let result = self.flush_float_to_vec(&mut scratch);
+        output.extend(scratch.iter().map(|sample| float_to_i16(*sample)));
+        self.scratch = scratch;
+        result
+    }
+}
+
+///Encoder accepting input at arbitrary sample rate
+///
+///Input is resampled to encoder's rate and split into frames of encoder's frame duration (20ms by default).
+pub struct ResamplingEncoder {
+    encoder: Encoder,
+    resampler: Resampler,
+    pending: Vec<f32>,
+    scratch: Vec<f32>,
+}
+
+impl ResamplingEncoder {
+    ///Creates new instance, converting from `input_rate` to `encoder`'s sample rate
+    pub fn new(mut encoder: Encoder, input_rate: u32, quality: Quality) -> Result<Self, ErrorCode> {
+        let rate = encoder.get_sample_rate()? as usize;
+        let channels = encoder.channels() as usize;
+        let resampler = Resampler::new(input_rate, rate as _, channels, quality)?;
+
+        Ok(Self {
+            encoder,
+            resampler,
+            pending: Vec::new(),
+            scratch: Vec::new(),
+        })
+    }
+
+    #[inline(always)]
+    ///Accesses underlying encoder
+    pub fn encoder(&mut self) -> &mut Encoder {
+        &mut self.encoder
+    }
+
+    #[inline(always)]
+    ///Returns number of samples per channel in single frame at encoder's rate, as configured by its frame duration
+    pub fn frame_size(&mut self) -> Result<usize, ErrorCode> {
+        utils::frame_size(&mut self.encoder)
+    }
+
+    ///Creates identification header for encoded stream.
+    ///
+    ///Unlike [OpusHead::from_encoder](../header/struct.OpusHead.html#method.from_encoder), input sample rate is set to rate before resampling.
+    pub fn opus_head(&mut self) -> Result<header::OpusHead, ErrorCode> {
+        let mut head = header::OpusHead::from_encoder(&mut self.encoder)?;
+        head.input_sample_rate = self.resampler.input_rate();
+        Ok(head)
+    }
+
+    #[inline]
+    ///Adds interleaved `input` to be encoded
+    pub fn push(&mut self, input: &[u16]) {
+        self.scratch.clear();
+        self.scratch.extend(input.iter().map(|sample| i16_to_float(*sample)));
+        self.resampler.resample_float_to_vec(&self.scratch, &mut self.pending);
+    }
+
+    #[inline]
+    ///Adds interleaved `input` to be encoded
+    pub fn push_float(&mut self, input: &[f32]) {
+        self.resampler.resample_float_to_vec(input, &mut self.pending);
+    }
+
+    ///Flushes resampler and pads pending input with silence up to full frame.
+    ///
+    ///Returns number of padding samples per channel (at encoder's rate), which can be used to trim end of stream.
+    pub fn flush(&mut self) -> Result<usize, ErrorCode> {
+        let frame_len = self.frame_size()? * self.encoder.channels() as usize;
+        self.resampler.flush_float_to_vec(&mut self.pending);
+
+        let padding = match self.pending.len() % frame_len {
+            0 => 0,
+            rem => frame_len - rem,
+        };
+        self.pending.resize(self.pending.len() + padding, 0.0);
+        Ok(padding / self.encoder.channels() as usize)
+    }
+
+    ///Encodes next frame, if enough input is pending, returning number of bytes written.
+    ///
+    ///Vector will be written into spare capacity, modifying its length on success.
+    ///
+    ///It is user responsibility to reserve correct amount of space
+    ///
+    ///Returns `None` if there is not enough pending input.
+    pub fn encode_to_vec(&mut self, output: &mut Vec<u8>) -> Result<Option<usize>, ErrorCode> {
+        let frame_len = self.frame_size()? * self.encoder.channels() as usize;
+        if self.pending.len() < frame_len {
+            return Ok(None);
+        }
+
+        let result = self.encoder.encode_float_to_vec(&self.pending[..frame_len], output)?;
+        self.pending.drain(..frame_len);
+        Ok(Some(result))
+    }
+}
+
+///Decoder producing output at arbitrary sample rate
+pub struct ResamplingDecoder {
+    decoder: Decoder,
+    resampler: Resampler,
+    scratch: Vec<f32>,
+    resampled: Vec<f32>,
+}
+
+impl ResamplingDecoder {
+    ///Creates new instance, converting from `decoder`'s sample rate to `output_rate`
+    pub fn new(mut decoder: Decoder, output_rate: u32, quality: Quality) -> Result<Self, ErrorCode> {
+        let rate = decoder.get_sample_rate()? as u32;
+        let resampler = Resampler::new(rate, output_rate, decoder.channels() as usize, quality)?;
+
+        Ok(Self {
+            decoder,
+            resampler,
+            scratch: Vec::new(),
+            resampled: Vec::new(),
+        })
+    }
+
+    #[inline(always)]
+    ///Accesses underlying decoder
+    pub fn decoder(&mut self) -> &mut Decoder {
+        &mut self.decoder
+    }
+
+    #[inline(always)]
+    ///Returns output sample rate
+    pub fn output_rate(&self) -> u32 {
+        self.resampler.output_rate()
+    }
+
+    fn decode_scratch(&mut self, input: &[u8], decode_len: usize, decode_fec: bool) -> Result<(), ErrorCode> {
+        let rate = self.resampler.input_rate() as usize;
+        let max_len = rate * MAX_PACKET_DURATION_MS / 1000 * self.decoder.channels() as usize;
+        let decode_len = match input.len() {
+            //Lost packet requires exact length
+            0 => decode_len,
+            _ => match utils::get_nb_samples(input, self.decoder.get_sample_rate()?) {
+                Ok(samples) => samples * self.decoder.channels() as usize,
+                Err(_) => max_len,
+            },
+        };
+        if decode_len > max_len {
+            return Err(ErrorCode::bad_arg());
+        }
+
+        self.scratch.resize(decode_len, 0.0);
+        let samples = self.decoder.decode_float_to_slice(input, &mut self.scratch, decode_fec)?;
+        self.scratch.truncate(samples * self.decoder.channels() as usize);
+        Ok(())
+    }
+
+    ///Decodes input packet, appending resampled output to `output` and returning number of samples per channel appended.
+    ///
+    ///`decode_len` is only used when `input` is empty (packet loss) and specifies number of samples (all channels) at decoder's rate to conceal.
+    ///
+    ///Refer to [Decoder::decode_to](../struct.Decoder.html#method.decode_to) for details
+    pub fn decode_float_to_vec(&mut self, input: &[u8], output: &mut Vec<f32>, decode_len: usize, decode_fec: bool) -> Result<usize, ErrorCode> {
+        self.decode_scratch(input, decode_len, decode_fec)?;
+        Ok(self.resampler.resample_float_to_vec(&self.scratch, output))
+    }
+
+    ///Decodes input packet, appending resampled output to `output` and returning number of samples per channel appended.
+    ///
+    ///`decode_len` is only used when `input` is empty (packet loss) and specifies number of samples (all channels) at decoder's rate to conceal.
+    ///
+    ///Refer to [Decoder::decode_to](../struct.Decoder.html#method.decode_to) for details
+    pub fn decode_to_vec(&mut self, input: &[u8], output: &mut Vec<u16>, decode_len: usize, decode_fec: bool) -> Result<usize, ErrorCode> {
+        self.decode_scratch(input, decode_len, decode_fec)?;
+        self.resampled.clear();
+        let result = self.resampler.resample_float_to_vec(&self.scratch, &mut self.resampled);
+        output.extend(self.resampled.iter().map(|sample| float_to_i16(*sample)));
+        Ok(result)
+    }
+
+    #[inline]
+    ///Produces remaining output of resampler, returning number of samples per channel appended.
+    pub fn flush_float_to_vec(&mut self, output: &mut Vec<f32>) -> usize {
+        self.resampler.flush_float_to_vec(output)
+    }
+
+    #[inline]
+    ///Produces remaining output of resampler, returning number of samples per channel appended.
+    pub fn flush_to_vec(&mut self, output: &mut Vec<u16>) -> usize {
+        self.resampler.flush_to_vec(output)
+    }
+}
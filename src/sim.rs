@@ -0,0 +1,397 @@
+//! Packet loss simulation
+//!
+//!Allows to evaluate encoder settings (e.g. [set_packet_loss](../struct.Encoder.html#method.set_packet_loss),
+//![InbandFec](../enum.InbandFec.html) or DRED duration) offline by running PCM through encoder,
+//!simulated network and decoder.
+//!
+//!## Network
+//!
+//![Network](struct.Network.html) describes loss model along with reordering and jitter.
+//!Receiver plays out frame `N` at time `N + playout_delay` (in frames), hence any packet delayed further is considered lost.
+//!
+//!## Recovery
+//!
+//!When packet is missing at its playout time, decoder attempts to recover it from the next packet (if it arrived in time)
+//!using [Recovery](enum.Recovery.html) method, otherwise it is concealed.
+
+use crate::{mem, utils, Encoder, Decoder, ErrorCode};
+
+use mem::alloc::vec::Vec;
+
+const DEFAULT_FRAME_DURATION_MS: usize = 20;
+const MAX_PACKET_SIZE: usize = 1275 * 3;
+
+///Pseudo random number generator used by simulation
+///
+///Uses splitmix64, so that results are reproducible for the same seed on any platform.
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    #[inline(always)]
+    ///Creates new instance with specified `seed`
+    pub const fn new(seed: u64) -> Self {
+        Self {
+            state: seed,
+        }
+    }
+
+    #[inline]
+    ///Generates next random number
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E3779B97F4A7C15);
+        let mut result = self.state;
+        result = (result ^ (result >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        result = (result ^ (result >> 27)).wrapping_mul(0x94D049BB133111EB);
+        result ^ (result >> 31)
+    }
+
+    #[inline]
+    ///Generates random number in range `[0, 1)`
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    #[inline]
+    ///Generates random number in range `[0, max]`
+    pub fn next_max(&mut self, max: u32) -> u32 {
+        (self.next_u64() % (max as u64 + 1)) as u32
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+///Packet loss model
+pub enum LossModel {
+    ///No loss
+    None,
+    ///Each packet is lost independently with specified probability
+    Bernoulli(f32),
+    ///Two state Markov model, producing bursts of loss
+    GilbertElliott {
+        ///Probability of transition from good to bad state
+        p_bad: f32,
+        ///Probability of transition from bad to good state
+        p_good: f32,
+        ///Probability of loss in good state
+        loss_good: f32,
+        ///Probability of loss in bad state
+        loss_bad: f32,
+    },
+}
+
+impl LossModel {
+    #[inline]
+    ///Creates simple Gilbert model with specified average loss rate and average burst length (in packets)
+    ///
+    ///All packets are lost in bad state, while none are lost in good state.
+    pub fn bursts(loss: f32, burst_len: f32) -> Self {
+        let p_good = 1.0 / burst_len.max(1.0);
+        let loss = loss.clamp(0.0, 0.99);
+        Self::GilbertElliott {
+            p_bad: p_good * loss / (1.0 - loss),
+            p_good,
+            loss_good: 0.0,
+            loss_bad: 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+///Network configuration
+pub struct Network {
+    ///Loss model
+    pub loss: LossModel,
+    ///Probability of packet to arrive after the next one
+    pub reorder: f32,
+    ///Maximum random delay of packet, in frames
+    pub jitter: u32,
+    ///Delay of playout, in frames. Packets delayed further are lost.
+    ///
+    ///Needs to be at least 1 for recovery using the next packet.
+    pub playout_delay: u32,
+    ///Seed for random number generator
+    pub seed: u64,
+}
+
+impl Default for Network {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            loss: LossModel::None,
+            reorder: 0.0,
+            jitter: 0,
+            playout_delay: 1,
+            seed: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+///Lost frame recovery method
+pub enum Recovery {
+    ///Always conceal
+    None,
+    ///Decode in-band FEC of the next packet
+    Fec,
+    #[cfg(feature = "dred")]
+    ///Decode DRED data of the next packet
+    Dred,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+///Outcome of single frame
+pub enum FrameStatus {
+    ///Packet was received and decoded
+    Decoded,
+    ///Packet was lost and recovered from the next packet
+    Recovered,
+    ///Packet was lost and concealed
+    Concealed,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+///Simulation result
+pub struct Report {
+    ///Status of each frame
+    pub frames: Vec<FrameStatus>,
+    ///Number of packets lost by loss model
+    pub lost: usize,
+    ///Number of packets arriving after playout time
+    pub late: usize,
+    ///Number of reordered packets
+    pub reordered: usize,
+    ///Total size of encoded packets
+    pub bytes: usize,
+    ///Signal to noise ratio of output against input in dB, after compensating encoder's delay
+    pub snr: f64,
+    ///Decoded output, interleaved and not delay compensated
+    pub output: Vec<f32>,
+}
+
+impl Report {
+    #[inline]
+    ///Returns number of frames with specified status
+    pub fn count(&self, status: FrameStatus) -> usize {
+        self.frames.iter().filter(|frame| **frame == status).count()
+    }
+
+    #[inline(always)]
+    ///Returns number of lost frames recovered from the next packet
+    pub fn recovered(&self) -> usize {
+        self.count(FrameStatus::Recovered)
+    }
+
+    #[inline(always)]
+    ///Returns number of concealed frames
+    pub fn concealed(&self) -> usize {
+        self.count(FrameStatus::Concealed)
+    }
+
+    #[inline]
+    ///Returns ratio of concealed frames to total number of frames
+    pub fn concealment_ratio(&self) -> f64 {
+        match self.frames.len() {
+            0 => 0.0,
+            len => self.concealed() as f64 / len as f64,
+        }
+    }
+}
+
+enum SimDecoder {
+    Plain(Decoder),
+    #[cfg(feature = "dred")]
+    Dred(crate::dred::Dred),
+}
+
+impl SimDecoder {
+    #[inline(always)]
+    fn decoder(&mut self) -> &mut Decoder {
+        match self {
+            Self::Plain(decoder) => decoder,
+            #[cfg(feature = "dred")]
+            Self::Dred(dred) => dred.decoder_mut(),
+        }
+    }
+}
+
+///Packet loss simulation
+pub struct Simulation {
+    network: Network,
+    recovery: Recovery,
+    frame_duration_ms: usize,
+}
+
+impl Simulation {
+    #[inline]
+    ///Creates new simulation using 20ms frames
+    pub const fn new(network: Network, recovery: Recovery) -> Self {
+        Self {
+            network,
+            recovery,
+            frame_duration_ms: DEFAULT_FRAME_DURATION_MS,
+        }
+    }
+
+    #[inline(always)]
+    ///Sets frame duration in milliseconds, which must be one of the durations allowed by encoder.
+    pub fn set_frame_duration(&mut self, duration_ms: usize) {
+        self.frame_duration_ms = duration_ms;
+    }
+
+    //Returns arrival delay (in frames) of each packet, `None` if packet is lost
+    fn transmit(&self, packets: usize, report: &mut Report) -> Vec<Option<u32>> {
+        let mut rng = Rng::new(self.network.seed);
+        let mut is_bad = false;
+        let mut result = Vec::with_capacity(packets);
+
+        for _ in 0..packets {
+            let is_lost = match self.network.loss {
+                LossModel::None => false,
+                LossModel::Bernoulli(loss) => rng.next_f32() < loss,
+                LossModel::GilbertElliott { p_bad, p_good, loss_good, loss_bad } => {
+                    is_bad = match is_bad {
+                        true => rng.next_f32() >= p_good,
+                        false => rng.next_f32() < p_bad,
+                    };
+                    match is_bad {
+                        true => rng.next_f32() < loss_bad,
+                        false => rng.next_f32() < loss_good,
+                    }
+                }
+            };
+
+            if is_lost {
+                report.lost += 1;
+                result.push(None);
+                continue;
+            }
+
+            let mut delay = rng.next_max(self.network.jitter);
+            if self.network.reorder > 0.0 && rng.next_f32() < self.network.reorder {
+                report.reordered += 1;
+                delay += 1;
+            }
+
+            if delay > self.network.playout_delay {
+                report.late += 1;
+                result.push(None);
+            } else {
+                result.push(Some(delay));
+            }
+        }
+
+        result
+    }
+
+    ///Runs interleaved `input` through `encoder`, network and decoder.
+    ///
+    ///Decoder is created with the same sample rate and channels as `encoder`.
+    ///Last partial frame is padded with silence.
+    pub fn run(&self, encoder: &mut Encoder, input: &[f32]) -> Result<Report, ErrorCode> {
+        let rate = encoder.get_sample_rate()?;
+        let channels = encoder.channels();
+        let delay = encoder.get_look_ahead()? as usize * channels as usize;
+        let frame_len = rate as usize * self.frame_duration_ms / 1000 * channels as usize;
+        if frame_len == 0 {
+            return Err(ErrorCode::bad_arg());
+        }
+
+        let mut packets = Vec::with_capacity(input.len().div_ceil(frame_len));
+        let mut frame = Vec::with_capacity(frame_len);
+        for chunk in input.chunks(frame_len) {
+            frame.clear();
+            frame.extend_from_slice(chunk);
+            frame.resize(frame_len, 0.0);
+
+            let mut packet = Vec::with_capacity(MAX_PACKET_SIZE);
+            encoder.encode_float_to_vec(&frame, &mut packet)?;
+            packets.push(packet);
+        }
+
+        let mut report = Report {
+            frames: Vec::with_capacity(packets.len()),
+            lost: 0,
+            late: 0,
+            reordered: 0,
+            bytes: packets.iter().map(|packet| packet.len()).sum(),
+            snr: 0.0,
+            output: Vec::with_capacity(packets.len() * frame_len),
+        };
+        let arrivals = self.transmit(packets.len(), &mut report);
+
+        let decoder = Decoder::new(channels, rate)?;
+        let mut decoder = match self.recovery {
+            Recovery::None | Recovery::Fec => SimDecoder::Plain(decoder),
+            #[cfg(feature = "dred")]
+            Recovery::Dred => SimDecoder::Dred(crate::dred::Dred::new(decoder)?),
+        };
+
+        let mut output = Vec::new();
+        output.resize(frame_len, 0.0);
+        for idx in 0..packets.len() {
+            let status = if arrivals[idx].is_some() {
+                decoder.decoder().decode_float_to_slice(&packets[idx], &mut output, false)?;
+                FrameStatus::Decoded
+            } else {
+                //Next packet is usable only if it arrives before playout of the current frame
+                let next = match arrivals.get(idx + 1) {
+                    Some(Some(delay)) if *delay < self.network.playout_delay => Some(packets[idx + 1].as_slice()),
+                    _ => None,
+                };
+
+                match (self.recovery, next) {
+                    (Recovery::Fec, Some(next)) => {
+                        decoder.decoder().decode_float_to_slice(next, &mut output, true)?;
+                        match utils::has_lbrr(next)? {
+                            true => FrameStatus::Recovered,
+                            false => FrameStatus::Concealed,
+                        }
+                    },
+                    #[cfg(feature = "dred")]
+                    (Recovery::Dred, Some(next)) => match decoder {
+                        SimDecoder::Dred(ref mut dred) => match utils::has_dred(next)? && dred.decode_float_to_slice(next, &mut output).is_ok() {
+                            true => FrameStatus::Recovered,
+                            false => {
+                                dred.decoder_mut().decode_float_to_slice(&[], &mut output, false)?;
+                                FrameStatus::Concealed
+                            }
+                        },
+                        SimDecoder::Plain(_) => unreachable!(),
+                    },
+                    _ => {
+                        decoder.decoder().decode_float_to_slice(&[], &mut output, false)?;
+                        FrameStatus::Concealed
+                    }
+                }
+            };
+
+            report.frames.push(status);
+            report.output.extend_from_slice(&output);
+        }
+
+        report.snr = snr(input, report.output.get(delay..).unwrap_or(&[]));
+        Ok(report)
+    }
+}
+
+///Computes signal to noise ratio of `actual` against `expected` in dB
+///
+///Only common length of both is compared.
+pub fn snr(expected: &[f32], actual: &[f32]) -> f64 {
+    let mut signal = 0.0;
+    let mut noise = 0.0;
+    for (expected, actual) in expected.iter().zip(actual.iter()) {
+        let expected = *expected as f64;
+        let diff = expected - *actual as f64;
+        signal += expected * expected;
+        noise += diff * diff;
+    }
+
+    if noise == 0.0 {
+        f64::INFINITY
+    } else {
+        10.0 * libm::log10(signal / noise)
+    }
+}
@@ -1,12 +1,124 @@
 //! Utility functions
 
-use crate::{sys, mem, SampleRate, Channels, ErrorCode};
+use crate::{sys, mem, Encoder, SampleRate, Channels, Bandwidth, FrameDuration, ErrorCode};
+
+use core::ptr;
+use mem::alloc::vec::Vec;
+
+//Extension ID, used by libopus to carry DRED data in packet padding
+const DRED_EXTENSION_ID: u8 = 126;
+//Temporary DRED extension prefix, which is to be removed once extension is finalized
+const DRED_EXPERIMENTAL_VERSION: u8 = 10;
+const DRED_EXPERIMENTAL_BYTES: usize = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+///Coding mode of Opus packet
+pub enum Mode {
+    ///SILK only, used for speech at lower bandwidths
+    Silk,
+    ///SILK for lower band and CELT for higher band
+    Hybrid,
+    ///CELT only, used for music and low delay
+    Celt,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+///Table-of-contents byte of Opus packet
+///
+///Refer to [RFC 6716](https://www.rfc-editor.org/rfc/rfc6716#section-3.1) for details
+pub struct Toc(pub u8);
+
+impl Toc {
+    #[inline]
+    ///Reads TOC of `input` packet
+    ///
+    ///Returns `ErrorCode::InvalidPacket` if packet is empty
+    pub fn parse(input: &[u8]) -> Result<Self, ErrorCode> {
+        match input.first() {
+            Some(toc) => Ok(Self(*toc)),
+            None => Err(ErrorCode::invalid_packet()),
+        }
+    }
+
+    #[inline(always)]
+    ///Returns configuration number (0..=31), combining mode, bandwidth and frame size
+    pub const fn config(&self) -> u8 {
+        self.0 >> 3
+    }
+
+    #[inline]
+    ///Returns coding mode
+    pub const fn mode(&self) -> Mode {
+        match self.config() {
+            0..=11 => Mode::Silk,
+            12..=15 => Mode::Hybrid,
+            _ => Mode::Celt,
+        }
+    }
+
+    #[inline]
+    ///Returns audio bandwidth
+    pub const fn bandwidth(&self) -> Bandwidth {
+        match self.config() {
+            0..=3 => Bandwidth::Narrow,
+            4..=7 => Bandwidth::Medium,
+            8..=11 => Bandwidth::Wide,
+            12..=13 => Bandwidth::Superwide,
+            14..=15 => Bandwidth::Full,
+            16..=19 => Bandwidth::Narrow,
+            20..=23 => Bandwidth::Wide,
+            24..=27 => Bandwidth::Superwide,
+            _ => Bandwidth::Full,
+        }
+    }
+
+    #[inline]
+    ///Returns number of samples in single frame at 48 kHz
+    pub const fn frame_size(&self) -> usize {
+        let config = self.config();
+        match self.mode() {
+            //10, 20, 40 and 60ms
+            Mode::Silk => match config & 3 {
+                3 => 2880,
+                size => 480 << size,
+            },
+            //10 and 20ms
+            Mode::Hybrid => 480 << (config & 1),
+            //2.5, 5, 10 and 20ms
+            Mode::Celt => 120 << (config & 3),
+        }
+    }
+
+    #[inline]
+    ///Returns number of channels
+    pub const fn channels(&self) -> Channels {
+        match self.0 & 0x4 {
+            0 => Channels::Mono,
+            _ => Channels::Stereo,
+        }
+    }
+
+    #[inline(always)]
+    ///Returns frame count code:
+    ///
+    ///- `0` - single frame;
+    ///- `1` - two frames of equal size;
+    ///- `2` - two frames of different size;
+    ///- `3` - arbitrary number of frames.
+    pub const fn code(&self) -> u8 {
+        self.0 & 0x3
+    }
+}
 
 #[inline]
 ///Gets the number of frames in an Opus packet.
 pub fn get_nb_frames(input: &[u8]) -> Result<usize, ErrorCode> {
+    let len = match input.len().try_into() {
+        Ok(len) => len,
+        Err(_) => return Err(ErrorCode::bad_arg()),
+    };
     let result = unsafe {
-        sys::opus_packet_get_nb_frames(input.as_ptr(), input.len() as _)
+        sys::opus_packet_get_nb_frames(input.as_ptr(), len)
     };
 
     map_sys_error!(result => result as _)
@@ -15,13 +127,130 @@ pub fn get_nb_frames(input: &[u8]) -> Result<usize, ErrorCode> {
 #[inline]
 ///Gets the number of samples of an Opus packet.
 pub fn get_nb_samples(input: &[u8], rate: SampleRate) -> Result<usize, ErrorCode> {
+    let len = match input.len().try_into() {
+        Ok(len) => len,
+        Err(_) => return Err(ErrorCode::bad_arg()),
+    };
     let result = unsafe {
-        sys::opus_packet_get_nb_samples(input.as_ptr(), input.len() as _, rate as _)
+        sys::opus_packet_get_nb_samples(input.as_ptr(), len, rate as _)
     };
 
     map_sys_error!(result => result as _)
 }
 
+#[inline]
+///Gets the bandwidth of an Opus packet.
+pub fn get_bandwidth(input: &[u8]) -> Result<Bandwidth, ErrorCode> {
+    if input.is_empty() {
+        return Err(ErrorCode::invalid_packet());
+    }
+
+    let result = unsafe {
+        sys::opus_packet_get_bandwidth(input.as_ptr())
+    };
+
+    map_sys_error!(result => result.into())
+}
+
+#[inline]
+///Gets the number of channels of an Opus packet.
+pub fn get_nb_channels(input: &[u8]) -> Result<Channels, ErrorCode> {
+    Toc::parse(input).map(|toc| toc.channels())
+}
+
+///Gets padding of an Opus packet.
+///
+///Padding is only present in packets with arbitrary number of frames (code 3) and may carry extensions.
+pub fn get_padding(input: &[u8]) -> Result<&[u8], ErrorCode> {
+    let len = match i32::try_from(input.len()) {
+        Ok(len) => len,
+        Err(_) => return Err(ErrorCode::bad_arg()),
+    };
+    let mut frames = [ptr::null(); 48];
+    let mut sizes = [0i16; 48];
+    let result = unsafe {
+        sys::opus_packet_parse(input.as_ptr(), len, ptr::null_mut(), frames.as_mut_ptr(), sizes.as_mut_ptr(), ptr::null_mut())
+    };
+
+    map_sys_error!(result => {
+        let last = result as usize - 1;
+        let end = unsafe {
+            frames[last].offset_from(input.as_ptr()) as usize
+        } + sizes[last] as usize;
+        &input[end..]
+    })
+}
+
+//Splits first extension in padding, returning its ID, payload and remaining extensions.
+fn split_extension(data: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let id = data[0] >> 1;
+    let is_long = data[0] & 1 == 1;
+
+    if id == 0 && is_long {
+        //Single byte of padding
+        Some((id, &[], &data[1..]))
+    } else if id > 0 && id < 32 {
+        //Short extension, with at most 1 byte of payload
+        let len = 1 + is_long as usize;
+        if data.len() < len {
+            return None;
+        }
+        Some((id, &data[1..len], &data[len..]))
+    } else if !is_long {
+        //Extension spans till the end of padding
+        Some((id, &data[1..], &[]))
+    } else {
+        let mut pos = 1;
+        let mut len = 0;
+        loop {
+            let byte = *data.get(pos)?;
+            pos += 1;
+            len += byte as usize;
+            if byte != 255 {
+                break;
+            }
+        }
+
+        if data.len() - pos < len {
+            return None;
+        }
+        Some((id, &data[pos..pos + len], &data[pos + len..]))
+    }
+}
+
+///Checks whether Opus packet contains DRED (Deep REDundancy) data.
+///
+///This only inspects packet's padding and doesn't require `dred` feature.
+pub fn has_dred(input: &[u8]) -> Result<bool, ErrorCode> {
+    let mut extensions = get_padding(input)?;
+    while !extensions.is_empty() {
+        let (id, payload, rest) = match split_extension(extensions) {
+            Some(extension) => extension,
+            None => break,
+        };
+        if id == DRED_EXTENSION_ID && payload.len() > DRED_EXPERIMENTAL_BYTES && payload[0] == b'D' && payload[1] == DRED_EXPERIMENTAL_VERSION {
+            return Ok(true);
+        }
+        extensions = rest;
+    }
+
+    Ok(false)
+}
+
+#[inline]
+///Checks whether Opus packet contains LBRR (in-band FEC) data for the previous frame.
+pub fn has_lbrr(input: &[u8]) -> Result<bool, ErrorCode> {
+    let len = match input.len().try_into() {
+        Ok(len) => len,
+        Err(_) => return Err(ErrorCode::bad_arg()),
+    };
+    let result = unsafe {
+        sys::opus_packet_has_lbrr(input.as_ptr(), len)
+    };
+
+    map_sys_error!(result => result == 1)
+}
+
 #[inline]
 ///Applies soft-clipping to bring a float signal within the [-1,1] range.
 ///
@@ -40,3 +269,68 @@ pub fn soft_clip(input: &mut [f32], channels: Channels) {
         )
     }
 }
+
+#[inline(always)]
+pub(crate) fn i16_to_float(value: u16) -> f32 {
+    value as i16 as f32 / 32768.0
+}
+
+#[inline(always)]
+pub(crate) fn float_to_i16(value: f32) -> u16 {
+    let value = value * 32768.0;
+    let value = if value >= 0.0 {
+        value + 0.5
+    } else {
+        value - 0.5
+    };
+    (value as i32).clamp(i16::MIN as i32, i16::MAX as i32) as i16 as u16
+}
+
+//Returns number of samples per channel in single frame of `encoder`, defaulting to 20ms
+pub(crate) fn frame_size(encoder: &mut Encoder) -> Result<usize, ErrorCode> {
+    let rate = encoder.get_sample_rate()? as usize;
+    //In units of 0.5ms
+    let duration = match encoder.get_frame_duration()? {
+        FrameDuration::Size2_5 => 5,
+        FrameDuration::Size5 => 10,
+        FrameDuration::Size10 => 20,
+        FrameDuration::SizeArg | FrameDuration::Size20 => 40,
+        FrameDuration::Size40 => 80,
+        FrameDuration::Size60 => 120,
+        FrameDuration::Size80 => 160,
+        FrameDuration::Size100 => 200,
+        FrameDuration::Size120 => 240,
+    };
+    Ok(rate / 2000 * duration)
+}
+
+//Interleaves planar `input` into `output`, returning number of samples per channel
+pub(crate) fn interleave(input: &[&[f32]], output: &mut Vec<f32>) -> Result<usize, ErrorCode> {
+    let frames = match input.first() {
+        Some(channel) => channel.len(),
+        None => return Err(ErrorCode::bad_arg()),
+    };
+    if input.iter().any(|channel| channel.len() != frames) {
+        return Err(ErrorCode::bad_arg());
+    }
+
+    let channels = input.len();
+    output.clear();
+    output.resize(frames * channels, 0.0);
+    for (idx, channel) in input.iter().enumerate() {
+        for (sample, value) in output[idx..].iter_mut().step_by(channels).zip(channel.iter()) {
+            *sample = *value;
+        }
+    }
+    Ok(frames)
+}
+
+//Splits interleaved `input` into planar `output`
+pub(crate) fn deinterleave(input: &[f32], output: &mut [&mut [f32]]) {
+    let channels = output.len();
+    for (idx, channel) in output.iter_mut().enumerate() {
+        for (sample, value) in channel.iter_mut().zip(input[idx..].iter().step_by(channels)) {
+            *sample = *value;
+        }
+    }
+}
@@ -0,0 +1,370 @@
+//! RIFF/WAVE PCM file helpers
+//!
+//!Supports 16-bit integer and 32-bit float PCM, including `WAVE_FORMAT_EXTENSIBLE` with channel mask.
+//!
+//!Samples are interleaved and use the same representation as encoder/decoder (`u16` as bits of `i16` and `f32`),
+//!so that content of the file can be passed directly to the encoder and decoder output written into file.
+//!
+//!## Multichannel files
+//!
+//!WAV channels are ordered according to bits set in channel mask, while Opus mapping family 1 expects Vorbis channel order.
+//![multistream_config](struct.WavReader.html#method.multistream_config) produces encoder configuration that consumes samples in WAV order,
+//!while encoded stream is still described by [Config::vorbis](../multistream/struct.Config.html#method.vorbis).
+
+use crate::{mem, layout, multistream, Channels, ErrorCode, SampleRate};
+use crate::layout::Layout;
+use crate::utils::{i16_to_float, float_to_i16};
+
+use mem::alloc::vec::Vec;
+
+const FORMAT_PCM: u16 = 0x0001;
+const FORMAT_IEEE_FLOAT: u16 = 0x0003;
+const FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+
+//Tail of KSDATAFORMAT_SUBTYPE_PCM/KSDATAFORMAT_SUBTYPE_IEEE_FLOAT GUID, first 2 bytes are format tag
+const SUBFORMAT_GUID_TAIL: [u8; 14] = [0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x80, 0x00, 0x00, 0xAA, 0x00, 0x38, 0x9B, 0x71];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+///PCM sample format
+pub enum SampleFormat {
+    ///16-bit signed integer
+    Int16,
+    ///32-bit IEEE float
+    Float32,
+}
+
+impl SampleFormat {
+    #[inline(always)]
+    ///Returns size of single sample in bytes
+    pub const fn size(self) -> usize {
+        match self {
+            Self::Int16 => 2,
+            Self::Float32 => 4,
+        }
+    }
+
+    #[inline(always)]
+    const fn tag(self) -> u16 {
+        match self {
+            Self::Int16 => FORMAT_PCM,
+            Self::Float32 => FORMAT_IEEE_FLOAT,
+        }
+    }
+}
+
+///WAV file reader over in-memory data
+pub struct WavReader<'a> {
+    format: SampleFormat,
+    channels: u16,
+    sample_rate: u32,
+    channel_mask: Option<u32>,
+    data: &'a [u8],
+}
+
+impl<'a> WavReader<'a> {
+    ///Parses WAV file
+    ///
+    ///Returns `ErrorCode::InvalidPacket` if file is malformed or uses unsupported sample format
+    pub fn new(input: &'a [u8]) -> Result<Self, ErrorCode> {
+        if input.len() < 12 || &input[..4] != b"RIFF" || &input[8..12] != b"WAVE" {
+            return Err(ErrorCode::invalid_packet());
+        }
+
+        let mut fmt = None;
+        let mut pos = 12;
+        while pos + 8 <= input.len() {
+            let id = &input[pos..pos + 4];
+            let size = u32::from_le_bytes([input[pos + 4], input[pos + 5], input[pos + 6], input[pos + 7]]) as usize;
+            pos += 8;
+
+            match id {
+                b"fmt " => match input.get(pos..).and_then(|rest| rest.get(..size)) {
+                    Some(chunk) => fmt = Some(Self::parse_fmt(chunk)?),
+                    None => return Err(ErrorCode::invalid_packet()),
+                },
+                b"data" => {
+                    let (format, channels, sample_rate, channel_mask) = match fmt {
+                        Some(fmt) => fmt,
+                        None => return Err(ErrorCode::invalid_packet()),
+                    };
+                    //Streaming writers leave size unset, so clamp it to what is actually available
+                    let data = &input[pos..];
+                    let size = core::cmp::min(size, data.len());
+                    let frame_size = format.size() * channels as usize;
+                    let data = &data[..size - size % frame_size];
+
+                    return Ok(Self {
+                        format,
+                        channels,
+                        sample_rate,
+                        channel_mask,
+                        data,
+                    });
+                },
+                _ => (),
+            }
+
+            //Chunks are padded to even size
+            pos = pos.saturating_add(size).saturating_add(size & 1);
+        }
+
+        Err(ErrorCode::invalid_packet())
+    }
+
+    fn parse_fmt(chunk: &[u8]) -> Result<(SampleFormat, u16, u32, Option<u32>), ErrorCode> {
+        if chunk.len() < 16 {
+            return Err(ErrorCode::invalid_packet());
+        }
+
+        let mut tag = u16::from_le_bytes([chunk[0], chunk[1]]);
+        let channels = u16::from_le_bytes([chunk[2], chunk[3]]);
+        let sample_rate = u32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]);
+        let bits = u16::from_le_bytes([chunk[14], chunk[15]]);
+        let mut channel_mask = None;
+
+        if tag == FORMAT_EXTENSIBLE {
+            if chunk.len() < 40 || chunk[26..40] != SUBFORMAT_GUID_TAIL {
+                return Err(ErrorCode::invalid_packet());
+            }
+            channel_mask = match u32::from_le_bytes([chunk[20], chunk[21], chunk[22], chunk[23]]) {
+                0 => None,
+                mask => Some(mask),
+            };
+            tag = u16::from_le_bytes([chunk[24], chunk[25]]);
+        }
+
+        if channels == 0 || sample_rate == 0 {
+            return Err(ErrorCode::invalid_packet());
+        }
+
+        let format = match (tag, bits) {
+            (FORMAT_PCM, 16) => SampleFormat::Int16,
+            (FORMAT_IEEE_FLOAT, 32) => SampleFormat::Float32,
+            _ => return Err(ErrorCode::invalid_packet()),
+        };
+
+        Ok((format, channels, sample_rate, channel_mask))
+    }
+
+    #[inline(always)]
+    ///Returns sample format
+    pub fn format(&self) -> SampleFormat {
+        self.format
+    }
+
+    #[inline(always)]
+    ///Returns number of channels
+    pub fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    #[inline(always)]
+    ///Returns sample rate
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    #[inline(always)]
+    ///Returns channel mask, if specified by file
+    pub fn channel_mask(&self) -> Option<u32> {
+        self.channel_mask
+    }
+
+    #[inline(always)]
+    ///Returns number of samples per channel
+    pub fn samples(&self) -> usize {
+        self.data.len() / (self.format.size() * self.channels as usize)
+    }
+
+    #[inline(always)]
+    ///Returns raw content of data chunk
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    ///Verifies that file matches expected sample rate and channels
+    ///
+    ///Returns `ErrorCode::BadArg` on mismatch
+    pub fn check(&self, rate: SampleRate, channels: Channels) -> Result<(), ErrorCode> {
+        if self.sample_rate != rate as u32 || self.channels != channels as u16 {
+            Err(ErrorCode::bad_arg())
+        } else {
+            Ok(())
+        }
+    }
+
+    ///Returns sample rate of the file as `SampleRate`
+    ///
+    ///Returns `ErrorCode::BadArg` if it is not supported by Opus
+    pub fn opus_sample_rate(&self) -> Result<SampleRate, ErrorCode> {
+        match self.sample_rate {
+            8000 => Ok(SampleRate::Hz8000),
+            12000 => Ok(SampleRate::Hz12000),
+            16000 => Ok(SampleRate::Hz16000),
+            24000 => Ok(SampleRate::Hz24000),
+            48000 => Ok(SampleRate::Hz48000),
+            _ => Err(ErrorCode::bad_arg()),
+        }
+    }
+
+    ///Appends all samples to `output`, converting them to 16-bit integer if necessary
+    pub fn read_to_vec(&self, output: &mut Vec<u16>) {
+        match self.format {
+            SampleFormat::Int16 => output.extend(self.data.chunks_exact(2).map(|sample| u16::from_le_bytes([sample[0], sample[1]]))),
+            SampleFormat::Float32 => output.extend(self.data.chunks_exact(4).map(|sample| float_to_i16(f32::from_le_bytes([sample[0], sample[1], sample[2], sample[3]])))),
+        }
+    }
+
+    ///Appends all samples to `output`, converting them to float if necessary
+    pub fn read_float_to_vec(&self, output: &mut Vec<f32>) {
+        match self.format {
+            SampleFormat::Int16 => output.extend(self.data.chunks_exact(2).map(|sample| i16_to_float(u16::from_le_bytes([sample[0], sample[1]])))),
+            SampleFormat::Float32 => output.extend(self.data.chunks_exact(4).map(|sample| f32::from_le_bytes([sample[0], sample[1], sample[2], sample[3]]))),
+        }
+    }
+
+    ///Returns channel layout of the file
+    ///
+    ///If file has no channel mask, then default WAV layout for number of channels is assumed.
+    ///
+    ///Returns `ErrorCode::BadArg` if layout cannot be determined or doesn't match number of channels
+    pub fn layout(&self) -> Result<Layout, ErrorCode> {
+        let layout = match self.channel_mask {
+            Some(mask) => Layout::from_mask(mask),
+            None => Layout::new(layout::Order::Wav, self.channels as usize),
+        };
+
+        match layout {
+            Some(layout) if layout.channels() == self.channels as usize => Ok(layout),
+            _ => Err(ErrorCode::bad_arg()),
+        }
+    }
+
+    #[inline]
+    ///Creates multistream configuration for mapping family 1, that accepts samples in order of the file.
+    ///
+    ///Refer to [Layout::multistream_config](../layout/struct.Layout.html#method.multistream_config) for details
+    pub fn multistream_config<const CH: usize>(&self) -> Result<multistream::Config<CH>, ErrorCode> {
+        self.layout()?.multistream_config()
+    }
+}
+
+///WAV file writer
+///
+///Output is accumulated in memory until [finish](#method.finish) is called
+pub struct WavWriter {
+    format: SampleFormat,
+    out: Vec<u8>,
+}
+
+impl WavWriter {
+    const HEADER_SIZE: usize = 12 + 8 + 16 + 8;
+    const EXTENSIBLE_HEADER_SIZE: usize = 12 + 8 + 40 + 8;
+
+    #[inline]
+    ///Creates writer for mono or stereo file
+    pub fn new(rate: SampleRate, channels: Channels, format: SampleFormat) -> Self {
+        let mut out = Vec::with_capacity(Self::HEADER_SIZE);
+        Self::write_header(&mut out, rate as u32, channels as u16, format, None);
+        Self {
+            format,
+            out,
+        }
+    }
+
+    ///Creates writer for multichannel file using `WAVE_FORMAT_EXTENSIBLE`
+    ///
+    ///If `channel_mask` is 0, then mask of standard layout for number of channels is used.
+    ///
+    ///Returns `ErrorCode::BadArg` if `channels` is 0 or greater than 255, or `channel_mask` has more speakers than channels.
+    pub fn multichannel(rate: SampleRate, channels: u16, channel_mask: u32, format: SampleFormat) -> Result<Self, ErrorCode> {
+        let channel_mask = match channel_mask {
+            0 => Layout::vorbis(channels as usize).map(|layout| layout.mask()).unwrap_or(0),
+            mask => mask,
+        };
+        if channels == 0 || channels > u8::MAX as u16 || channel_mask.count_ones() > channels as u32 {
+            return Err(ErrorCode::bad_arg());
+        }
+
+        let mut out = Vec::with_capacity(Self::EXTENSIBLE_HEADER_SIZE);
+        Self::write_header(&mut out, rate as u32, channels, format, Some(channel_mask));
+        Ok(Self {
+            format,
+            out,
+        })
+    }
+
+    fn write_header(out: &mut Vec<u8>, rate: u32, channels: u16, format: SampleFormat, channel_mask: Option<u32>) {
+        let block_align = channels * format.size() as u16;
+        let bits = format.size() as u16 * 8;
+
+        out.extend_from_slice(b"RIFF");
+        out.extend_from_slice(&[0; 4]);
+        out.extend_from_slice(b"WAVE");
+        out.extend_from_slice(b"fmt ");
+        match channel_mask {
+            Some(_) => {
+                out.extend_from_slice(&40u32.to_le_bytes());
+                out.extend_from_slice(&FORMAT_EXTENSIBLE.to_le_bytes());
+            },
+            None => {
+                out.extend_from_slice(&16u32.to_le_bytes());
+                out.extend_from_slice(&format.tag().to_le_bytes());
+            }
+        }
+        out.extend_from_slice(&channels.to_le_bytes());
+        out.extend_from_slice(&rate.to_le_bytes());
+        out.extend_from_slice(&(rate * block_align as u32).to_le_bytes());
+        out.extend_from_slice(&block_align.to_le_bytes());
+        out.extend_from_slice(&bits.to_le_bytes());
+        if let Some(channel_mask) = channel_mask {
+            out.extend_from_slice(&22u16.to_le_bytes());
+            out.extend_from_slice(&bits.to_le_bytes());
+            out.extend_from_slice(&channel_mask.to_le_bytes());
+            out.extend_from_slice(&format.tag().to_le_bytes());
+            out.extend_from_slice(&SUBFORMAT_GUID_TAIL);
+        }
+        out.extend_from_slice(b"data");
+        out.extend_from_slice(&[0; 4]);
+    }
+
+    ///Writes interleaved 16-bit samples, converting them to file's format if necessary
+    pub fn write(&mut self, input: &[u16]) {
+        self.out.reserve(input.len() * self.format.size());
+        match self.format {
+            SampleFormat::Int16 => for sample in input {
+                self.out.extend_from_slice(&sample.to_le_bytes());
+            },
+            SampleFormat::Float32 => for sample in input {
+                self.out.extend_from_slice(&i16_to_float(*sample).to_le_bytes());
+            },
+        }
+    }
+
+    ///Writes interleaved float samples, converting them to file's format if necessary
+    pub fn write_float(&mut self, input: &[f32]) {
+        self.out.reserve(input.len() * self.format.size());
+        match self.format {
+            SampleFormat::Int16 => for sample in input {
+                self.out.extend_from_slice(&float_to_i16(*sample).to_le_bytes());
+            },
+            SampleFormat::Float32 => for sample in input {
+                self.out.extend_from_slice(&sample.to_le_bytes());
+            },
+        }
+    }
+
+    ///Finalizes file, returning its content
+    pub fn finish(mut self) -> Vec<u8> {
+        let header_size = match &self.out[20..22] {
+            tag if tag == FORMAT_EXTENSIBLE.to_le_bytes() => Self::EXTENSIBLE_HEADER_SIZE,
+            _ => Self::HEADER_SIZE,
+        };
+        let data_size = (self.out.len() - header_size) as u32;
+        let riff_size = (self.out.len() - 8) as u32;
+
+        self.out[4..8].copy_from_slice(&riff_size.to_le_bytes());
+        self.out[header_size - 4..header_size].copy_from_slice(&data_size.to_le_bytes());
+        self.out
+    }
+}
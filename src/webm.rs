@@ -0,0 +1,816 @@
+//! Minimal Matroska/WebM encapsulation of Opus track
+//!
+//!Implements subset of [Matroska](https://www.matroska.org/technical/elements.html) required to
+//!store single Opus audio track as described by [Opus in Matroska](https://wiki.xiph.org/MatroskaOpus).
+//!
+//!## Muxer
+//!
+//![Muxer](struct.Muxer.html) places each packet into its own `SimpleBlock`, grouping them into
+//!clusters of up to 1 second. `CodecPrivate` is set to `OpusHead`, `CodecDelay` to its pre-skip and
+//!`SeekPreRoll` to 80ms. Each cluster is referenced by `Cues` to allow seeking.
+//!
+//!## Demuxer
+//!
+//![Demuxer](struct.Demuxer.html) yields packets of the first Opus track together with their timestamps.
+//!Unknown-size segments and clusters (as produced by live recorders) are supported, but laced blocks are not.
+
+use crate::{mem, utils, ErrorCode, SampleRate};
+use crate::header::OpusHead;
+
+use mem::alloc::vec::Vec;
+
+const EBML: u32 = 0x1A45DFA3;
+const EBML_VERSION: u32 = 0x4286;
+const EBML_READ_VERSION: u32 = 0x42F7;
+const EBML_MAX_ID_LENGTH: u32 = 0x42F2;
+const EBML_MAX_SIZE_LENGTH: u32 = 0x42F3;
+const DOC_TYPE: u32 = 0x4282;
+const DOC_TYPE_VERSION: u32 = 0x4287;
+const DOC_TYPE_READ_VERSION: u32 = 0x4285;
+
+const SEGMENT: u32 = 0x18538067;
+
+const SEEK_HEAD: u32 = 0x114D9B74;
+const SEEK: u32 = 0x4DBB;
+const SEEK_ID: u32 = 0x53AB;
+const SEEK_POSITION: u32 = 0x53AC;
+
+const INFO: u32 = 0x1549A966;
+const TIMECODE_SCALE: u32 = 0x2AD7B1;
+const DURATION: u32 = 0x4489;
+const MUXING_APP: u32 = 0x4D80;
+const WRITING_APP: u32 = 0x5741;
+
+const TRACKS: u32 = 0x1654AE6B;
+const TRACK_ENTRY: u32 = 0xAE;
+const TRACK_NUMBER: u32 = 0xD7;
+const TRACK_UID: u32 = 0x73C5;
+const TRACK_TYPE: u32 = 0x83;
+const FLAG_LACING: u32 = 0x9C;
+const CODEC_ID: u32 = 0x86;
+const CODEC_PRIVATE: u32 = 0x63A2;
+const CODEC_DELAY: u32 = 0x56AA;
+const SEEK_PRE_ROLL: u32 = 0x56BB;
+const AUDIO: u32 = 0xE1;
+const SAMPLING_FREQUENCY: u32 = 0xB5;
+const CHANNELS: u32 = 0x9F;
+
+const CLUSTER: u32 = 0x1F43B675;
+const TIMECODE: u32 = 0xE7;
+const SIMPLE_BLOCK: u32 = 0xA3;
+const BLOCK_GROUP: u32 = 0xA0;
+const BLOCK: u32 = 0xA1;
+
+const CUES: u32 = 0x1C53BB6B;
+const CUE_POINT: u32 = 0xBB;
+const CUE_TIME: u32 = 0xB3;
+const CUE_TRACK_POSITIONS: u32 = 0xB7;
+const CUE_TRACK: u32 = 0xF7;
+const CUE_CLUSTER_POSITION: u32 = 0xF1;
+
+///Opus codec identifier within Matroska
+pub const CODEC_ID_OPUS: &str = "A_OPUS";
+///Recommended seek pre-roll for Opus in nanoseconds (80ms)
+pub const OPUS_SEEK_PRE_ROLL: u64 = 80_000_000;
+
+const APP_NAME: &str = "opusic-c";
+const TRACK: u64 = 1;
+//Timestamps are stored in milliseconds
+const DEFAULT_TIMECODE_SCALE: u64 = 1_000_000;
+const CLUSTER_DURATION_MS: u64 = 1000;
+const NANOS_PER_SECOND: u64 = 1_000_000_000;
+const UNKNOWN_SIZE: u64 = u64::MAX;
+
+#[inline(always)]
+const fn samples_to_nanos(samples: u64) -> u64 {
+    samples * NANOS_PER_SECOND / SampleRate::Hz48000 as u64
+}
+
+fn write_id(out: &mut Vec<u8>, id: u32) {
+    let bytes = id.to_be_bytes();
+    let skip = (id.leading_zeros() / 8) as usize;
+    out.extend_from_slice(&bytes[skip..]);
+}
+
+fn write_size(out: &mut Vec<u8>, size: u64) {
+    let mut len = 1;
+    //All ones value is reserved for unknown size
+    while len < 8 && size >= (1u64 << (7 * len)) - 1 {
+        len += 1;
+    }
+
+    let value = size | (1u64 << (7 * len));
+    out.extend_from_slice(&value.to_be_bytes()[8 - len..]);
+}
+
+fn write_uint(out: &mut Vec<u8>, id: u32, value: u64) {
+    let len = match value {
+        0 => 1,
+        value => 8 - (value.leading_zeros() / 8) as usize,
+    };
+    write_id(out, id);
+    write_size(out, len as _);
+    out.extend_from_slice(&value.to_be_bytes()[8 - len..]);
+}
+
+fn write_uint_fixed(out: &mut Vec<u8>, id: u32, value: u64) {
+    write_id(out, id);
+    write_size(out, 8);
+    out.extend_from_slice(&value.to_be_bytes());
+}
+
+fn write_float(out: &mut Vec<u8>, id: u32, value: f64) {
+    write_id(out, id);
+    write_size(out, 8);
+    out.extend_from_slice(&value.to_be_bytes());
+}
+
+fn write_bytes(out: &mut Vec<u8>, id: u32, value: &[u8]) {
+    write_id(out, id);
+    write_size(out, value.len() as _);
+    out.extend_from_slice(value);
+}
+
+fn write_master(out: &mut Vec<u8>, id: u32, body: &[u8]) {
+    write_bytes(out, id, body)
+}
+
+///WebM muxer for single Opus track
+///
+///Output is accumulated in memory and produced by [finish](#method.finish)
+pub struct Muxer {
+    codec_private: Vec<u8>,
+    channels: u8,
+    pre_skip: u16,
+    clusters: Vec<u8>,
+    cluster: Vec<u8>,
+    cluster_time: u64,
+    //Pairs of cluster time and its offset within `clusters`
+    cues: Vec<(u64, u64)>,
+    samples: u64,
+}
+
+impl Muxer {
+    ///Creates new muxer using provided identification header
+    ///
+    ///`CodecDelay` is derived from `head.pre_skip`, hence it should be created using encoder's
+    ///look ahead (see [OpusHead::from_encoder](../header/struct.OpusHead.html#method.from_encoder))
+    pub fn new(head: &OpusHead) -> Self {
+        Self {
+            codec_private: head.to_vec(),
+            channels: head.channels,
+            pre_skip: head.pre_skip,
+            clusters: Vec::new(),
+            cluster: Vec::new(),
+            cluster_time: 0,
+            cues: Vec::new(),
+            samples: 0,
+        }
+    }
+
+    #[inline(always)]
+    ///Returns total duration of added packets in samples at 48 kHz
+    pub fn samples(&self) -> u64 {
+        self.samples
+    }
+
+    ///Adds packet as `SimpleBlock`
+    ///
+    ///Packet duration is determined from its TOC and returns error if packet is invalid.
+    pub fn add_packet(&mut self, packet: &[u8]) -> Result<(), ErrorCode> {
+        let duration = utils::get_nb_samples(packet, SampleRate::Hz48000)?;
+        let time = samples_to_nanos(self.samples) / DEFAULT_TIMECODE_SCALE;
+
+        if self.cluster.is_empty() || time - self.cluster_time >= CLUSTER_DURATION_MS {
+            self.flush_cluster();
+            self.cluster_time = time;
+        }
+
+        let relative_time = (time - self.cluster_time) as i16;
+        write_id(&mut self.cluster, SIMPLE_BLOCK);
+        write_size(&mut self.cluster, 4 + packet.len() as u64);
+        self.cluster.push(0x80 | TRACK as u8);
+        self.cluster.extend_from_slice(&relative_time.to_be_bytes());
+        //Every Opus packet is keyframe
+        self.cluster.push(0x80);
+        self.cluster.extend_from_slice(packet);
+
+        self.samples += duration as u64;
+        Ok(())
+    }
+
+    fn flush_cluster(&mut self) {
+        if self.cluster.is_empty() {
+            return;
+        }
+
+        let mut timecode = Vec::with_capacity(10);
+        write_uint(&mut timecode, TIMECODE, self.cluster_time);
+
+        self.cues.push((self.cluster_time, self.clusters.len() as u64));
+        write_id(&mut self.clusters, CLUSTER);
+        write_size(&mut self.clusters, (timecode.len() + self.cluster.len()) as u64);
+        self.clusters.extend_from_slice(&timecode);
+        self.clusters.extend_from_slice(&self.cluster);
+        self.cluster.clear();
+    }
+
+    fn write_seek_head(out: &mut Vec<u8>, entries: &[(u32, u64)]) {
+        let mut body = Vec::new();
+        for (id, position) in entries {
+            let mut seek = Vec::new();
+            let mut seek_id = Vec::with_capacity(4);
+            write_id(&mut seek_id, *id);
+            write_bytes(&mut seek, SEEK_ID, &seek_id);
+            //Fixed size to be able to calculate seek head's size in advance
+            write_uint_fixed(&mut seek, SEEK_POSITION, *position);
+            write_master(&mut body, SEEK, &seek);
+        }
+        write_master(out, SEEK_HEAD, &body);
+    }
+
+    ///Finalizes stream, returning complete WebM file
+    pub fn finish(mut self) -> Vec<u8> {
+        self.flush_cluster();
+
+        let mut info_body = Vec::new();
+        write_uint(&mut info_body, TIMECODE_SCALE, DEFAULT_TIMECODE_SCALE);
+        write_float(&mut info_body, DURATION, samples_to_nanos(self.samples) as f64 / DEFAULT_TIMECODE_SCALE as f64);
+        write_bytes(&mut info_body, MUXING_APP, APP_NAME.as_bytes());
+        write_bytes(&mut info_body, WRITING_APP, APP_NAME.as_bytes());
+        let mut info = Vec::new();
+        write_master(&mut info, INFO, &info_body);
+
+        let mut audio = Vec::new();
+        write_float(&mut audio, SAMPLING_FREQUENCY, SampleRate::Hz48000 as i32 as f64);
+        write_uint(&mut audio, CHANNELS, self.channels as _);
+
+        let mut track_entry = Vec::new();
+        write_uint(&mut track_entry, TRACK_NUMBER, TRACK);
+        write_uint(&mut track_entry, TRACK_UID, TRACK);
+        //audio
+        write_uint(&mut track_entry, TRACK_TYPE, 2);
+        write_uint(&mut track_entry, FLAG_LACING, 0);
+        write_bytes(&mut track_entry, CODEC_ID, CODEC_ID_OPUS.as_bytes());
+        write_bytes(&mut track_entry, CODEC_PRIVATE, &self.codec_private);
+        write_uint(&mut track_entry, CODEC_DELAY, samples_to_nanos(self.pre_skip as _));
+        write_uint(&mut track_entry, SEEK_PRE_ROLL, OPUS_SEEK_PRE_ROLL);
+        write_master(&mut track_entry, AUDIO, &audio);
+        let mut tracks_body = Vec::new();
+        write_master(&mut tracks_body, TRACK_ENTRY, &track_entry);
+        let mut tracks = Vec::new();
+        write_master(&mut tracks, TRACKS, &tracks_body);
+
+        let mut seek_entries = [(INFO, 0), (TRACKS, 0), (CUES, 0)];
+        let seek_entries = match self.cues.is_empty() {
+            true => &mut seek_entries[..2],
+            false => &mut seek_entries[..],
+        };
+        let mut seek_head = Vec::new();
+        Self::write_seek_head(&mut seek_head, seek_entries);
+
+        let info_pos = seek_head.len() as u64;
+        let tracks_pos = info_pos + info.len() as u64;
+        let clusters_pos = tracks_pos + tracks.len() as u64;
+        let cues_pos = clusters_pos + self.clusters.len() as u64;
+
+        seek_entries[0].1 = info_pos;
+        seek_entries[1].1 = tracks_pos;
+        if let Some(cues) = seek_entries.get_mut(2) {
+            cues.1 = cues_pos;
+        }
+        seek_head.clear();
+        Self::write_seek_head(&mut seek_head, seek_entries);
+
+        let mut cues = Vec::new();
+        if !self.cues.is_empty() {
+            let mut cues_body = Vec::new();
+            for (time, position) in self.cues.iter() {
+                let mut positions = Vec::new();
+                write_uint(&mut positions, CUE_TRACK, TRACK);
+                write_uint(&mut positions, CUE_CLUSTER_POSITION, clusters_pos + position);
+
+                let mut point = Vec::new();
+                write_uint(&mut point, CUE_TIME, *time);
+                write_master(&mut point, CUE_TRACK_POSITIONS, &positions);
+                write_master(&mut cues_body, CUE_POINT, &point);
+            }
+            write_master(&mut cues, CUES, &cues_body);
+        }
+
+        let mut ebml_body = Vec::new();
+        write_uint(&mut ebml_body, EBML_VERSION, 1);
+        write_uint(&mut ebml_body, EBML_READ_VERSION, 1);
+        write_uint(&mut ebml_body, EBML_MAX_ID_LENGTH, 4);
+        write_uint(&mut ebml_body, EBML_MAX_SIZE_LENGTH, 8);
+        write_bytes(&mut ebml_body, DOC_TYPE, b"webm");
+        write_uint(&mut ebml_body, DOC_TYPE_VERSION, 4);
+        write_uint(&mut ebml_body, DOC_TYPE_READ_VERSION, 2);
+
+        let segment_size = cues_pos + cues.len() as u64;
+        let mut out = Vec::with_capacity(segment_size as usize + ebml_body.len() + 24);
+        write_master(&mut out, EBML, &ebml_body);
+        write_id(&mut out, SEGMENT);
+        write_size(&mut out, segment_size);
+        out.extend_from_slice(&seek_head);
+        out.extend_from_slice(&info);
+        out.extend_from_slice(&tracks);
+        out.extend_from_slice(&self.clusters);
+        out.extend_from_slice(&cues);
+        out
+    }
+}
+
+fn read_id(data: &[u8], pos: usize) -> Result<(u32, usize), ErrorCode> {
+    let first = match data.get(pos) {
+        Some(first) => *first,
+        None => return Err(ErrorCode::invalid_packet()),
+    };
+    let len = first.leading_zeros() as usize + 1;
+    if len > 4 || pos + len > data.len() {
+        return Err(ErrorCode::invalid_packet());
+    }
+
+    let mut id = 0u32;
+    for byte in &data[pos..pos + len] {
+        id = (id << 8) | *byte as u32;
+    }
+    Ok((id, len))
+}
+
+fn read_size(data: &[u8], pos: usize) -> Result<(u64, usize), ErrorCode> {
+    let first = match data.get(pos) {
+        Some(first) => *first,
+        None => return Err(ErrorCode::invalid_packet()),
+    };
+    let len = first.leading_zeros() as usize + 1;
+    if len > 8 || pos + len > data.len() {
+        return Err(ErrorCode::invalid_packet());
+    }
+
+    let mut value = (first as u64) & ((1 << (8 - len)) - 1);
+    for byte in &data[pos + 1..pos + len] {
+        value = (value << 8) | *byte as u64;
+    }
+
+    //All ones indicate unknown size
+    if value == (1u64 << (7 * len)) - 1 {
+        value = UNKNOWN_SIZE;
+    }
+    Ok((value, len))
+}
+
+#[derive(Clone, Copy)]
+struct Element {
+    id: u32,
+    //Start of element's body
+    body: usize,
+    //Size of the body, UNKNOWN_SIZE if unknown
+    size: u64,
+}
+
+impl Element {
+    fn read(data: &[u8], pos: usize) -> Result<Self, ErrorCode> {
+        let (id, id_len) = read_id(data, pos)?;
+        let (size, size_len) = read_size(data, pos + id_len)?;
+        Ok(Self {
+            id,
+            body: pos + id_len + size_len,
+            size,
+        })
+    }
+
+    #[inline(always)]
+    fn is_unknown_size(&self) -> bool {
+        self.size == UNKNOWN_SIZE
+    }
+
+    #[inline]
+    fn end(&self, limit: usize) -> usize {
+        match self.is_unknown_size() {
+            true => limit,
+            false => match (self.body as u64).checked_add(self.size) {
+                Some(end) if end <= limit as u64 => end as usize,
+                _ => limit,
+            }
+        }
+    }
+
+    #[inline]
+    fn body<'a>(&self, data: &'a [u8]) -> Result<&'a [u8], ErrorCode> {
+        match self.is_unknown_size() {
+            true => Err(ErrorCode::invalid_packet()),
+            false => match (self.body as u64).checked_add(self.size) {
+                Some(end) if end <= data.len() as u64 => Ok(&data[self.body..end as usize]),
+                _ => Err(ErrorCode::invalid_packet()),
+            }
+        }
+    }
+}
+
+fn parse_uint(body: &[u8]) -> Result<u64, ErrorCode> {
+    if body.len() > 8 {
+        return Err(ErrorCode::invalid_packet());
+    }
+
+    Ok(body.iter().fold(0u64, |value, byte| (value << 8) | *byte as u64))
+}
+
+fn parse_float(body: &[u8]) -> Result<f64, ErrorCode> {
+    match body.len() {
+        0 => Ok(0.0),
+        4 => Ok(f32::from_be_bytes([body[0], body[1], body[2], body[3]]) as f64),
+        8 => {
+            let mut bytes = [0u8; 8];
+            bytes.copy_from_slice(body);
+            Ok(f64::from_be_bytes(bytes))
+        },
+        _ => Err(ErrorCode::invalid_packet()),
+    }
+}
+
+//Iterates over children of master element's body with known size
+struct Children<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Iterator for Children<'a> {
+    type Item = Result<(u32, &'a [u8]), ErrorCode>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pos >= self.data.len() {
+            return None;
+        }
+
+        let element = match Element::read(self.data, self.pos).and_then(|element| element.body(self.data).map(|body| (element, body))) {
+            Ok(element) => element,
+            Err(error) => {
+                self.pos = self.data.len();
+                return Some(Err(error));
+            }
+        };
+
+        self.pos = element.0.body + element.1.len();
+        Some(Ok((element.0.id, element.1)))
+    }
+}
+
+#[inline(always)]
+fn children(data: &[u8]) -> Children<'_> {
+    Children {
+        data,
+        pos: 0,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+///Demuxed packet
+pub struct Packet<'a> {
+    ///Timestamp of the packet in nanoseconds.
+    ///
+    ///Timestamps include codec delay, i.e. the first `codec_delay` nanoseconds of decoded output must be discarded.
+    pub timestamp: u64,
+    ///Opus packet
+    pub data: &'a [u8],
+}
+
+#[derive(Clone, Copy)]
+struct CuePoint {
+    time: u64,
+    //Relative to segment's body
+    position: u64,
+}
+
+struct Track {
+    number: u64,
+    head: OpusHead,
+    codec_delay: u64,
+    seek_pre_roll: u64,
+}
+
+impl Track {
+    fn parse(body: &[u8]) -> Result<Option<Self>, ErrorCode> {
+        let mut number = 0;
+        let mut is_opus = false;
+        let mut head = None;
+        let mut codec_delay = 0;
+        let mut seek_pre_roll = 0;
+
+        for child in children(body) {
+            let (id, body) = child?;
+            match id {
+                TRACK_NUMBER => number = parse_uint(body)?,
+                CODEC_ID => is_opus = body == CODEC_ID_OPUS.as_bytes(),
+                CODEC_PRIVATE => head = Some(body),
+                CODEC_DELAY => codec_delay = parse_uint(body)?,
+                SEEK_PRE_ROLL => seek_pre_roll = parse_uint(body)?,
+                _ => (),
+            }
+        }
+
+        match (is_opus, head) {
+            (true, Some(head)) => Ok(Some(Self {
+                number,
+                head: OpusHead::parse(head)?,
+                codec_delay,
+                seek_pre_roll,
+            })),
+            (true, None) => Err(ErrorCode::invalid_packet()),
+            (false, _) => Ok(None),
+        }
+    }
+}
+
+///WebM demuxer yielding packets of the first Opus track
+pub struct Demuxer<'a> {
+    data: &'a [u8],
+    pos: usize,
+    segment_body: usize,
+    segment_end: usize,
+    first_cluster: usize,
+    timecode_scale: u64,
+    duration: Option<f64>,
+    track: Track,
+    cluster_time: u64,
+    cues: Vec<CuePoint>,
+}
+
+impl<'a> Demuxer<'a> {
+    ///Parses WebM headers up to the first cluster
+    ///
+    ///Returns `ErrorCode::InvalidPacket` if data is malformed or contains no Opus track
+    pub fn new(data: &'a [u8]) -> Result<Self, ErrorCode> {
+        let ebml = Element::read(data, 0)?;
+        if ebml.id != EBML {
+            return Err(ErrorCode::invalid_packet());
+        }
+        for child in children(ebml.body(data)?) {
+            let (id, body) = child?;
+            if id == DOC_TYPE && body != b"webm" && body != b"matroska" {
+                return Err(ErrorCode::invalid_packet());
+            }
+        }
+
+        let segment = Element::read(data, ebml.end(data.len()))?;
+        if segment.id != SEGMENT {
+            return Err(ErrorCode::invalid_packet());
+        }
+        let segment_end = segment.end(data.len());
+
+        let mut timecode_scale = DEFAULT_TIMECODE_SCALE;
+        let mut duration = None;
+        let mut track = None;
+        let mut cues = Vec::new();
+        let mut cues_position = None;
+
+        let mut pos = segment.body;
+        while pos < segment_end {
+            let element = Element::read(data, pos)?;
+            match element.id {
+                CLUSTER => break,
+                SEEK_HEAD => for seek in children(element.body(data)?) {
+                    let (id, body) = seek?;
+                    if id != SEEK {
+                        continue;
+                    }
+
+                    let mut seek_id = 0;
+                    let mut seek_position = None;
+                    for child in children(body) {
+                        let (id, body) = child?;
+                        match id {
+                            SEEK_ID => seek_id = parse_uint(body)?,
+                            SEEK_POSITION => seek_position = Some(parse_uint(body)?),
+                            _ => (),
+                        }
+                    }
+                    if seek_id == CUES as u64 {
+                        cues_position = seek_position;
+                    }
+                },
+                INFO => for child in children(element.body(data)?) {
+                    let (id, body) = child?;
+                    match id {
+                        TIMECODE_SCALE => timecode_scale = parse_uint(body)?,
+                        DURATION => duration = Some(parse_float(body)?),
+                        _ => (),
+                    }
+                },
+                TRACKS => for child in children(element.body(data)?) {
+                    let (id, body) = child?;
+                    if id == TRACK_ENTRY && track.is_none() {
+                        track = Track::parse(body)?;
+                    }
+                },
+                CUES => Self::parse_cues(element.body(data)?, &mut cues)?,
+                _ => if element.is_unknown_size() {
+                    return Err(ErrorCode::invalid_packet());
+                },
+            }
+            pos = element.end(segment_end);
+        }
+
+        let track = match track {
+            Some(track) => track,
+            None => return Err(ErrorCode::invalid_packet()),
+        };
+
+        if cues.is_empty() {
+            if let Some(position) = cues_position {
+                let position = (segment.body as u64).saturating_add(position);
+                if position < data.len() as u64 {
+                    let element = Element::read(data, position as usize)?;
+                    if element.id == CUES {
+                        Self::parse_cues(element.body(data)?, &mut cues)?;
+                    }
+                }
+            }
+        }
+
+        Ok(Self {
+            data,
+            pos,
+            segment_body: segment.body,
+            segment_end,
+            first_cluster: pos,
+            timecode_scale,
+            duration,
+            track,
+            cluster_time: 0,
+            cues,
+        })
+    }
+
+    fn parse_cues(body: &[u8], cues: &mut Vec<CuePoint>) -> Result<(), ErrorCode> {
+        for point in children(body) {
+            let (id, body) = point?;
+            if id != CUE_POINT {
+                continue;
+            }
+
+            let mut time = None;
+            let mut position = None;
+            for child in children(body) {
+                let (id, body) = child?;
+                match id {
+                    CUE_TIME => time = Some(parse_uint(body)?),
+                    CUE_TRACK_POSITIONS => for child in children(body) {
+                        let (id, body) = child?;
+                        if id == CUE_CLUSTER_POSITION {
+                            position = Some(parse_uint(body)?);
+                        }
+                    },
+                    _ => (),
+                }
+            }
+
+            if let (Some(time), Some(position)) = (time, position) {
+                cues.push(CuePoint {
+                    time,
+                    position,
+                });
+            }
+        }
+
+        cues.sort_unstable_by_key(|cue| cue.time);
+        Ok(())
+    }
+
+    #[inline(always)]
+    ///Returns identification header stored as codec private data
+    pub fn head(&self) -> &OpusHead {
+        &self.track.head
+    }
+
+    #[inline(always)]
+    ///Returns track number of Opus track
+    pub fn track_number(&self) -> u64 {
+        self.track.number
+    }
+
+    #[inline(always)]
+    ///Returns codec delay in nanoseconds
+    pub fn codec_delay(&self) -> u64 {
+        self.track.codec_delay
+    }
+
+    #[inline(always)]
+    ///Returns seek pre-roll in nanoseconds
+    pub fn seek_pre_roll(&self) -> u64 {
+        self.track.seek_pre_roll
+    }
+
+    #[inline]
+    ///Returns duration of segment in nanoseconds, if specified
+    pub fn duration(&self) -> Option<u64> {
+        self.duration.map(|duration| (duration * self.timecode_scale as f64) as u64)
+    }
+
+    ///Moves reading position to the cluster that allows to start decoding at `timestamp` (in nanoseconds)
+    ///
+    ///Seek pre-roll is taken into account, therefore returned position is at least `seek_pre_roll` before `timestamp`, if possible.
+    ///Decoder must be reset and packets before `timestamp` should be decoded with their output discarded.
+    ///
+    ///Returns timestamp of the cluster, from which reading is going to start.
+    ///Without `Cues`, reading restarts from the first cluster.
+    pub fn seek(&mut self, timestamp: u64) -> u64 {
+        let target = timestamp.saturating_sub(self.track.seek_pre_roll);
+
+        let mut time = 0;
+        self.pos = self.first_cluster;
+        for cue in self.cues.iter() {
+            let cue_time = cue.time.saturating_mul(self.timecode_scale);
+            if cue_time > target {
+                break;
+            }
+
+            match (self.segment_body as u64).checked_add(cue.position) {
+                Some(position) if position < self.segment_end as u64 => {
+                    time = cue_time;
+                    self.pos = position as usize;
+                }
+                _ => break,
+            }
+        }
+
+        time
+    }
+
+    fn read_block(&self, body: &'a [u8]) -> Result<Option<Packet<'a>>, ErrorCode> {
+        let (track, track_len) = read_size(body, 0)?;
+        if track != self.track.number {
+            return Ok(None);
+        }
+
+        let header = match body.get(track_len..track_len + 3) {
+            Some(header) => header,
+            None => return Err(ErrorCode::invalid_packet()),
+        };
+        let relative_time = i16::from_be_bytes([header[0], header[1]]);
+        //Lacing is not supported
+        if header[2] & 0x06 != 0 {
+            return Err(ErrorCode::invalid_packet());
+        }
+
+        let time = (self.cluster_time as i64).saturating_add(relative_time as i64).max(0) as u64;
+        Ok(Some(Packet {
+            timestamp: time.saturating_mul(self.timecode_scale),
+            data: &body[track_len + 3..],
+        }))
+    }
+
+    fn read_next(&mut self) -> Result<Option<Packet<'a>>, ErrorCode> {
+        while self.pos < self.segment_end {
+            let element = Element::read(self.data, self.pos)?;
+            match element.id {
+                //Descend into cluster, which allows to handle unknown size
+                CLUSTER => {
+                    self.pos = element.body;
+                    continue;
+                },
+                TIMECODE => self.cluster_time = parse_uint(element.body(self.data)?)?,
+                SIMPLE_BLOCK => if let Some(packet) = self.read_block(element.body(self.data)?)? {
+                    self.pos = element.end(self.segment_end);
+                    return Ok(Some(packet));
+                },
+                BLOCK_GROUP => for child in children(element.body(self.data)?) {
+                    let (id, body) = child?;
+                    if id == BLOCK {
+                        if let Some(packet) = self.read_block(body)? {
+                            self.pos = element.end(self.segment_end);
+                            return Ok(Some(packet));
+                        }
+                    }
+                },
+                _ => if element.is_unknown_size() {
+                    return Err(ErrorCode::invalid_packet());
+                },
+            }
+            self.pos = element.end(self.segment_end);
+        }
+
+        Ok(None)
+    }
+
+    ///Reads next packet, returning `None` at the end of segment
+    pub fn next_packet(&mut self) -> Option<Result<Packet<'a>, ErrorCode>> {
+        match self.read_next() {
+            Ok(Some(packet)) => Some(Ok(packet)),
+            Ok(None) => None,
+            Err(error) => {
+                self.pos = self.segment_end;
+                Some(Err(error))
+            }
+        }
+    }
+}
+
+impl<'a> Iterator for Demuxer<'a> {
+    type Item = Result<Packet<'a>, ErrorCode>;
+
+    #[inline(always)]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_packet()
+    }
+}
@@ -0,0 +1,134 @@
+#![cfg(feature = "tokio-util")]
+
+use bytes::{Bytes, BytesMut};
+use tokio_util::codec::{Decoder as _, Encoder as _};
+
+use opusic_c::codec::{CodecError, Frame, OpusCodec};
+use opusic_c::{Encoder, Decoder, ErrorCode, SampleRate, Channels, Application};
+
+const FRAME: usize = 960;
+
+fn encode_packets(count: usize) -> Vec<Bytes> {
+    let mut encoder = Encoder::new(Channels::Mono, SampleRate::Hz48000, Application::Audio).expect("Create");
+    let input: Vec<u16> = (0..FRAME * count).map(|idx| ((idx as f32 / 24.0).sin() * 9000.0) as i16 as u16).collect();
+    input.chunks_exact(FRAME).map(|frame| {
+        let mut packet = Vec::with_capacity(1275);
+        encoder.encode_to_vec(frame, &mut packet).expect("encode");
+        Bytes::from(packet)
+    }).collect()
+}
+
+fn frame(timestamp: u64, packet: Bytes) -> Frame {
+    Frame {
+        timestamp,
+        packet,
+    }
+}
+
+#[test]
+fn should_frame_and_deframe_packets() {
+    let packets = encode_packets(5);
+    let mut codec = OpusCodec::new(false);
+    let mut buffer = BytesMut::new();
+    for packet in packets.iter() {
+        codec.encode(frame(0, packet.clone()), &mut buffer).expect("encode");
+    }
+    codec.encode(frame(0, Bytes::new()), &mut buffer).expect("encode");
+    codec.encode(frame(0, packets[0].clone()), &mut buffer).expect("encode");
+
+    //Feed data byte by byte to make sure partial frames are handled
+    let data = buffer.freeze();
+    let mut input = BytesMut::new();
+    let mut frames = Vec::new();
+    for byte in data.iter() {
+        input.extend_from_slice(&[*byte]);
+        while let Some(frame) = codec.decode(&mut input).expect("decode") {
+            frames.push(frame);
+        }
+    }
+    assert!(input.is_empty());
+    assert_eq!(frames.len(), 7);
+
+    let mut decoder = Decoder::new(Channels::Mono, SampleRate::Hz48000).expect("Create");
+    let mut output = [0u16; FRAME];
+    for (idx, frame) in frames.iter().take(5).enumerate() {
+        assert_eq!(frame.packet, packets[idx]);
+        assert_eq!(frame.timestamp, (idx * FRAME) as u64);
+        assert_eq!(decoder.decode_to_slice(&frame.packet, &mut output, false).expect("decode"), FRAME);
+    }
+    //Lost packet is assumed to be as long as the previous one
+    assert!(frames[5].is_lost());
+    assert_eq!(frames[5].timestamp, (5 * FRAME) as u64);
+    assert_eq!(frames[6].timestamp, (6 * FRAME) as u64);
+    assert_eq!(codec.next_timestamp(), (7 * FRAME) as u64);
+}
+
+#[test]
+fn should_advance_timestamp_by_previous_duration_on_loss() {
+    let mut codec = OpusCodec::default();
+    let mut buffer = BytesMut::new();
+    //Lost packet without previous packet is assumed to be 20ms
+    codec.encode(frame(0, Bytes::new()), &mut buffer).expect("encode");
+    //10ms CELT packet
+    codec.encode(frame(0, Bytes::from_static(&[0xF0, 1, 2])), &mut buffer).expect("encode");
+    codec.encode(frame(0, Bytes::new()), &mut buffer).expect("encode");
+    codec.encode(frame(0, Bytes::new()), &mut buffer).expect("encode");
+
+    let mut timestamps = Vec::new();
+    while let Some(frame) = codec.decode(&mut buffer).expect("decode") {
+        timestamps.push(frame.timestamp);
+    }
+    assert_eq!(timestamps, [0, 960, 1440, 1920]);
+    assert_eq!(codec.next_timestamp(), 2400);
+}
+
+#[test]
+fn should_carry_timestamps() {
+    let packets = encode_packets(2);
+    let mut codec = OpusCodec::new(true);
+    let mut buffer = BytesMut::new();
+    codec.encode(frame(u64::MAX, packets[0].clone()), &mut buffer).expect("encode");
+    codec.encode(&frame(123456, packets[1].clone()), &mut buffer).expect("encode");
+    assert_eq!(buffer.len(), 2 + 10 + packets[0].len() + 1 + 3 + packets[1].len());
+
+    let first = codec.decode(&mut buffer).expect("decode").expect("frame");
+    assert_eq!(first.timestamp, u64::MAX);
+    assert_eq!(first.packet, packets[0]);
+    let second = codec.decode(&mut buffer).expect("decode").expect("frame");
+    assert_eq!(second.timestamp, 123456);
+    assert_eq!(second.packet, packets[1]);
+    assert!(codec.decode(&mut buffer).expect("decode").is_none());
+}
+
+#[test]
+fn should_reject_malformed_frames() {
+    let mut codec = OpusCodec::new(false);
+
+    //Code 1 packet with odd payload length
+    let mut buffer = BytesMut::from(&[4, 0x01, 1, 2, 3][..]);
+    match codec.decode(&mut buffer) {
+        Err(CodecError::Opus(ErrorCode::InvalidPacket)) => (),
+        result => panic!("Unexpected result: {result:?}"),
+    }
+
+    //Code 3 packet exceeding 120ms
+    let mut buffer = BytesMut::from(&[3, 0x03, 49, 0][..]);
+    match codec.decode(&mut buffer) {
+        Err(CodecError::Opus(ErrorCode::InvalidPacket)) => (),
+        result => panic!("Unexpected result: {result:?}"),
+    }
+
+    //Length exceeding maximum packet size
+    let mut buffer = BytesMut::from(&[0xff, 0xff, 0xff, 0x7f][..]);
+    match codec.decode(&mut buffer) {
+        Err(CodecError::Opus(ErrorCode::InvalidPacket)) => (),
+        result => panic!("Unexpected result: {result:?}"),
+    }
+
+    //Varint overflow
+    let mut buffer = BytesMut::from(&[0xff; 11][..]);
+    match codec.decode(&mut buffer) {
+        Err(CodecError::Opus(ErrorCode::InvalidPacket)) => (),
+        result => panic!("Unexpected result: {result:?}"),
+    }
+}
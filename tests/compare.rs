@@ -0,0 +1,79 @@
+use opusic_c::compare::{compare, compare_float};
+use opusic_c::{Encoder, Decoder, ErrorCode, SampleRate, Channels, Application, Bitrate};
+
+const FRAME: usize = 960;
+
+//Tones over noise floor, as metric is meant for natural audio rather than pure tones
+fn signal(frames: usize) -> Vec<u16> {
+    let mut result = Vec::with_capacity(frames * FRAME * 2);
+    let mut seed = 1u32;
+    let mut noise = move || {
+        seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+        (seed >> 16) as f32 / 65536.0 - 0.5
+    };
+    for idx in 0..frames * FRAME {
+        let time = idx as f32 / 48000.0;
+        let left = (time * 440.0 * 2.0 * core::f32::consts::PI).sin() * 8000.0 + (time * 3000.0 * 2.0 * core::f32::consts::PI).sin() * 2000.0 + noise() * 4000.0;
+        let right = (time * 660.0 * 2.0 * core::f32::consts::PI).sin() * 8000.0 + noise() * 4000.0;
+        result.push(left as i16 as u16);
+        result.push(right as i16 as u16);
+    }
+    result
+}
+
+fn encode(input: &[u16]) -> Vec<Vec<u8>> {
+    let mut encoder = Encoder::new(Channels::Stereo, SampleRate::Hz48000, Application::Audio).expect("Create");
+    encoder.set_bitrate(Bitrate::Value(64000)).expect("set bitrate");
+    input.chunks(FRAME * 2).map(|frame| {
+        let mut packet = Vec::with_capacity(1275);
+        encoder.encode_to_vec(frame, &mut packet).expect("to encode");
+        packet
+    }).collect()
+}
+
+fn decode(packets: &[Vec<u8>], rate: SampleRate, channels: Channels) -> Vec<u16> {
+    let mut decoder = Decoder::new(channels, rate).expect("Create");
+    let frame_size = FRAME * rate as usize / 48000 * channels as usize;
+    let mut output = Vec::new();
+    let mut decoded = vec![0u16; frame_size];
+    for packet in packets {
+        let len = decoder.decode_to_slice(packet, &mut decoded, false).expect("to decode");
+        output.extend_from_slice(&decoded[..len * channels as usize]);
+    }
+    output
+}
+
+#[test]
+fn should_compare_identical_output() {
+    let input = signal(10);
+    let result = compare(&input, Channels::Stereo, &input, Channels::Stereo, SampleRate::Hz48000).expect("compare");
+    assert!(result.is_pass());
+    assert_eq!(result.quality, 100.0);
+    assert_eq!(result.error, 0.0);
+}
+
+#[test]
+fn should_pass_decoded_output() {
+    let packets = encode(&signal(25));
+    let reference = decode(&packets, SampleRate::Hz48000, Channels::Stereo);
+
+    for (rate, channels) in [(SampleRate::Hz24000, Channels::Stereo), (SampleRate::Hz16000, Channels::Stereo), (SampleRate::Hz8000, Channels::Stereo)] {
+        let decoded = decode(&packets, rate, channels);
+        let result = compare(&reference, Channels::Stereo, &decoded, channels, rate).expect("compare");
+        assert!(result.is_pass(), "{rate:?}/{channels:?}: {result:?}");
+    }
+}
+
+#[test]
+fn should_fail_unrelated_output() {
+    let input = signal(10);
+    let noise: Vec<f32> = (0..input.len()).map(|idx| ((idx * 7919 % 1000) as f32 / 1000.0 - 0.5) * 0.5).collect();
+    let input_float: Vec<f32> = input.iter().map(|sample| *sample as i16 as f32 / 32768.0).collect();
+    let result = compare_float(&input_float, Channels::Stereo, &noise, Channels::Stereo, SampleRate::Hz48000).expect("compare");
+    assert!(!result.is_pass(), "{result:?}");
+
+    assert_eq!(compare(&input, Channels::Stereo, &input[2..], Channels::Stereo, SampleRate::Hz48000).unwrap_err(), ErrorCode::BadArg);
+    assert_eq!(compare(&input, Channels::Mono, &input, Channels::Stereo, SampleRate::Hz48000).unwrap_err(), ErrorCode::BadArg);
+    assert_eq!(compare(&input[..100], Channels::Stereo, &input[..100], Channels::Stereo, SampleRate::Hz48000).unwrap_err(), ErrorCode::BadArg);
+}
+
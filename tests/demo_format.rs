@@ -0,0 +1,75 @@
+use opusic_c::demo_format::{Reader, Writer, HEADER_SIZE};
+use opusic_c::{Encoder, Decoder, ErrorCode, SampleRate, Channels, Application};
+
+#[test]
+fn should_roundtrip_frames() {
+    let mut writer = Writer::new();
+    writer.add_packet(&[0xfc, 0xff, 0xfe], 0xdeadbeef).expect("add packet");
+    writer.add_lost();
+    writer.add_packet(&[1; 300], 1).expect("add packet");
+    let data = writer.finish();
+    assert_eq!(data.len(), HEADER_SIZE * 3 + 3 + 300);
+    assert_eq!(&data[..HEADER_SIZE], &[0, 0, 0, 3, 0xde, 0xad, 0xbe, 0xef]);
+
+    let mut reader = Reader::new(&data);
+    let frame = reader.next_frame().expect("read").expect("frame");
+    assert_eq!(frame.data, &[0xfc, 0xff, 0xfe]);
+    assert_eq!(frame.final_range, 0xdeadbeef);
+    assert!(!frame.is_lost());
+    assert_eq!(reader.position(), HEADER_SIZE + 3);
+
+    let frame = reader.next_frame().expect("read").expect("frame");
+    assert!(frame.is_lost());
+    assert_eq!(frame.final_range, 0);
+
+    let frame = reader.next_frame().expect("read").expect("frame");
+    assert_eq!(frame.data, &[1; 300]);
+    assert_eq!(frame.final_range, 1);
+
+    assert!(reader.next_frame().expect("read").is_none());
+    assert_eq!(reader.position(), data.len());
+}
+
+#[test]
+fn should_reject_malformed_frames() {
+    let mut reader = Reader::new(&[0, 0, 0, 1, 0, 0]);
+    assert_eq!(reader.next_frame().unwrap_err(), ErrorCode::InvalidPacket);
+
+    let mut reader = Reader::new(&[0, 0, 0, 2, 0, 0, 0, 0, 1]);
+    assert_eq!(reader.next_frame().unwrap_err(), ErrorCode::InvalidPacket);
+
+    let mut reader = Reader::new(&[0xff, 0xff, 0xff, 0xff, 0, 0, 0, 0]);
+    assert_eq!(reader.next_frame().unwrap_err(), ErrorCode::InvalidPacket);
+}
+
+#[test]
+fn should_replay_encoder_output() {
+    const FRAME: usize = 960;
+
+    let mut encoder = Encoder::new(Channels::Mono, SampleRate::Hz48000, Application::Audio).expect("Create");
+    let input: Vec<u16> = (0..FRAME * 10).map(|idx| ((idx as f32 / 48.0).sin() * 10000.0) as i16 as u16).collect();
+
+    let mut writer = Writer::new();
+    let mut output = Vec::new();
+    for (idx, frame) in input.chunks_exact(FRAME).enumerate() {
+        let mut packet = Vec::with_capacity(1275);
+        encoder.encode_to_vec(frame, &mut packet).expect("to encode");
+        writer.add_packet(&packet, encoder.get_final_range().expect("get final range")).expect("add packet");
+        if idx == 4 {
+            output.extend(writer.take_output());
+        }
+    }
+    output.extend(writer.finish());
+
+    let mut decoder = Decoder::new(Channels::Mono, SampleRate::Hz48000).expect("Create");
+    let mut decoded = [0u16; FRAME];
+    let mut reader = Reader::new(&output);
+    let mut count = 0;
+    while let Some(frame) = reader.next_frame().expect("read") {
+        let len = decoder.decode_to_slice(frame.data, &mut decoded, false).expect("to decode");
+        assert_eq!(len, FRAME);
+        assert_eq!(decoder.get_final_range().expect("get final range"), frame.final_range);
+        count += 1;
+    }
+    assert_eq!(count, 10);
+}
@@ -0,0 +1,115 @@
+use opusic_c::{edit, header, probe, repacketizer, utils, Encoder, Decoder, Bitrate};
+use opusic_c::{SampleRate, Channels, Application};
+
+const FRAME_SIZE: usize = 960;
+
+//Encodes mono sine in 60ms packets of three 20ms frames
+fn encode_clip(frequency: f32, frames: usize) -> edit::Clip {
+    let mut encoder = Encoder::new(Channels::Mono, SampleRate::Hz48000, Application::Audio).expect("Create");
+    encoder.set_bitrate(Bitrate::Value(64000)).expect("set bitrate");
+    let head = header::OpusHead::from_encoder(&mut encoder).expect("create head");
+    let mut repacketizer = repacketizer::Repacketizer::new().expect("create repacketizer");
+
+    let mut clip = edit::Clip::new(head);
+    let mut input = [0f32; FRAME_SIZE];
+    let mut frame_packets = Vec::new();
+    for frame in 0..frames {
+        for (idx, sample) in input.iter_mut().enumerate() {
+            let time = (frame * FRAME_SIZE + idx) as f32 / 48000.0;
+            *sample = (time * frequency * 2.0 * core::f32::consts::PI).sin() * 0.5;
+        }
+        let mut packet = vec![0; 1275];
+        let len = encoder.encode_float_to_slice(&input, &mut packet).expect("to encode");
+        packet.truncate(len);
+        frame_packets.push(packet);
+
+        if frame_packets.len() == 3 {
+            let bufs = frame_packets.iter().map(Vec::as_slice).collect::<Vec<_>>();
+            let mut packet = Vec::with_capacity(3 * 1277);
+            let len = repacketizer.combine_all(&bufs, packet.spare_capacity_mut()).expect("combine");
+            unsafe {
+                packet.set_len(len);
+            }
+            clip.packets.push(packet);
+            frame_packets.clear();
+        }
+    }
+    clip
+}
+
+//Decodes playable part of the clip
+fn decode_clip(clip: &edit::Clip) -> Vec<f32> {
+    let mut decoder = Decoder::new(Channels::Mono, SampleRate::Hz48000).expect("Create");
+    let mut output = Vec::new();
+    let mut frame = [0f32; 5760];
+    for packet in clip.packets.iter() {
+        let len = decoder.decode_float_to_slice(packet, &mut frame, false).expect("decode");
+        output.extend_from_slice(&frame[..len]);
+    }
+    let start = clip.head.pre_skip as usize;
+    let end = output.len() - clip.end_trim as usize;
+    output[start..end].to_vec()
+}
+
+#[test]
+fn should_trim_clip_losslessly() {
+    let clip = encode_clip(440.0, 150);
+    assert_eq!(clip.samples().expect("samples"), 150 * 960);
+    let duration = clip.duration().expect("duration");
+    assert_eq!(duration, 150 * 960 - clip.head.pre_skip as u64);
+
+    let start = 48000 + 123;
+    let end = 2 * 48000 + 24000 + 7;
+    let trimmed = clip.trim(start, end).expect("trim");
+    assert_eq!(trimmed.duration().expect("duration"), end - start);
+    assert!(trimmed.head.pre_skip as u64 >= edit::PRE_ROLL);
+    assert!(trimmed.end_trim < FRAME_SIZE as u64);
+    //Frames are dropped from multi-frame packets
+    assert!(trimmed.packets.iter().any(|packet| utils::get_nb_frames(packet).expect("frames") < 3));
+    assert!(trimmed.packets.len() < clip.packets.len());
+
+    let original = decode_clip(&clip);
+    let decoded = decode_clip(&trimmed);
+    assert_eq!(decoded.len() as u64, end - start);
+    let expected = &original[start as usize..end as usize];
+    let error = decoded.iter().zip(expected).map(|(left, right)| (left - right).powi(2)).sum::<f32>() / decoded.len() as f32;
+    let error = error.sqrt();
+    assert!(error < 0.01, "rms error={error}");
+
+    //Trim from the very beginning keeps original pre-skip
+    let trimmed = clip.trim(0, 1000).expect("trim");
+    assert_eq!(trimmed.head.pre_skip, clip.head.pre_skip);
+    assert_eq!(trimmed.packets.len(), 1);
+    assert_eq!(trimmed.duration().expect("duration"), 1000);
+
+    assert!(clip.trim(1000, 1000).is_err());
+    assert!(clip.trim(0, duration + 1).is_err());
+    assert_eq!(clip.trim(0, duration).expect("trim").packets, clip.packets);
+}
+
+#[test]
+fn should_concatenate_clips() {
+    let greeting = encode_clip(440.0, 60).trim(100, 48000 + 100).expect("trim");
+    let message = encode_clip(660.0, 90).trim(24000, 72000 + 555).expect("trim");
+    let greeting_duration = greeting.duration().expect("duration");
+    let message_duration = message.duration().expect("duration");
+
+    let mut joined = edit::Clip::new(greeting.head.clone());
+    joined.append(&greeting).expect("append to empty");
+    assert_eq!(joined, greeting);
+    joined.append(&message).expect("append");
+    assert_eq!(joined.head.pre_skip, greeting.head.pre_skip);
+    assert_eq!(joined.end_trim, message.end_trim);
+
+    let duration = joined.duration().expect("duration");
+    assert!(duration >= greeting_duration + message_duration);
+    assert!(duration < greeting_duration + message_duration + 2 * FRAME_SIZE as u64);
+
+    let file = joined.to_ogg(&header::OpusTags::new(), 3).expect("write ogg");
+    let result = probe::probe_ogg(&file).expect("probe");
+    assert_eq!(result.duration, duration);
+    assert_eq!(edit::Clip::from_ogg(&file).expect("read ogg"), joined);
+
+    let mut stereo = edit::Clip::new(header::OpusHead::new(Channels::Stereo, 312));
+    assert!(stereo.append(&greeting).is_err());
+}
@@ -0,0 +1,281 @@
+#![cfg(feature = "std")]
+
+use std::io::Cursor;
+
+use opusic_c::file::{OpusFileReader, OpusFileWriter};
+use opusic_c::{header, multistream, ogg, Encoder, SampleRate, Channels, Application, Bitrate};
+
+fn signal(len: usize, channels: usize) -> Vec<u16> {
+    (0..len * channels).map(|idx| (((idx / channels) as f32 / 20.0).sin() * 8000.0) as i16 as u16).collect()
+}
+
+#[test]
+fn should_roundtrip_file() {
+    let encoder = Encoder::new(Channels::Stereo, SampleRate::Hz48000, Application::Audio).expect("Create");
+    let mut tags = header::OpusTags::new();
+    tags.add("TITLE", "sine");
+    let mut writer = OpusFileWriter::new(Cursor::new(Vec::new()), encoder, &tags, 0x1234).expect("create writer");
+
+    let input = signal(48000 + 333, 2);
+    for chunk in input.chunks(1001 * 2) {
+        writer.write(chunk).expect("write");
+    }
+    //Partial sample would misalign channels
+    assert_eq!(writer.write(&input[..3]).expect_err("odd write").kind(), std::io::ErrorKind::InvalidInput);
+    assert_eq!(writer.write_float(&[0.0; 5]).expect_err("odd write").kind(), std::io::ErrorKind::InvalidInput);
+    let file = writer.finish().expect("finish").into_inner();
+
+    let mut reader = OpusFileReader::new(Cursor::new(file)).expect("create reader");
+    assert_eq!(reader.channels(), 2);
+    assert_eq!(reader.tags().get("TITLE"), Some("sine"));
+    assert_eq!(reader.duration().expect("duration"), 48333);
+
+    //Odd sized output buffer is truncated to whole samples
+    let mut output = vec![0u16; 777];
+    let mut decoded = Vec::new();
+    loop {
+        let len = reader.read(&mut output).expect("read");
+        if len == 0 {
+            break;
+        }
+        decoded.extend_from_slice(&output[..len * 2]);
+    }
+    assert_eq!(decoded.len(), input.len());
+    assert_eq!(reader.read(&mut output).expect("read"), 0);
+}
+
+#[test]
+fn should_resample_to_48khz() {
+    let encoder = Encoder::new(Channels::Mono, SampleRate::Hz16000, Application::Voip).expect("Create");
+    let mut writer = OpusFileWriter::new(Vec::new(), encoder, &header::OpusTags::new(), 1).expect("create writer");
+    writer.write_float(&vec![0.25; 16000 + 50]).expect("write");
+    let file = writer.finish().expect("finish");
+
+    let mut reader = OpusFileReader::new(Cursor::new(file)).expect("create reader");
+    assert_eq!(reader.head().input_sample_rate, 16000);
+    assert_eq!(reader.duration().expect("duration"), 48150);
+
+    let mut decoded = Vec::new();
+    assert_eq!(reader.read_to_vec(&mut decoded).expect("read"), 48150);
+    assert_eq!(decoded.len(), 48150);
+}
+
+#[test]
+fn should_read_multistream_file() {
+    let config = multistream::Config::<6>::vorbis().expect("vorbis config");
+    let head = header::OpusHead::multistream(1, &config, 312);
+    let mut encoder = multistream::Encoder::new(config, SampleRate::Hz48000, Application::Audio).expect("Create");
+
+    let mut writer = ogg::Writer::new(&head, &header::OpusTags::new(), 7);
+    let mut packet = Vec::with_capacity(1275 * 4);
+    for _ in 0..3 {
+        packet.clear();
+        encoder.encode_to_vec(&[0; 6 * 960], &mut packet).expect("to encode");
+        writer.add_packet(&packet).expect("add packet");
+    }
+    writer.set_end_trim(100);
+    let file = writer.finish();
+
+    let mut reader = OpusFileReader::new(Cursor::new(file)).expect("create reader");
+    assert_eq!(reader.channels(), 6);
+    let mut decoded = Vec::new();
+    assert_eq!(reader.read_to_vec(&mut decoded).expect("read"), 960 * 3 - 312 - 100);
+    assert_eq!(decoded.len(), (960 * 3 - 312 - 100) * 6);
+}
+
+#[test]
+fn should_reject_invalid_files() {
+    assert!(OpusFileReader::new(Cursor::new(Vec::new())).is_err());
+    assert!(OpusFileReader::new(Cursor::new(b"OggS".to_vec())).is_err());
+    assert!(OpusFileReader::new(Cursor::new(vec![0xAA; 4096])).is_err());
+}
+
+#[test]
+fn should_seek_sample_accurately() {
+    let mut encoder = Encoder::new(Channels::Mono, SampleRate::Hz48000, Application::Audio).expect("Create");
+    //Make file large enough to require bisection
+    encoder.set_bitrate(Bitrate::Value(192000)).expect("set bitrate");
+    let mut writer = OpusFileWriter::new(Vec::new(), encoder, &header::OpusTags::new(), 3).expect("create writer");
+    //Repeating chirp, so that misaligned output is easy to spot
+    let input: Vec<f32> = (0..48000 * 20).map(|idx| {
+        let time = (idx % (48000 * 5)) as f32 / 48000.0;
+        (time * (200.0 + 300.0 * time) * core::f32::consts::TAU).sin() * 0.5
+    }).collect();
+    writer.write_float(&input).expect("write");
+    let file = writer.finish().expect("finish");
+
+    let mut reader = OpusFileReader::new(Cursor::new(file)).expect("create reader");
+    let mut reference = Vec::new();
+    let mut output = [0.0f32; 4096];
+    loop {
+        let len = reader.read_float(&mut output).expect("read");
+        if len == 0 {
+            break;
+        }
+        reference.extend_from_slice(&output[..len]);
+    }
+    assert_eq!(reference.len(), input.len());
+
+    for target in [0, 1, 3839, 3840, 48000 + 17, 96000 * 2 - 1, 239000, 480000 + 5, 959500] {
+        reader.seek(target).expect("seek");
+        assert_eq!(reader.position(), target);
+        let len = reader.read_float(&mut output[..960]).expect("read");
+        assert_eq!(len, core::cmp::min(960, input.len() - target as usize));
+        assert_eq!(reader.position(), target + len as u64);
+
+        let expected = &reference[target as usize..target as usize + len];
+        let error = output[..len].iter().zip(expected).map(|(a, b)| (a - b) * (a - b)).sum::<f32>() / len as f32;
+        assert!(error < 1e-5, "target={target} error={error}");
+        if target == 0 {
+            assert_eq!(&output[..len], expected);
+        }
+    }
+
+    reader.seek(input.len() as u64 + 100).expect("seek");
+    assert_eq!(reader.read_float(&mut output).expect("read"), 0);
+}
+
+fn encode_file(channels: Channels, rate: SampleRate, input: &[u16], title: &str, serial: u32) -> Vec<u8> {
+    let encoder = Encoder::new(channels, rate, Application::Audio).expect("Create");
+    let mut tags = header::OpusTags::new();
+    tags.add("TITLE", title);
+    let mut writer = OpusFileWriter::new(Vec::new(), encoder, &tags, serial).expect("create writer");
+    writer.write(input).expect("write");
+    writer.finish().expect("finish")
+}
+
+fn split_pages(mut data: &[u8]) -> Vec<&[u8]> {
+    let mut pages = Vec::new();
+    while !data.is_empty() {
+        let (_, size) = ogg::Page::parse(data).expect("parse page");
+        pages.push(&data[..size]);
+        data = &data[size..];
+    }
+    pages
+}
+
+fn raw_page(header_type: u8, serial: u32, sequence: u32, body: &[u8]) -> Vec<u8> {
+    let mut page = Vec::new();
+    page.extend_from_slice(&ogg::CAPTURE_PATTERN);
+    page.push(0);
+    page.push(header_type);
+    page.extend_from_slice(&0u64.to_le_bytes());
+    page.extend_from_slice(&serial.to_le_bytes());
+    page.extend_from_slice(&sequence.to_le_bytes());
+    page.extend_from_slice(&[0; 4]);
+    page.push(1);
+    page.push(body.len() as u8);
+    page.extend_from_slice(body);
+    let crc = ogg::page_checksum(&page);
+    page[22..26].copy_from_slice(&crc.to_le_bytes());
+    page
+}
+
+#[test]
+fn should_read_chained_and_multiplexed_file() {
+    let first = encode_file(Channels::Stereo, SampleRate::Hz48000, &signal(9600 + 7, 2), "first", 10);
+    let second = encode_file(Channels::Mono, SampleRate::Hz16000, &signal(3200 + 3, 1), "second", 20);
+
+    //Second link is multiplexed with Skeleton-like stream, whose pages are interleaved with Opus pages
+    let second_pages = split_pages(&second);
+    let mut file = first.clone();
+    let second_offset = file.len() as u64;
+    file.extend_from_slice(&raw_page(ogg::FLAG_BOS, 30, 0, b"fishead\0"));
+    for (idx, page) in second_pages.iter().enumerate() {
+        file.extend_from_slice(page);
+        if idx == 1 {
+            file.extend_from_slice(&raw_page(0, 30, 1, b"fisbone\0"));
+        }
+    }
+    file.extend_from_slice(&raw_page(ogg::FLAG_EOS, 30, 2, b""));
+
+    let mut reader = OpusFileReader::new(Cursor::new(file)).expect("create reader");
+    let links = reader.links().expect("scan links");
+    assert_eq!(links.len(), 2);
+    assert_eq!(links[0].serial, 10);
+    assert_eq!(links[0].offset, 0);
+    assert_eq!(links[0].duration, 9607);
+    assert_eq!(links[0].tags.get("TITLE"), Some("first"));
+    assert_eq!(links[1].serial, 20);
+    assert_eq!(links[1].offset, second_offset);
+    assert_eq!(links[1].head.channels, 1);
+    assert_eq!(links[1].head.input_sample_rate, 16000);
+    assert_eq!(links[1].duration, 9609);
+    assert_eq!(links[1].tags.get("TITLE"), Some("second"));
+
+    let mut decoded = [Vec::new(), Vec::new()];
+    let mut output = vec![0u16; 1000];
+    loop {
+        let len = reader.read(&mut output).expect("read");
+        if len == 0 {
+            break;
+        }
+        let channels = reader.channels();
+        assert_eq!(channels, links[reader.link()].head.channels as usize);
+        decoded[reader.link()].extend_from_slice(&output[..len * channels]);
+    }
+    assert_eq!(reader.link(), 1);
+    assert_eq!(reader.tags().get("TITLE"), Some("second"));
+    assert_eq!(decoded[0].len(), 9607 * 2);
+    assert_eq!(decoded[1].len(), 9609);
+}
+
+#[test]
+fn should_read_chain_without_end_of_stream() {
+    let mut first = encode_file(Channels::Mono, SampleRate::Hz48000, &signal(4800, 1), "first", 1);
+    //Clear EOS flag of the last page
+    let last = first.len() - split_pages(&first).last().expect("last page").len();
+    first[last + 5] &= !ogg::FLAG_EOS;
+    first[last + 22..last + 26].copy_from_slice(&[0; 4]);
+    let crc = ogg::page_checksum(&first[last..]);
+    first[last + 22..last + 26].copy_from_slice(&crc.to_le_bytes());
+    first.extend_from_slice(&encode_file(Channels::Stereo, SampleRate::Hz48000, &signal(4800, 2), "second", 2));
+
+    let mut reader = OpusFileReader::new(Cursor::new(first)).expect("create reader");
+    let mut decoded = Vec::new();
+    //Without EOS page end trimming is unknown, so padding of the last frame remains
+    let len = reader.read_to_vec(&mut decoded).expect("read");
+    assert!((4800..4800 + 960).contains(&len), "len={len}");
+    assert_eq!(reader.read_to_vec(&mut decoded).expect("read"), 4800);
+    assert_eq!(reader.channels(), 2);
+    assert_eq!(decoded.len(), len + 4800 * 2);
+    assert_eq!(reader.read_to_vec(&mut decoded).expect("read"), 0);
+}
+
+#[test]
+fn should_conceal_damaged_pages() {
+    let input = signal(48000 * 3 + 100, 1);
+    let file = encode_file(Channels::Mono, SampleRate::Hz48000, &input, "damaged", 8);
+    let pages = split_pages(&file);
+    assert_eq!(pages.len(), 6);
+    let page_start = |idx: usize| pages[..idx].iter().map(|page| page.len()).sum::<usize>();
+
+    let read_all = |data: Vec<u8>| {
+        let mut reader = OpusFileReader::new(Cursor::new(data)).expect("create reader");
+        let mut decoded = Vec::new();
+        while reader.read_to_vec(&mut decoded).expect("read") > 0 {}
+        decoded
+    };
+    let reference = read_all(file.clone());
+    assert_eq!(reference.len(), input.len());
+
+    //Lost second of audio is concealed, keeping the rest aligned
+    let mut damaged = file.clone();
+    damaged[page_start(3) + 200] ^= 0x55;
+    let decoded = read_all(damaged);
+    assert_eq!(decoded.len(), input.len());
+    //Output is offset by pre-skip relatively to granule positions
+    assert_eq!(decoded[..47000], reference[..47000]);
+    assert_ne!(decoded[48000..95000], reference[48000..95000]);
+
+    //Garbage between pages is skipped
+    let mut garbage = file[..page_start(3)].to_vec();
+    garbage.extend_from_slice(b"OggSOggS\0\0garbage");
+    garbage.extend_from_slice(&file[page_start(3)..]);
+    assert_eq!(read_all(garbage), reference);
+
+    //Truncated file ends with the last complete page
+    let decoded = read_all(file[..file.len() - 10].to_vec());
+    assert_eq!(decoded[..], reference[..decoded.len()]);
+    assert!(decoded.len() >= 48000 * 3 - 312);
+}
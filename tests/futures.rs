@@ -0,0 +1,112 @@
+#![cfg(feature = "futures")]
+
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+use futures::channel::mpsc;
+use futures::executor::block_on;
+use futures::{stream, task, Sink, SinkExt, StreamExt};
+use bytes::Bytes;
+
+use opusic_c::futures::{DecodeStream, EncodeSink, PcmChunk, SinkError};
+use opusic_c::{Encoder, Decoder, ErrorCode, SampleRate, Channels, Application};
+
+const FRAME: usize = 960;
+
+fn signal(len: usize, channels: usize) -> Vec<u16> {
+    (0..len * channels).map(|idx| (((idx / channels) as f32 / 20.0).sin() * 8000.0) as i16 as u16).collect()
+}
+
+#[test]
+fn should_encode_and_decode_pipeline() {
+    let encoder = Encoder::new(Channels::Stereo, SampleRate::Hz48000, Application::Audio).expect("Create");
+    let (tx, rx) = mpsc::unbounded::<Bytes>();
+    let mut sink = EncodeSink::new(encoder, FRAME, tx).expect("create sink");
+
+    let input = signal(FRAME * 10 + 100, 2);
+    block_on(async {
+        //Odd sized chunks to exercise buffering
+        for chunk in input.chunks(777) {
+            sink.send(PcmChunk(chunk.to_vec())).await.expect("send");
+        }
+        assert_eq!(sink.buffered(), 100);
+        sink.close().await.expect("close");
+    });
+
+    let decoder = Decoder::new(Channels::Stereo, SampleRate::Hz48000).expect("Create");
+    let stream = DecodeStream::new(decoder, rx).expect("create stream");
+    let output: Vec<PcmChunk> = block_on(stream.map(|pcm| pcm.expect("decode")).collect());
+    assert_eq!(output.len(), 11);
+    for pcm in output.iter() {
+        assert_eq!(pcm.0.len(), FRAME * 2);
+    }
+}
+
+#[test]
+fn should_conceal_lost_packets() {
+    let mut encoder = Encoder::new(Channels::Mono, SampleRate::Hz48000, Application::Voip).expect("Create");
+    let mut packets = Vec::new();
+    for frame in signal(FRAME * 3, 1).chunks_exact(FRAME / 2) {
+        let mut packet = Vec::with_capacity(1275);
+        encoder.encode_to_vec(frame, &mut packet).expect("encode");
+        packets.push(Bytes::from(packet));
+    }
+    packets.insert(2, Bytes::new());
+
+    let decoder = Decoder::new(Channels::Mono, SampleRate::Hz16000).expect("Create");
+    let stream = DecodeStream::new(decoder, stream::iter(packets)).expect("create stream");
+    let output: Vec<_> = block_on(stream.collect());
+    assert_eq!(output.len(), 7);
+    for pcm in output {
+        assert_eq!(pcm.expect("decode").0.len(), 160);
+    }
+
+    let decoder = Decoder::new(Channels::Mono, SampleRate::Hz48000).expect("Create");
+    let mut stream = DecodeStream::new(decoder, stream::iter([&[0xffu8][..]])).expect("create stream");
+    let result = block_on(stream.next()).expect("item");
+    assert_eq!(result.unwrap_err(), ErrorCode::InvalidPacket);
+}
+
+#[test]
+fn should_propagate_backpressure() {
+    let encoder = Encoder::new(Channels::Mono, SampleRate::Hz48000, Application::Audio).expect("Create");
+    let (tx, mut rx) = mpsc::channel::<Bytes>(0);
+    let mut sink = EncodeSink::new(encoder, FRAME, tx).expect("create sink");
+
+    let waker = task::noop_waker();
+    let mut cx = Context::from_waker(&waker);
+    assert!(Pin::new(&mut sink).poll_ready(&mut cx).is_ready());
+    Pin::new(&mut sink).start_send(PcmChunk(signal(FRAME * 4, 1))).expect("send");
+
+    //Channel accepts single packet per sender slot, after which sink must wait for receiver
+    assert!(Pin::new(&mut sink).poll_ready(&mut cx).is_pending());
+    assert!(sink.buffered() > 0);
+
+    let mut received = 0;
+    while sink.buffered() > 0 {
+        if let Poll::Ready(Some(packet)) = rx.poll_next_unpin(&mut cx) {
+            assert!(!packet.is_empty());
+            received += 1;
+        }
+        let _ = Pin::new(&mut sink).poll_ready(&mut cx);
+    }
+    assert!(Pin::new(&mut sink).poll_ready(&mut cx).is_ready());
+    while let Poll::Ready(Some(_)) = rx.poll_next_unpin(&mut cx) {
+        received += 1;
+    }
+    assert_eq!(received, 4);
+
+    drop(rx);
+    Pin::new(&mut sink).start_send(PcmChunk(signal(FRAME, 1))).expect("send");
+    match Pin::new(&mut sink).poll_ready(&mut cx) {
+        Poll::Ready(Err(SinkError::Inner(_))) => (),
+        _ => panic!("Closed channel should fail"),
+    }
+}
+
+#[test]
+fn should_reject_invalid_frame_size() {
+    let encoder = Encoder::new(Channels::Mono, SampleRate::Hz16000, Application::Audio).expect("Create");
+    let (tx, _rx) = mpsc::unbounded::<Bytes>();
+    assert_eq!(EncodeSink::new(encoder, 1000, tx).err(), Some(ErrorCode::BadArg));
+}
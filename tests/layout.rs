@@ -0,0 +1,90 @@
+use opusic_c::layout::{Layout, Matrix, Order, Remap, Speaker};
+use opusic_c::{multistream, SampleRate, Application};
+
+#[test]
+fn should_reorder_between_layouts() {
+    let vorbis = Layout::new(Order::Vorbis, 6).expect("vorbis 5.1");
+    let wav = Layout::new(Order::Wav, 6).expect("wav 5.1");
+    let aac = Layout::new(Order::Aac, 6).expect("aac 5.1");
+    assert_eq!(wav.mask(), 0x3F);
+    assert_eq!(vorbis.mask(), wav.mask());
+    assert_eq!(Layout::from_mask(0x3F), Some(wav.clone()));
+    assert_eq!(wav.speakers(), [Speaker::FrontLeft, Speaker::FrontRight, Speaker::FrontCenter, Speaker::LowFrequency, Speaker::BackLeft, Speaker::BackRight]);
+
+    let remap = Remap::new(&vorbis, &wav).expect("remap");
+    assert_eq!(remap.table(), [0, 2, 1, 5, 3, 4]);
+    let remap = Remap::new(&vorbis, &aac).expect("remap");
+    assert_eq!(remap.table(), [1, 0, 2, 3, 4, 5]);
+
+    //5.1 with side speakers
+    let side = Layout::from_mask(0x60F).expect("5.1 side");
+    let remap = Remap::new(&vorbis, &side).expect("remap");
+    assert_eq!(remap.table(), [0, 2, 1, 5, 3, 4]);
+
+    let mut frame = [0u16, 1, 2, 3, 4, 5, 10, 11, 12, 13, 14, 15];
+    remap.apply(&mut frame);
+    assert_eq!(frame, [0, 2, 1, 5, 3, 4, 10, 12, 11, 15, 13, 14]);
+    let mut output = Vec::new();
+    Remap::new(&side, &vorbis).expect("remap").apply_to_vec(&frame, &mut output);
+    assert_eq!(output, [0, 1, 2, 3, 4, 5, 10, 11, 12, 13, 14, 15]);
+
+    assert!(Remap::new(&vorbis, &Layout::vorbis(5).expect("5.0")).is_none());
+    assert!(Remap::new(&Layout::new(Order::Aac, 4).expect("aac 4.0"), &Layout::vorbis(4).expect("quad")).is_none());
+    assert!(Layout::from_speakers(&[Speaker::FrontLeft, Speaker::FrontLeft]).is_none());
+    assert!(Layout::new(Order::Wav, 9).is_none());
+}
+
+#[test]
+fn should_downmix() {
+    let surround = Layout::vorbis(6).expect("5.1");
+    let stereo = Layout::vorbis(2).expect("stereo");
+    let mono = Layout::vorbis(1).expect("mono");
+    let half = core::f32::consts::FRAC_1_SQRT_2;
+
+    let matrix = Matrix::new(&surround, &stereo);
+    assert_eq!(matrix.inputs(), 6);
+    assert_eq!(matrix.outputs(), 2);
+    //FL C FR BL BR LFE
+    let expected = [
+        [1.0, half, 0.0, half, 0.0, 0.0],
+        [0.0, half, 1.0, 0.0, half, 0.0],
+    ];
+    for (output, row) in expected.iter().enumerate() {
+        for (input, gain) in row.iter().enumerate() {
+            assert_eq!(matrix.coefficient(output, input), *gain, "output={output} input={input}");
+        }
+    }
+
+    let mut matrix = Matrix::new(&stereo, &mono);
+    assert_eq!(matrix.coefficient(0, 0), 0.5);
+    assert_eq!(matrix.coefficient(0, 1), 0.5);
+    matrix.normalize();
+    assert_eq!(matrix.coefficient(0, 0), 0.5);
+    let mut output = Vec::new();
+    assert_eq!(matrix.apply_float_to_vec(&[1.0, 0.0, 0.5, 0.5], &mut output), 2);
+    assert_eq!(output, [0.5, 0.5]);
+
+    let matrix = Matrix::new(&mono, &stereo);
+    let mut output = Vec::new();
+    matrix.apply_to_vec(&[1000, 2000], &mut output);
+    assert_eq!(output, [1000, 1000, 2000, 2000]);
+
+    let mut matrix = Matrix::new(&surround, &stereo);
+    matrix.normalize();
+    let mut output = Vec::new();
+    matrix.apply_float_to_vec(&[1.0; 6], &mut output);
+    assert!(output.iter().all(|sample| (*sample - 1.0).abs() < 1e-6));
+}
+
+#[test]
+fn should_create_multistream_config_for_layout() {
+    let wav = Layout::new(Order::Wav, 6).expect("wav 5.1");
+    let config = wav.multistream_config::<6>().expect("config");
+    assert_eq!(*config.mapping(), [0, 1, 4, 5, 2, 3]);
+
+    let vorbis = Layout::vorbis(8).expect("7.1").multistream_config::<8>().expect("config");
+    assert_eq!(vorbis.mapping(), multistream::Config::<8>::vorbis().expect("config").mapping());
+    assert!(wav.multistream_config::<5>().is_err());
+
+    multistream::Encoder::new(config, SampleRate::Hz48000, Application::Audio).expect("create encoder");
+}
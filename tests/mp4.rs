@@ -0,0 +1,154 @@
+use opusic_c::{header, mp4, Encoder, Decoder, ErrorCode};
+use opusic_c::{frame_bytes_size, SampleRate, Channels, Application};
+
+const SIZE_20MS: usize = frame_bytes_size(SampleRate::Hz48000, Channels::Mono, 20);
+
+fn encode_sine(encoder: &mut Encoder, frames: usize) -> Vec<Vec<u8>> {
+    let mut packets = Vec::with_capacity(frames);
+    let mut input = [0f32; SIZE_20MS];
+    for frame in 0..frames {
+        for (idx, sample) in input.iter_mut().enumerate() {
+            let time = (frame * SIZE_20MS + idx) as f32 / 48000.0;
+            *sample = (time * 440.0 * 2.0 * core::f32::consts::PI).sin() * 0.5;
+        }
+
+        let mut packet = vec![0; 1275];
+        let len = encoder.encode_float_to_slice(&input, &mut packet).expect("to encode");
+        packet.truncate(len);
+        packets.push(packet);
+    }
+    packets
+}
+
+#[test]
+fn should_write_and_read_fragmented_mp4() {
+    let mut encoder = Encoder::new(Channels::Mono, SampleRate::Hz48000, Application::Audio).expect("Create");
+    let mut head = header::OpusHead::from_encoder(&mut encoder).expect("create head");
+    head.output_gain = -256;
+    let packets = encode_sine(&mut encoder, 120);
+
+    //Fragment every second
+    let mut writer = mp4::Writer::new(&head, 48000);
+    let mut file = writer.take_output();
+    let init_len = file.len();
+    assert_eq!(&file[4..8], b"ftyp");
+
+    for packet in packets.iter() {
+        writer.add_packet(packet).expect("add packet");
+    }
+    file.extend_from_slice(&writer.finish());
+    assert!(file.len() > init_len);
+
+    let reader = mp4::Reader::new(&file[..init_len]).expect("parse init segment");
+    assert!(reader.is_empty());
+
+    let mut reader = mp4::Reader::new(&file).expect("parse mp4");
+    assert_eq!(*reader.head(), head);
+    assert_eq!(reader.timescale(), 48000);
+    assert_eq!(reader.pre_skip(), head.pre_skip as u64);
+    assert_eq!(reader.len(), packets.len());
+
+    let mut decoder = Decoder::new(Channels::Mono, SampleRate::Hz48000).expect("Create");
+    decoder.set_gain(reader.head().output_gain as _).expect("set gain");
+    let mut decoded = [0u16; SIZE_20MS];
+    for (idx, packet) in (&mut reader).enumerate() {
+        assert_eq!(packet.data, packets[idx].as_slice());
+        assert_eq!(packet.timestamp, idx as u64 * 960);
+        let len = decoder.decode_to_slice(packet.data, &mut decoded, false).expect("to decode");
+        assert_eq!(len, SIZE_20MS);
+    }
+
+    reader.set_position(100);
+    assert_eq!(reader.next().expect("sample").data, packets[100].as_slice());
+    assert!(reader.get(packets.len()).is_none());
+}
+
+#[test]
+fn should_reject_invalid_mp4() {
+    assert!(mp4::Reader::new(&[]).is_err());
+    assert!(mp4::Reader::new(&[0, 0, 0, 9, b'f', b't', b'y', b'p']).is_err());
+
+    let head = header::OpusHead::new(Channels::Stereo, 312);
+    let mut entry = Vec::new();
+    mp4::write_sample_entry(&mut entry, &head);
+    assert_eq!(&entry[4..8], b"Opus");
+    assert_eq!(&entry[40..44], b"dOps");
+    assert_eq!(entry.len(), 36 + 8 + 11);
+}
+
+fn find(data: &[u8], kind: &[u8; 4]) -> usize {
+    data.windows(4).position(|window| window == kind).expect("box")
+}
+
+#[test]
+fn should_skip_empty_edit() {
+    let mut encoder = Encoder::new(Channels::Mono, SampleRate::Hz48000, Application::Audio).expect("Create");
+    let head = header::OpusHead::from_encoder(&mut encoder).expect("create head");
+    let mut writer = mp4::Writer::new(&head, 48000);
+    for packet in encode_sine(&mut encoder, 10).iter() {
+        writer.add_packet(packet).expect("add packet");
+    }
+    let mut file = writer.finish();
+
+    //Turn the only edit into empty one: version/flags, entry count and segment duration precede media time
+    let media_time = find(&file, b"elst") + 4 + 12;
+    file[media_time..media_time + 4].copy_from_slice(&u32::MAX.to_be_bytes());
+    let reader = mp4::Reader::new(&file).expect("parse mp4");
+    assert_eq!(reader.pre_skip(), head.pre_skip as u64);
+    assert_eq!(reader.len(), 10);
+}
+
+#[test]
+fn should_reject_invalid_channel_mapping() {
+    let mut encoder = Encoder::new(Channels::Mono, SampleRate::Hz48000, Application::Audio).expect("Create");
+    let head = header::OpusHead::from_encoder(&mut encoder).expect("create head");
+    let file = mp4::Writer::new(&head, 48000).finish();
+    let channels = find(&file, b"dOps") + 4 + 1;
+    mp4::Reader::new(&file).expect("parse mp4");
+
+    for invalid in [0, 3] {
+        let mut file = file.clone();
+        file[channels] = invalid;
+        assert_eq!(mp4::Reader::new(&file).err(), Some(ErrorCode::InvalidPacket));
+    }
+}
+
+//Appends fragment with single track run of `count` samples of `size` bytes, as set by track fragment defaults
+fn push_hostile_fragment(file: &mut Vec<u8>, size: u32, count: u32) {
+    let mut moof = Vec::new();
+    moof.extend_from_slice(&52u32.to_be_bytes());
+    moof.extend_from_slice(b"moof");
+    moof.extend_from_slice(&44u32.to_be_bytes());
+    moof.extend_from_slice(b"traf");
+    //tfhd with default-sample-size
+    moof.extend_from_slice(&20u32.to_be_bytes());
+    moof.extend_from_slice(b"tfhd");
+    moof.extend_from_slice(&0x000010u32.to_be_bytes());
+    moof.extend_from_slice(&1u32.to_be_bytes());
+    moof.extend_from_slice(&size.to_be_bytes());
+    //trun without per-sample fields
+    moof.extend_from_slice(&16u32.to_be_bytes());
+    moof.extend_from_slice(b"trun");
+    moof.extend_from_slice(&0u32.to_be_bytes());
+    moof.extend_from_slice(&count.to_be_bytes());
+    file.extend_from_slice(&moof);
+}
+
+#[test]
+fn should_reject_hostile_track_run() {
+    let mut encoder = Encoder::new(Channels::Mono, SampleRate::Hz48000, Application::Audio).expect("Create");
+    let head = header::OpusHead::from_encoder(&mut encoder).expect("create head");
+    let init = mp4::Writer::new(&head, 48000).finish();
+
+    for (size, count) in [(0, u32::MAX), (1, u32::MAX), (0, 1)] {
+        let mut file = init.clone();
+        push_hostile_fragment(&mut file, size, count);
+        assert_eq!(mp4::Reader::new(&file).err(), Some(ErrorCode::InvalidPacket), "size={size} count={count}");
+    }
+
+    //Run that fits into the rest of input is fine
+    let mut file = init.clone();
+    push_hostile_fragment(&mut file, 1, 4);
+    file.extend_from_slice(&[0, 0, 0, 12, b'f', b'r', b'e', b'e', 1, 2, 3, 4]);
+    assert_eq!(mp4::Reader::new(&file).expect("parse mp4").len(), 4);
+}
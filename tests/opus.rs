@@ -1,4 +1,4 @@
-use opusic_c::{multistream, repacketizer, Encoder, Decoder};
+use opusic_c::{multistream, repacketizer, utils, Encoder, Decoder};
 use opusic_c::{ErrorCode, frame_bytes_size, version};
 use opusic_c::{SampleRate, Channels, Application, Bandwidth, Bitrate, Signal, InbandFec, FrameDuration};
 
@@ -358,6 +358,153 @@ fn should_fail_to_repacketizer() {
     state.add_packet(&packet[..3]).expect("should successfully add packet with zero len");
 }
 
+#[test]
+fn should_pad_and_unpad_packet() {
+    let mut encoder = Encoder::new(Channels::Mono, SampleRate::Hz48000, Application::Audio).expect("create encoder");
+    let input = [0u16; 960];
+    let mut packet = Vec::with_capacity(1275);
+    encoder.encode_to_vec(&input, &mut packet).expect("encode");
+    let original = packet.clone();
+
+    //Packet occupies whole slice, so it cannot grow in place
+    let error = repacketizer::pad_packet(&mut packet, original.len() + 10).expect_err("should not pad beyond slice");
+    assert_eq!(error, ErrorCode::BadArg);
+    repacketizer::pad_packet(&mut packet, original.len()).expect("pad to the same size");
+    assert_eq!(packet, original);
+
+    let error = repacketizer::pad_packet_vec(&mut packet, original.len() - 1).expect_err("should not shrink");
+    assert_eq!(error, ErrorCode::BadArg);
+    assert_eq!(packet, original);
+
+    repacketizer::pad_packet_vec(&mut packet, original.len() + 300).expect("pad");
+    assert_eq!(packet.len(), original.len() + 300);
+    assert_eq!(utils::get_nb_samples(&packet, SampleRate::Hz48000).expect("get samples"), 960);
+
+    let len = repacketizer::unpad_packet(&mut packet).expect("unpad");
+    packet.truncate(len);
+    assert_eq!(packet, original);
+}
+
+#[test]
+fn should_repacketize_owned_packets() {
+    fn assert_send<T: Send + 'static>(_: &T) {}
+
+    let mut encoder = Encoder::new(Channels::Mono, SampleRate::Hz48000, Application::Audio).expect("create encoder");
+    let input = [0u16; 960];
+    let mut packets = Vec::new();
+    for _ in 0..6 {
+        let mut packet = Vec::with_capacity(1275);
+        encoder.encode_to_vec(&input, &mut packet).expect("encode");
+        packets.push(packet);
+    }
+
+    let mut repacketizer = repacketizer::OwnedRepacketizer::new().expect("create repacketizer");
+    assert_send(&repacketizer);
+    for packet in packets.iter() {
+        //Input is dropped right after submission
+        let packet = packet.clone();
+        repacketizer.add_packet(&packet).expect("add packet");
+    }
+    assert_eq!(repacketizer.get_nb_frames(), 6);
+
+    //Mismatching configuration leaves state intact
+    assert_eq!(repacketizer.add_packet(&[0x08, 0]).expect_err("should fail with change of TOC"), ErrorCode::InvalidPacket);
+    assert_eq!(repacketizer.get_nb_frames(), 6);
+
+    let mut combined = Vec::new();
+    repacketizer.create_packet_vec((0, 6), &mut combined).expect("create packet");
+    let bufs = packets.iter().map(Vec::as_slice).collect::<Vec<_>>();
+    let mut expected = Vec::with_capacity(6 * 1277);
+    let len = repacketizer::Repacketizer::new().expect("create repacketizer").combine_all(&bufs, expected.spare_capacity_mut()).expect("combine");
+    unsafe {
+        expected.set_len(len);
+    }
+    assert_eq!(combined, expected);
+
+    let mut single = Vec::new();
+    repacketizer.create_packet_vec((5, 6), &mut single).expect("create packet");
+    assert_eq!(single, packets[5]);
+
+    repacketizer.reset();
+    assert_eq!(repacketizer.get_nb_frames(), 0);
+    repacketizer.add_packet(&[0x08, 0]).expect("add packet after reset");
+    assert_eq!(repacketizer.get_nb_frames(), 1);
+}
+
+#[test]
+fn should_reframe_packets() {
+    let mut encoder = Encoder::new(Channels::Mono, SampleRate::Hz48000, Application::Audio).expect("create encoder");
+    let input = [0u16; 960];
+    let mut packets = Vec::new();
+    for _ in 0..10 {
+        let mut packet = Vec::with_capacity(1275);
+        encoder.encode_to_vec(&input, &mut packet).expect("encode");
+        packets.push(packet);
+    }
+
+    assert_eq!(repacketizer::Reframer::new(0).err(), Some(ErrorCode::BadArg));
+    assert_eq!(repacketizer::Reframer::new(5761).err(), Some(ErrorCode::BadArg));
+
+    //20ms into 60ms
+    let mut reframer = repacketizer::Reframer::new(2880).expect("create reframer");
+    let mut combined = Vec::new();
+    for packet in packets.iter() {
+        reframer.add_packet(packet).expect("add packet");
+        while let Some(packet) = reframer.next_packet() {
+            combined.push(packet);
+        }
+    }
+    assert_eq!(combined.len(), 3);
+    reframer.flush().expect("flush");
+    combined.push(reframer.next_packet().expect("flushed packet"));
+    assert!(reframer.next_packet().is_none());
+    let durations = combined.iter().map(|packet| utils::get_nb_samples(packet, SampleRate::Hz48000).expect("samples")).collect::<Vec<_>>();
+    assert_eq!(durations, [2880, 2880, 2880, 960]);
+
+    //And back to 20ms
+    let mut reframer = repacketizer::Reframer::new(960).expect("create reframer");
+    let mut split = Vec::new();
+    for packet in combined.iter() {
+        reframer.add_packet(packet).expect("add packet");
+        while let Some(packet) = reframer.next_packet() {
+            split.push(packet);
+        }
+    }
+    assert_eq!(split, packets);
+
+    //Configuration change flushes pending frames, while empty packet is passed through
+    let mut reframer = repacketizer::Reframer::new(2880).expect("create reframer");
+    reframer.add_packet(&[0xF8, 1, 2]).expect("add packet");
+    reframer.add_packet(&[0xF8, 3, 4]).expect("add packet");
+    assert!(reframer.next_packet().is_none());
+    reframer.add_packet(&[0xF0, 5]).expect("add packet");
+    assert_eq!(reframer.next_packet().expect("flushed"), [0xF9, 1, 2, 3, 4]);
+    reframer.add_packet(&[]).expect("add empty packet");
+    assert_eq!(reframer.next_packet().expect("flushed"), [0xF0, 5]);
+    assert_eq!(reframer.next_packet().expect("empty"), []);
+
+    //Corrupt packet with new configuration neither flushes pending frames nor changes configuration
+    let mut reframer = repacketizer::Reframer::new(2880).expect("create reframer");
+    reframer.add_packet(&[0xF8, 1, 2]).expect("add packet");
+    reframer.add_packet(&[0xF8, 3, 4]).expect("add packet");
+    assert_eq!(reframer.add_packet(&[0xF1, 5, 6, 7]).err(), Some(ErrorCode::InvalidPacket));
+    assert!(reframer.next_packet().is_none());
+    reframer.add_packet(&[0xF8, 8]).expect("add packet");
+    let packet = reframer.next_packet().expect("combined");
+    assert_eq!(utils::get_nb_frames(&packet).expect("frames"), 3);
+    assert!(reframer.next_packet().is_none());
+
+    //Packets never exceed 120ms
+    let mut reframer = repacketizer::Reframer::new(5760 - 960).expect("create reframer");
+    for _ in 0..3 {
+        reframer.add_packet(&[0x18, 1]).expect("add 60ms packet");
+    }
+    reframer.flush().expect("flush");
+    while let Some(packet) = reframer.next_packet() {
+        assert_eq!(utils::get_nb_samples(&packet, SampleRate::Hz48000).expect("samples"), 2880);
+    }
+}
+
 #[test]
 fn should_verify_multistream_encoder_building() {
     let config = multistream::Config::<2>::new(2, 0, [0, 1]);
@@ -558,3 +705,121 @@ fn should_verify_multistream_encoding_multi() {
     encoder.encode_to_vec(&input, &mut vec_output).expect("to encode");
     assert_eq!(vec_output, &[248, 2, 255, 254, 248, 255, 254, 248, 2, 255, 254, 248, 255, 254]);
 }
+
+#[test]
+fn should_verify_planar_encoding_and_decoding() {
+    const SIZE_20MS: usize = frame_bytes_size(SampleRate::Hz48000, Channels::Stereo, 20);
+    let mut left = [0f32; SIZE_20MS / 2];
+    let mut right = [0f32; SIZE_20MS / 2];
+    let mut interleaved = [0f32; SIZE_20MS];
+    for idx in 0..SIZE_20MS / 2 {
+        let time = idx as f32 / 48000.0;
+        left[idx] = (time * 440.0 * 2.0 * core::f32::consts::PI).sin() * 0.5;
+        right[idx] = (time * 660.0 * 2.0 * core::f32::consts::PI).sin() * 0.5;
+        interleaved[idx * 2] = left[idx];
+        interleaved[idx * 2 + 1] = right[idx];
+    }
+
+    let mut encoder = Encoder::new(Channels::Stereo, SampleRate::Hz48000, Application::Audio).expect("Create");
+    let mut expected = [0; 1275];
+    let expected_len = encoder.encode_float_to_slice(&interleaved, &mut expected).expect("to encode");
+    encoder.reset().expect("reset");
+    let mut output = Vec::with_capacity(1275);
+    let len = encoder.encode_float_planar_to_vec(&[&left, &right], &mut output).expect("to encode");
+    assert_eq!(output, &expected[..expected_len]);
+    assert_eq!(len, expected_len);
+    assert_eq!(encoder.encode_float_planar_to_vec(&[&left], &mut output).unwrap_err(), ErrorCode::BadArg);
+    assert_eq!(encoder.encode_float_planar_to_vec(&[&left, &right[1..]], &mut output).unwrap_err(), ErrorCode::BadArg);
+
+    let mut decoder = Decoder::new(Channels::Stereo, SampleRate::Hz48000).expect("Create");
+    let mut expected_decoded = [0f32; SIZE_20MS];
+    decoder.decode_float_to_slice(&output, &mut expected_decoded, false).expect("to decode");
+    decoder.reset().expect("reset");
+    let mut decoded_left = [0f32; SIZE_20MS / 2];
+    let mut decoded_right = [0f32; SIZE_20MS / 2];
+    let len = decoder.decode_float_planar_to(&output, &mut [&mut decoded_left, &mut decoded_right], false).expect("to decode");
+    assert_eq!(len, SIZE_20MS / 2);
+    for idx in 0..len {
+        assert_eq!(decoded_left[idx], expected_decoded[idx * 2]);
+        assert_eq!(decoded_right[idx], expected_decoded[idx * 2 + 1]);
+    }
+    assert_eq!(decoder.decode_float_planar_to(&output, &mut [&mut decoded_left], false).unwrap_err(), ErrorCode::BadArg);
+
+    let config = multistream::Config::<2>::new(2, 0, [0, 1]);
+    let mut encoder = multistream::Encoder::new(config, SampleRate::Hz48000, Application::Audio).expect("create new encoder");
+    let expected_len = encoder.encode_float_to_slice(&interleaved, &mut expected).expect("to encode");
+    encoder.reset().expect("reset");
+    let mut output = [0; 1275];
+    let len = encoder.encode_float_planar_to_slice(&[&left, &right], &mut output).expect("to encode");
+    assert_eq!(output[..len], expected[..expected_len]);
+
+    let config = multistream::Config::<2>::new(2, 0, [0, 1]);
+    let mut decoder = multistream::Decoder::new(config, SampleRate::Hz48000).expect("create new decoder");
+    decoder.decode_float_to_slice(&output[..len], &mut expected_decoded, false).expect("to decode");
+    decoder.reset().expect("reset");
+    let len = decoder.decode_float_planar_to(&output[..len], &mut [&mut decoded_left, &mut decoded_right], false).expect("to decode");
+    assert_eq!(len, SIZE_20MS / 2);
+    for idx in 0..len {
+        assert_eq!(decoded_left[idx], expected_decoded[idx * 2]);
+        assert_eq!(decoded_right[idx], expected_decoded[idx * 2 + 1]);
+    }
+}
+
+#[test]
+fn should_repacketize_multistream_packets() {
+    let config = || multistream::Config::<6>::vorbis().expect("5.1 config");
+    let mut encoder = multistream::Encoder::new(config(), SampleRate::Hz48000, Application::Audio).expect("create encoder");
+    let mut input = [0f32; 960 * 6];
+    let mut packets = Vec::new();
+    for frame in 0..6 {
+        for (idx, sample) in input.iter_mut().enumerate() {
+            let time = (frame * 960 + idx / 6) as f32 / 48000.0;
+            *sample = (time * (220.0 + 110.0 * (idx % 6) as f32) * 2.0 * core::f32::consts::PI).sin() * 0.3;
+        }
+        let mut packet = Vec::with_capacity(6 * 1277);
+        encoder.encode_float_to_vec(&input, &mut packet).expect("encode");
+        packets.push(packet);
+    }
+
+    let mut repacketizer = multistream::Repacketizer::new(&config()).expect("create repacketizer");
+    for packet in packets[..3].iter() {
+        repacketizer.add_packet(packet).expect("add packet");
+    }
+    assert_eq!(repacketizer.get_nb_frames(), 3);
+    assert_eq!(repacketizer.add_packet(&packets[3][..2]).expect_err("truncated packet"), ErrorCode::InvalidPacket);
+    assert_eq!(repacketizer.get_nb_frames(), 3);
+
+    let mut combined = Vec::new();
+    repacketizer.create_full_packet_vec(&mut combined).expect("create packet");
+    assert_eq!(utils::get_nb_samples(&combined, SampleRate::Hz48000).expect("samples"), 2880);
+
+    //Combined packet decodes into the same audio
+    let mut decoder = multistream::Decoder::new(config(), SampleRate::Hz48000).expect("create decoder");
+    let mut expected = vec![0f32; 2880 * 6];
+    for (packet, output) in packets[..3].iter().zip(expected.chunks_mut(960 * 6)) {
+        assert_eq!(decoder.decode_float_to_slice(packet, output, false).expect("decode"), 960);
+    }
+    let mut decoder = multistream::Decoder::new(config(), SampleRate::Hz48000).expect("create decoder");
+    let mut decoded = vec![0f32; 2880 * 6];
+    assert_eq!(decoder.decode_float_to_slice(&combined, &mut decoded, false).expect("decode"), 2880);
+    assert_eq!(decoded, expected);
+
+    //And can be split back
+    let mut repacketizer = multistream::Repacketizer::new(&config()).expect("create repacketizer");
+    repacketizer.add_packet(&combined).expect("add combined packet");
+    for (idx, packet) in packets[..3].iter().enumerate() {
+        let mut split = Vec::new();
+        repacketizer.create_packet_vec((idx as u32, idx as u32 + 1), &mut split).expect("create packet");
+        assert_eq!(split, *packet);
+    }
+
+    //Padding
+    let mut padded = packets[4].clone();
+    assert_eq!(multistream::pad_packet(&config(), &mut padded, packets[4].len() + 1).expect_err("should not pad beyond slice"), ErrorCode::BadArg);
+    multistream::pad_packet_vec(&config(), &mut padded, packets[4].len() + 100).expect("pad");
+    assert_eq!(padded.len(), packets[4].len() + 100);
+    let mut repacketizer = multistream::Repacketizer::new(&config()).expect("create repacketizer");
+    repacketizer.add_packet(&padded).expect("add padded packet");
+    let len = multistream::unpad_packet(&config(), &mut padded).expect("unpad");
+    assert_eq!(&padded[..len], packets[4].as_slice());
+}
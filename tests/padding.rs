@@ -0,0 +1,98 @@
+use opusic_c::padding::{self, Padder, Policy};
+use opusic_c::{multistream, Encoder, Decoder, ErrorCode};
+use opusic_c::{SampleRate, Channels, Application, Bitrate};
+
+fn frame(idx: usize, channels: usize) -> Vec<f32> {
+    //Alternate speech-like bursts and silence to get variable packet size
+    let amplitude = match (idx / 10) % 2 {
+        0 => 0.5,
+        _ => 0.01,
+    };
+    (0..960 * channels).map(|sample| {
+        let time = (idx * 960 + sample / channels) as f32 / 48000.0;
+        (time * (300.0 + 50.0 * (idx % 7) as f32) * 2.0 * core::f32::consts::PI).sin() * amplitude
+    }).collect()
+}
+
+#[test]
+fn should_pad_to_constant_size() {
+    let mut encoder = Encoder::new(Channels::Mono, SampleRate::Hz48000, Application::Voip).expect("Create");
+    encoder.set_bitrate(Bitrate::Value(32000)).expect("set bitrate");
+    encoder.set_vbr_constraint(true).expect("set vbr constraint");
+    let mut padder = Padder::new(Policy::Constant(120)).expect("create padder");
+    let mut decoder = Decoder::new(Channels::Mono, SampleRate::Hz48000).expect("Create");
+    let mut stripped_decoder = Decoder::new(Channels::Mono, SampleRate::Hz48000).expect("Create");
+    let mut decoded = [0f32; 960];
+    let mut stripped_decoded = [0f32; 960];
+
+    let mut sizes = Vec::new();
+    for idx in 0..50 {
+        let mut packet = Vec::new();
+        assert_eq!(padder.encode_float(&mut encoder, &frame(idx, 1), &mut packet).expect("encode"), 120);
+        assert_eq!(packet.len(), 120);
+        decoder.decode_float_to_slice(&packet, &mut decoded, false).expect("decode padded");
+
+        padding::strip(&mut packet).expect("strip");
+        assert!(packet.len() <= 120);
+        sizes.push(packet.len());
+        stripped_decoder.decode_float_to_slice(&packet, &mut stripped_decoded, false).expect("decode stripped");
+        assert_eq!(decoded, stripped_decoded);
+    }
+
+    assert!(sizes.iter().any(|size| *size != sizes[0]), "Payload must be VBR");
+    assert_eq!(padder.packets(), 50);
+    assert_eq!(padder.payload_bytes(), sizes.iter().sum::<usize>() as u64);
+    assert_eq!(padder.payload_bytes() + padder.padding_bytes(), 50 * 120);
+    assert!(padder.overhead() > 0.0);
+
+    let mut packet = vec![0xF8; 121];
+    assert_eq!(padder.pad(&mut packet).expect_err("should not fit"), ErrorCode::BadArg);
+    assert_eq!(packet.len(), 121);
+    assert!(Padder::new(Policy::Bucket(0)).is_err());
+}
+
+#[test]
+fn should_pad_to_bucket_size() {
+    let mut encoder = Encoder::new(Channels::Mono, SampleRate::Hz48000, Application::Voip).expect("Create");
+    let mut padder = Padder::new(Policy::Bucket(64)).expect("create padder");
+
+    let mut output = Vec::new();
+    let mut total = 0;
+    for idx in 0..30 {
+        let len = padder.encode_float(&mut encoder, &frame(idx, 1), &mut output).expect("encode");
+        assert_eq!(len % 64, 0);
+        total += len;
+    }
+    assert_eq!(output.len(), total);
+    assert_eq!((padder.payload_bytes() + padder.padding_bytes()) as usize, total);
+
+    let mut packet = vec![0xF8, 1, 2, 3];
+    padder.pad(&mut packet).expect("pad");
+    assert_eq!(packet.len(), 64);
+    padding::strip(&mut packet).expect("strip");
+    assert_eq!(packet, [0xF8, 1, 2, 3]);
+}
+
+#[test]
+fn should_pad_multistream_packets() {
+    let config = multistream::Config::<6>::vorbis().expect("5.1 config");
+    let mut encoder = multistream::Encoder::new(multistream::Config::<6>::vorbis().expect("5.1 config"), SampleRate::Hz48000, Application::Audio).expect("Create");
+    encoder.set_vbr_constraint(true).expect("set vbr constraint");
+    let mut decoder = multistream::Decoder::new(multistream::Config::<6>::vorbis().expect("5.1 config"), SampleRate::Hz48000).expect("Create");
+    let mut padder = Padder::new(Policy::Constant(600)).expect("create padder");
+    let mut decoded = vec![0f32; 960 * 6];
+
+    for idx in 0..20 {
+        let mut packet = Vec::new();
+        padder.encode_multistream_float(&mut encoder, &config, &frame(idx, 6), &mut packet).expect("encode");
+        assert_eq!(packet.len(), 600);
+        padding::strip_multistream(&config, &mut packet).expect("strip");
+        assert!(packet.len() <= 600);
+        assert_eq!(decoder.decode_float_to_slice(&packet, &mut decoded, false).expect("decode"), 960);
+
+        padder.pad_multistream(&config, &mut packet).expect("pad again");
+        assert_eq!(packet.len(), 600);
+        assert_eq!(decoder.decode_float_to_slice(&packet, &mut decoded, false).expect("decode"), 960);
+    }
+    assert_eq!(padder.packets(), 40);
+}
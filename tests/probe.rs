@@ -0,0 +1,106 @@
+use opusic_c::{header, ogg, webm, probe, Encoder, Bitrate};
+use opusic_c::{frame_bytes_size, SampleRate, Channels, Application};
+
+const SIZE_20MS: usize = frame_bytes_size(SampleRate::Hz48000, Channels::Stereo, 20);
+
+//Channels are in anti-phase, so that encoder keeps coding them as stereo
+fn encode_anti_phase(encoder: &mut Encoder, frames: usize) -> Vec<Vec<u8>> {
+    let mut packets = Vec::with_capacity(frames);
+    let mut input = [0f32; SIZE_20MS];
+    for frame in 0..frames {
+        for (idx, sample) in input.chunks_mut(2).enumerate() {
+            let time = (frame * SIZE_20MS / 2 + idx) as f32 / 48000.0;
+            let value = (time * 440.0 * 2.0 * core::f32::consts::PI).sin() * 0.5;
+            sample[0] = value;
+            sample[1] = -value;
+        }
+
+        let mut packet = vec![0; 1275];
+        let len = encoder.encode_float_to_slice(&input, &mut packet).expect("to encode");
+        packet.truncate(len);
+        packets.push(packet);
+    }
+    packets
+}
+
+fn assert_distribution(result: &probe::Probe) {
+    let modes = result.modes;
+    assert_eq!(modes.silk + modes.hybrid + modes.celt, result.samples);
+    let bandwidths = result.bandwidths;
+    assert_eq!(bandwidths.narrow + bandwidths.medium + bandwidths.wide + bandwidths.superwide + bandwidths.full, result.samples);
+    assert!(result.average_bitrate() <= result.peak_bitrate);
+}
+
+#[test]
+fn should_probe_ogg_duration() {
+    let mut encoder = Encoder::new(Channels::Stereo, SampleRate::Hz48000, Application::Audio).expect("Create");
+    encoder.set_bitrate(Bitrate::Value(64000)).expect("set bitrate");
+    let head = header::OpusHead::from_encoder(&mut encoder).expect("create head");
+    let packets = encode_anti_phase(&mut encoder, 150);
+
+    let mut writer = ogg::Writer::new(&head, &header::OpusTags::new(), 1);
+    for packet in packets.iter() {
+        writer.add_packet(packet).expect("add packet");
+    }
+    writer.set_end_trim(500);
+    let file = writer.finish();
+
+    let result = probe::probe_ogg(&file).expect("probe ogg");
+    assert_eq!(result.head.as_ref(), Some(&head));
+    assert_eq!(result.channels(), 2);
+    assert_eq!(result.packets, 150);
+    assert_eq!(result.bytes, packets.iter().map(|packet| packet.len() as u64).sum::<u64>());
+    assert_eq!(result.samples, 150 * 960);
+    assert_eq!(result.duration, 150 * 960 - 500 - head.pre_skip as u64);
+    assert_eq!(result.stereo_packets, 150);
+    assert_eq!(result.modes.celt, result.samples);
+    assert_distribution(&result);
+
+    let bitrate = result.average_bitrate();
+    assert!(bitrate > 48000 && bitrate < 80000, "bitrate={bitrate}");
+}
+
+#[test]
+fn should_probe_webm_duration() {
+    let mut encoder = Encoder::new(Channels::Stereo, SampleRate::Hz48000, Application::Voip).expect("Create");
+    encoder.set_bitrate(Bitrate::Value(16000)).expect("set bitrate");
+    let head = header::OpusHead::from_encoder(&mut encoder).expect("create head");
+    let packets = encode_anti_phase(&mut encoder, 100);
+
+    let mut muxer = webm::Muxer::new(&head);
+    for packet in packets.iter() {
+        muxer.add_packet(packet).expect("add packet");
+    }
+    let file = muxer.finish();
+
+    let result = probe::probe_webm(&file).expect("probe webm");
+    assert_eq!(result.head.as_ref(), Some(&head));
+    assert_eq!(result.packets, 100);
+    assert_eq!(result.samples, 100 * 960);
+    assert_eq!(result.duration, 100 * 960 - head.pre_skip as u64);
+    assert_distribution(&result);
+}
+
+#[test]
+fn should_probe_raw_packets() {
+    let mut encoder = Encoder::new(Channels::Stereo, SampleRate::Hz48000, Application::Audio).expect("Create");
+    let mut packets = encode_anti_phase(&mut encoder, 25);
+    packets.insert(10, Vec::new());
+
+    let result = probe::probe_packets(packets.iter()).expect("probe packets");
+    assert!(result.head.is_none());
+    assert_eq!(result.packets, 25);
+    assert_eq!(result.samples, 25 * 960);
+    assert_eq!(result.duration, result.samples);
+    assert_eq!(result.channels(), 2);
+    //Shorter than peak window
+    assert_eq!(result.peak_bitrate, result.average_bitrate());
+    assert_distribution(&result);
+
+    let result = probe::probe_packets(core::iter::empty::<&[u8]>()).expect("probe nothing");
+    assert_eq!(result.packets, 0);
+    assert_eq!(result.average_bitrate(), 0);
+    assert_eq!(result.channels(), 1);
+
+    assert!(probe::probe_packets([[0xFFu8].as_slice()]).is_err());
+}
@@ -0,0 +1,122 @@
+use opusic_c::resample::{Quality, Resampler, ResamplingEncoder, ResamplingDecoder};
+use opusic_c::{utils, Encoder, Decoder, SampleRate, Channels, Application, FrameDuration};
+
+fn sine(rate: u32, frequency: f64, len: usize) -> Vec<f32> {
+    (0..len).map(|idx| ((idx as f64 / rate as f64) * frequency * 2.0 * core::f64::consts::PI).sin() as f32 * 0.5).collect()
+}
+
+fn snr(expected: &[f32], actual: &[f32]) -> f64 {
+    let mut signal = 0.0;
+    let mut noise = 0.0;
+    for (expected, actual) in expected.iter().zip(actual.iter()) {
+        signal += (*expected as f64).powi(2);
+        noise += (*expected as f64 - *actual as f64).powi(2);
+    }
+    10.0 * (signal / noise).log10()
+}
+
+#[test]
+fn should_resample_sine() {
+    for (input_rate, output_rate) in [(44100, 48000), (48000, 44100), (96000, 48000), (8000, 48000), (48000, 16000)] {
+        for quality in [Quality::Low, Quality::Medium, Quality::High] {
+            let input = sine(input_rate, 1000.0, input_rate as usize);
+            let mut resampler = Resampler::new(input_rate, output_rate, 1, quality).expect("create resampler");
+            assert_eq!(resampler.output_len(input.len()), output_rate as usize);
+
+            let mut output = Vec::new();
+            let mut len = 0;
+            for chunk in input.chunks(1000) {
+                len += resampler.resample_float_to_vec(chunk, &mut output);
+            }
+            assert!(len < output_rate as usize);
+            len += resampler.flush_float_to_vec(&mut output);
+            assert_eq!(len, output_rate as usize);
+            assert_eq!(output.len(), output_rate as usize);
+
+            let expected = sine(output_rate, 1000.0, output_rate as usize);
+            //Skip edges which are affected by zero padding
+            let edge = output_rate as usize / 100;
+            let snr = snr(&expected[edge..expected.len() - edge], &output[edge..output.len() - edge]);
+            assert!(snr > 40.0, "{input_rate}->{output_rate} {quality:?}: SNR={snr}");
+        }
+    }
+}
+
+#[test]
+fn should_reject_unsupported_ratio() {
+    assert!(Resampler::new(0, 48000, 1, Quality::Medium).is_err());
+    assert!(Resampler::new(44100, 48000, 0, Quality::Medium).is_err());
+    assert!(Resampler::new(44101, 48000, 1, Quality::Medium).is_err());
+}
+
+#[test]
+fn should_encode_and_decode_44100() {
+    let encoder = Encoder::new(Channels::Stereo, SampleRate::Hz48000, Application::Audio).expect("Create");
+    let mut encoder = ResamplingEncoder::new(encoder, 44100, Quality::Medium).expect("create resampling encoder");
+    assert_eq!(encoder.frame_size().expect("frame size"), 960);
+    let head = encoder.opus_head().expect("create head");
+    assert_eq!(head.input_sample_rate, 44100);
+    assert_eq!(head.pre_skip, 312);
+
+    let input = sine(44100, 440.0, 44100);
+    let input: Vec<f32> = input.iter().flat_map(|sample| [*sample, *sample]).collect();
+    let mut packets = Vec::new();
+    for chunk in input.chunks(4410 * 2) {
+        encoder.push_float(chunk);
+        let mut packet = Vec::with_capacity(1275);
+        while encoder.encode_to_vec(&mut packet).expect("to encode").is_some() {
+            packets.push(packet);
+            packet = Vec::with_capacity(1275);
+        }
+    }
+    let padding = encoder.flush().expect("flush");
+    let mut packet = Vec::with_capacity(1275);
+    while encoder.encode_to_vec(&mut packet).expect("to encode").is_some() {
+        packets.push(packet);
+        packet = Vec::with_capacity(1275);
+    }
+    assert_eq!(packets.len() * 960 - padding, 48000);
+
+    let decoder = Decoder::new(Channels::Stereo, SampleRate::Hz48000).expect("Create");
+    let mut decoder = ResamplingDecoder::new(decoder, 44100, Quality::Medium).expect("create resampling decoder");
+    assert_eq!(decoder.output_rate(), 44100);
+    let mut output = Vec::new();
+    let mut len = 0;
+    for packet in packets.iter() {
+        len += decoder.decode_to_vec(packet, &mut output, 0, false).expect("to decode");
+    }
+    len += decoder.decode_to_vec(&[], &mut output, 960 * 2, false).expect("to conceal");
+    len += decoder.flush_to_vec(&mut output);
+    assert_eq!(len, (packets.len() + 1) * 882);
+    assert_eq!(output.len(), len * 2);
+}
+
+#[test]
+fn should_follow_encoder_frame_duration() {
+    let encoder = Encoder::new(Channels::Mono, SampleRate::Hz48000, Application::Audio).expect("Create");
+    let mut encoder = ResamplingEncoder::new(encoder, 44100, Quality::Low).expect("create resampling encoder");
+    let input = sine(44100, 440.0, 44100);
+
+    let mut total = 0;
+    //Duration is changed after construction too
+    for (duration, frame_size) in [(FrameDuration::Size60, 2880), (FrameDuration::Size10, 480)] {
+        encoder.encoder().set_frame_duration(duration).expect("set frame duration");
+        assert_eq!(encoder.frame_size().expect("frame size"), frame_size);
+
+        encoder.push_float(&input[..22050]);
+        let mut packet = Vec::with_capacity(1275);
+        while encoder.encode_to_vec(&mut packet).expect("to encode").is_some() {
+            assert_eq!(utils::get_nb_samples(&packet, SampleRate::Hz48000).expect("samples"), frame_size);
+            total += frame_size;
+            packet.clear();
+        }
+    }
+
+    let padding = encoder.flush().expect("flush");
+    let mut packet = Vec::with_capacity(1275);
+    while encoder.encode_to_vec(&mut packet).expect("to encode").is_some() {
+        total += 480;
+        packet.clear();
+    }
+    assert_eq!(total - padding, 48000);
+}
@@ -0,0 +1,78 @@
+use opusic_c::sim::{FrameStatus, LossModel, Network, Recovery, Simulation};
+use opusic_c::{Encoder, SampleRate, Channels, Application, Bitrate, InbandFec};
+
+fn input(rate: SampleRate, len: usize) -> Vec<f32> {
+    (0..len).map(|idx| {
+        let time = idx as f32 / rate as i32 as f32;
+        //Amplitude modulated tone to resemble speech envelope
+        let envelope = 0.5 + 0.5 * (time * 3.0 * 2.0 * core::f32::consts::PI).sin();
+        (time * 220.0 * 2.0 * core::f32::consts::PI).sin() * 0.4 * envelope
+    }).collect()
+}
+
+fn encoder(fec: InbandFec) -> Encoder {
+    let mut encoder = Encoder::new(Channels::Mono, SampleRate::Hz16000, Application::Voip).expect("Create");
+    encoder.set_bitrate(Bitrate::Value(24000)).expect("set bitrate");
+    encoder.set_inband_fec(fec).expect("set fec");
+    encoder.set_packet_loss(20).expect("set packet loss");
+    encoder
+}
+
+#[test]
+fn should_simulate_without_loss() {
+    let input = input(SampleRate::Hz16000, 16000 * 2);
+    let simulation = Simulation::new(Network::default(), Recovery::None);
+    let report = simulation.run(&mut encoder(InbandFec::Off), &input).expect("run");
+
+    assert_eq!(report.frames.len(), 100);
+    assert_eq!(report.count(FrameStatus::Decoded), 100);
+    assert_eq!(report.lost, 0);
+    assert_eq!(report.late, 0);
+    assert_eq!(report.concealment_ratio(), 0.0);
+    assert_eq!(report.output.len(), 100 * 320);
+    assert!(report.bytes > 0);
+    assert!(report.snr > 5.0, "snr={}", report.snr);
+}
+
+#[test]
+fn should_recover_lost_packets_with_fec() {
+    let input = input(SampleRate::Hz16000, 16000 * 4);
+    let network = Network {
+        loss: LossModel::Bernoulli(0.2),
+        seed: 5,
+        ..Network::default()
+    };
+
+    let report = Simulation::new(network, Recovery::None).run(&mut encoder(InbandFec::Off), &input).expect("run");
+    assert!(report.lost > 0);
+    assert_eq!(report.concealed(), report.lost);
+    assert_eq!(report.recovered(), 0);
+
+    let fec_report = Simulation::new(network, Recovery::Fec).run(&mut encoder(InbandFec::Mode1), &input).expect("run");
+    //Same seed produces same losses
+    assert_eq!(fec_report.lost, report.lost);
+    assert!(fec_report.recovered() > 0);
+    assert_eq!(fec_report.recovered() + fec_report.concealed(), fec_report.lost);
+    assert!(fec_report.concealment_ratio() < report.concealment_ratio());
+}
+
+#[test]
+fn should_simulate_bursts_and_jitter() {
+    let input = input(SampleRate::Hz16000, 16000 * 4);
+    let network = Network {
+        loss: LossModel::bursts(0.1, 3.0),
+        reorder: 0.1,
+        jitter: 2,
+        playout_delay: 2,
+        seed: 1,
+    };
+
+    let report = Simulation::new(network, Recovery::Fec).run(&mut encoder(InbandFec::Mode1), &input).expect("run");
+    assert!(report.lost > 0);
+    assert!(report.reordered > 0);
+    assert!(report.late > 0);
+    assert_eq!(report.recovered() + report.concealed(), report.lost + report.late);
+
+    let again = Simulation::new(network, Recovery::Fec).run(&mut encoder(InbandFec::Mode1), &input).expect("run");
+    assert_eq!(again.frames, report.frames);
+}
@@ -0,0 +1,106 @@
+use opusic_c::utils::{self, Toc, Mode};
+use opusic_c::{Encoder, SampleRate, Channels, Application, Bandwidth, Bitrate, ErrorCode};
+
+fn encode(channels: Channels, application: Application, bitrate: u32) -> Vec<u8> {
+    let mut encoder = Encoder::new(channels, SampleRate::Hz48000, application).expect("Create");
+    encoder.set_bitrate(Bitrate::Value(bitrate)).expect("set bitrate");
+    let input: Vec<u16> = (0..960 * channels as usize).map(|idx| ((idx as f32 / 30.0).sin() * 8000.0) as i16 as u16).collect();
+    let mut packet = Vec::with_capacity(1275);
+    encoder.encode_to_vec(&input, &mut packet).expect("to encode");
+    packet
+}
+
+#[test]
+fn should_parse_toc() {
+    //SILK NB 10ms, mono, single frame
+    let toc = Toc(0);
+    assert_eq!(toc.config(), 0);
+    assert_eq!(toc.mode(), Mode::Silk);
+    assert_eq!(toc.bandwidth(), Bandwidth::Narrow);
+    assert_eq!(toc.frame_size(), 480);
+    assert_eq!(toc.channels(), Channels::Mono);
+    assert_eq!(toc.code(), 0);
+
+    //SILK WB 60ms
+    let toc = Toc(11 << 3 | 0b101);
+    assert_eq!(toc.mode(), Mode::Silk);
+    assert_eq!(toc.bandwidth(), Bandwidth::Wide);
+    assert_eq!(toc.frame_size(), 2880);
+    assert_eq!(toc.channels(), Channels::Stereo);
+    assert_eq!(toc.code(), 1);
+
+    //Hybrid FB 20ms
+    let toc = Toc(15 << 3 | 0b011);
+    assert_eq!(toc.mode(), Mode::Hybrid);
+    assert_eq!(toc.bandwidth(), Bandwidth::Full);
+    assert_eq!(toc.frame_size(), 960);
+    assert_eq!(toc.code(), 3);
+
+    //CELT WB 2.5ms
+    let toc = Toc(20 << 3);
+    assert_eq!(toc.mode(), Mode::Celt);
+    assert_eq!(toc.bandwidth(), Bandwidth::Wide);
+    assert_eq!(toc.frame_size(), 120);
+
+    for config in 0..32u8 {
+        let packet = [config << 3, 0];
+        let toc = Toc::parse(&packet).expect("parse");
+        assert_eq!(toc.bandwidth(), utils::get_bandwidth(&packet).expect("get bandwidth"));
+        assert_eq!(toc.frame_size(), utils::get_nb_samples(&packet, SampleRate::Hz48000).expect("get samples"));
+    }
+
+    assert_eq!(Toc::parse(&[]).unwrap_err(), ErrorCode::InvalidPacket);
+    assert_eq!(utils::get_bandwidth(&[]).unwrap_err(), ErrorCode::InvalidPacket);
+}
+
+#[test]
+fn should_inspect_encoded_packets() {
+    let packet = encode(Channels::Stereo, Application::Audio, 128000);
+    let toc = Toc::parse(&packet).expect("parse");
+    assert_eq!(toc.mode(), Mode::Celt);
+    assert_eq!(toc.frame_size(), 960);
+    assert_eq!(utils::get_nb_channels(&packet).expect("get channels"), Channels::Stereo);
+
+    let packet = encode(Channels::Mono, Application::Voip, 12000);
+    let toc = Toc::parse(&packet).expect("parse");
+    assert_eq!(toc.mode(), Mode::Silk);
+    assert_eq!(utils::get_nb_channels(&packet).expect("get channels"), Channels::Mono);
+}
+
+#[test]
+fn should_find_padding_and_dred() {
+    let packet = encode(Channels::Mono, Application::Audio, 64000);
+    assert!(utils::get_padding(&packet).expect("get padding").is_empty());
+    assert!(!utils::has_dred(&packet).expect("has dred"));
+
+    //Single frame code 3 packet with plain padding
+    let mut padded = vec![packet[0] | 0x3, 0x40 | 1, 20];
+    padded.extend_from_slice(&packet[1..]);
+    padded.resize(padded.len() + 20, 0);
+    assert_eq!(utils::get_padding(&padded).expect("get padding"), &[0; 20]);
+    assert!(!utils::has_dred(&padded).expect("has dred"));
+
+    //Single frame code 3 packet with DRED extension spanning the rest of padding
+    let extension = [126 << 1, b'D', 10, 1, 2, 3];
+    let mut dred = vec![packet[0] | 0x3, 0x40 | 1, extension.len() as u8];
+    dred.extend_from_slice(&packet[1..]);
+    dred.extend_from_slice(&extension);
+    assert_eq!(utils::get_padding(&dred).expect("get padding"), &extension);
+    assert!(utils::has_dred(&dred).expect("has dred"));
+
+    //Preceded by padding byte and short extension
+    let extension = [0x01, 5 << 1 | 1, 0xaa, 126 << 1 | 1, 3, b'D', 10, 1];
+    let mut dred = vec![packet[0] | 0x3, 0x40 | 1, extension.len() as u8];
+    dred.extend_from_slice(&packet[1..]);
+    dred.extend_from_slice(&extension);
+    assert!(utils::has_dred(&dred).expect("has dred"));
+
+    //Unknown experimental version
+    let extension = [126 << 1, b'D', 9, 1, 2, 3];
+    let mut dred = vec![packet[0] | 0x3, 0x40 | 1, extension.len() as u8];
+    dred.extend_from_slice(&packet[1..]);
+    dred.extend_from_slice(&extension);
+    assert!(!utils::has_dred(&dred).expect("has dred"));
+
+    assert_eq!(utils::get_padding(&[packet[0] | 0x3]).unwrap_err(), ErrorCode::InvalidPacket);
+}
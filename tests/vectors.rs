@@ -0,0 +1,205 @@
+//Conformance test against RFC 6716/8251 test vectors.
+//
+//Official vectors are used when `OPUS_TEST_VECTORS` points to directory with `testvectorNN.bit`,
+//`testvectorNN.dec` and `testvectorNNm.dec` (RFC 8251 alternative stereo reference) files.
+//Otherwise vectors are generated locally in the same `opus_demo` format, so that test runs offline.
+
+use opusic_c::compare::compare;
+use opusic_c::demo_format::{Frame, Reader, Writer};
+use opusic_c::{Encoder, Decoder, SampleRate, Channels, Application, Bitrate, Bandwidth, FrameDuration};
+
+const RATES: [SampleRate; 5] = [SampleRate::Hz8000, SampleRate::Hz12000, SampleRate::Hz16000, SampleRate::Hz24000, SampleRate::Hz48000];
+const CHANNELS: [Channels; 2] = [Channels::Mono, Channels::Stereo];
+//120ms at 48kHz
+const MAX_FRAME_SIZE: usize = 5760;
+
+fn parse_bitstream(data: &[u8]) -> Vec<Frame<'_>> {
+    let mut reader = Reader::new(data);
+    let mut result = Vec::new();
+    while let Some(frame) = reader.next_frame().expect("read frame") {
+        result.push(frame);
+    }
+    result
+}
+
+fn read_pcm(data: &[u8]) -> Vec<u16> {
+    data.chunks_exact(2).map(|sample| u16::from_le_bytes([sample[0], sample[1]])).collect()
+}
+
+//Decodes the same way as `opus_demo -d`, verifying final range of every packet
+//
+//Returns decoded output together with number of packets, whose final range was verified
+fn decode(name: &str, frames: &[Frame], rate: SampleRate, channels: Channels) -> (Vec<u16>, usize) {
+    let mut decoder = Decoder::new(channels, rate).expect("create decoder");
+    let max_frame_size = MAX_FRAME_SIZE * rate as usize / 48000;
+    let mut buffer = vec![0u16; max_frame_size * channels as usize];
+    let mut output = Vec::new();
+    let mut lost_prev = false;
+    let mut verified = 0;
+
+    for (idx, frame) in frames.iter().enumerate() {
+        let lost = frame.is_lost();
+        let frame_size = match lost {
+            true => decoder.get_last_packet_duration().expect("get last packet duration") as usize,
+            false => max_frame_size,
+        };
+        let len = decoder.decode_to_slice(frame.data, &mut buffer[..frame_size * channels as usize], false).expect("to decode");
+        output.extend_from_slice(&buffer[..len * channels as usize]);
+
+        let range = decoder.get_final_range().expect("get final range");
+        if frame.final_range != 0 && !lost && !lost_prev {
+            assert_eq!(range, frame.final_range, "{name}: range mismatch at packet {idx} ({rate:?}, {channels:?})");
+            verified += 1;
+        }
+        lost_prev = lost;
+    }
+
+    (output, verified)
+}
+
+//Decodes `frames` at every rate and channels combination, requiring output to match any of references at 48kHz
+fn verify(name: &str, frames: &[Frame], references: &[(&[u16], Channels)]) {
+    for channels in CHANNELS {
+        for rate in RATES {
+            let (output, _) = decode(name, frames, rate, channels);
+            let mut qualities = Vec::new();
+            for (reference, reference_channels) in references {
+                if *reference_channels == Channels::Mono && channels == Channels::Stereo {
+                    continue;
+                }
+                let result = compare(reference, *reference_channels, &output, channels, rate).expect("compare");
+                qualities.push(result.quality);
+                if result.is_pass() {
+                    break;
+                }
+            }
+            assert!(qualities.iter().any(|quality| *quality >= 0.0), "{name}: output does not match reference ({rate:?}, {channels:?}): {qualities:?}");
+        }
+    }
+}
+
+#[test]
+fn should_pass_official_test_vectors() {
+    let path = match std::env::var_os("OPUS_TEST_VECTORS") {
+        Some(path) => std::path::PathBuf::from(path),
+        None => {
+            println!("OPUS_TEST_VECTORS is not set, skipping");
+            return;
+        }
+    };
+
+    for idx in 1..=12 {
+        let name = format!("testvector{idx:02}");
+        let bitstream = std::fs::read(path.join(format!("{name}.bit"))).expect("read bitstream");
+        let stereo = read_pcm(&std::fs::read(path.join(format!("{name}.dec"))).expect("read reference"));
+        let alternative = read_pcm(&std::fs::read(path.join(format!("{name}m.dec"))).expect("read alternative reference"));
+
+        let frames = parse_bitstream(&bitstream);
+        verify(&name, &frames, &[(&stereo, Channels::Stereo), (&alternative, Channels::Stereo)]);
+    }
+}
+
+struct Config {
+    channels: Channels,
+    application: Application,
+    bitrate: u32,
+    bandwidth: Bandwidth,
+    duration: FrameDuration,
+    //Frame size at 48kHz
+    frame_size: usize,
+}
+
+fn signal(channels: Channels, len: usize) -> Vec<u16> {
+    let mut result = Vec::with_capacity(len * channels as usize);
+    let mut seed = 7u32;
+    let mut state = 0f32;
+    //Low-passed noise, as natural audio has little energy at high frequencies
+    let mut noise = move || {
+        seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+        state = 0.9 * state + 0.1 * ((seed >> 16) as f32 / 65536.0 - 0.5);
+        state
+    };
+    for idx in 0..len {
+        let time = idx as f32 / 48000.0;
+        //Slow vibrato to exercise both stationary and changing content
+        let pitch = 220.0 + 40.0 * (time * 2.0 * core::f32::consts::PI).sin();
+        let left = (time * pitch * 2.0 * core::f32::consts::PI).sin() * 7000.0 + (time * 2500.0 * 2.0 * core::f32::consts::PI).sin() * 1500.0 + noise() * 20000.0;
+        let right = (time * pitch * 3.0 * core::f32::consts::PI).sin() * 7000.0 + noise() * 20000.0;
+        result.push(left as i16 as u16);
+        if channels == Channels::Stereo {
+            result.push(right as i16 as u16);
+        }
+    }
+    result
+}
+
+//Encodes 400ms of audio as `opus_demo -e` would, optionally dropping packet in the middle
+fn generate(config: &Config, lost: bool) -> Vec<u8> {
+    let mut encoder = Encoder::new(config.channels, SampleRate::Hz48000, config.application).expect("create encoder");
+    encoder.set_bitrate(Bitrate::Value(config.bitrate)).expect("set bitrate");
+    encoder.set_bandwidth(config.bandwidth).expect("set bandwidth");
+    encoder.set_frame_duration(config.duration).expect("set frame duration");
+
+    let input = signal(config.channels, 19200);
+    let lost_packet = input.len() / (config.frame_size * config.channels as usize) / 2;
+    let mut writer = Writer::new();
+    for (idx, chunk) in input.chunks_exact(config.frame_size * config.channels as usize).enumerate() {
+        let mut payload = Vec::with_capacity(1275 * 6);
+        encoder.encode_to_vec(chunk, &mut payload).expect("to encode");
+        let range = encoder.get_final_range().expect("get final range");
+        if lost && idx == lost_packet {
+            writer.add_lost();
+        } else {
+            writer.add_packet(&payload, range).expect("add packet");
+        }
+    }
+    writer.finish()
+}
+
+const CONFIGS: [Config; 6] = [
+    //SILK narrowband
+    Config { channels: Channels::Mono, application: Application::Voip, bitrate: 12000, bandwidth: Bandwidth::Narrow, duration: FrameDuration::Size20, frame_size: 960 },
+    //SILK wideband with multiple frames per packet
+    Config { channels: Channels::Mono, application: Application::Voip, bitrate: 20000, bandwidth: Bandwidth::Wide, duration: FrameDuration::Size60, frame_size: 2880 },
+    //Hybrid
+    Config { channels: Channels::Stereo, application: Application::Voip, bitrate: 32000, bandwidth: Bandwidth::Superwide, duration: FrameDuration::Size20, frame_size: 960 },
+    //CELT
+    Config { channels: Channels::Stereo, application: Application::Audio, bitrate: 96000, bandwidth: Bandwidth::Full, duration: FrameDuration::Size10, frame_size: 480 },
+    //CELT with smallest frames
+    Config { channels: Channels::Stereo, application: Application::LowDelay, bitrate: 128000, bandwidth: Bandwidth::Full, duration: FrameDuration::Size2_5, frame_size: 120 },
+    //CELT with long packets
+    Config { channels: Channels::Mono, application: Application::Audio, bitrate: 48000, bandwidth: Bandwidth::Full, duration: FrameDuration::Size120, frame_size: 5760 },
+];
+
+#[test]
+fn should_pass_generated_test_vectors() {
+    for (idx, config) in CONFIGS.iter().enumerate() {
+        let name = format!("generated{idx:02}");
+        let bitstream = generate(config, false);
+        let frames = parse_bitstream(&bitstream);
+
+        let (stereo, verified) = decode(&name, &frames, SampleRate::Hz48000, Channels::Stereo);
+        assert_eq!(verified, frames.len());
+        let (mono, _) = decode(&name, &frames, SampleRate::Hz48000, Channels::Mono);
+        verify(&name, &frames, &[(&mono, Channels::Mono), (&stereo, Channels::Stereo)]);
+    }
+}
+
+#[test]
+fn should_verify_range_after_packet_loss() {
+    for (idx, config) in CONFIGS.iter().enumerate() {
+        let name = format!("lossy{idx:02}");
+        let bitstream = generate(config, true);
+        let frames = parse_bitstream(&bitstream);
+        assert_eq!(frames.iter().filter(|frame| frame.is_lost()).count(), 1);
+
+        for channels in CHANNELS {
+            for rate in RATES {
+                let (output, verified) = decode(&name, &frames, rate, channels);
+                assert_eq!(output.len(), frames.len() * config.frame_size * rate as usize / 48000 * channels as usize);
+                //Only lost packet and the one right after it are not verified
+                assert_eq!(verified, frames.len() - 2, "{name}: ({rate:?}, {channels:?})");
+            }
+        }
+    }
+}
@@ -0,0 +1,196 @@
+use opusic_c::{header, multistream, ogg, wav, Encoder, Decoder};
+use opusic_c::{frame_bytes_size, SampleRate, Channels, Application};
+
+const SIZE_20MS: usize = frame_bytes_size(SampleRate::Hz48000, Channels::Stereo, 20);
+
+fn sine_wav(rate: SampleRate, channels: Channels, format: wav::SampleFormat, frames: usize) -> Vec<u8> {
+    let mut writer = wav::WavWriter::new(rate, channels, format);
+    let mut input = vec![0f32; frames * channels as usize];
+    for (idx, frame) in input.chunks_mut(channels as usize).enumerate() {
+        let time = idx as f32 / rate as i32 as f32;
+        let value = (time * 440.0 * 2.0 * core::f32::consts::PI).sin() * 0.5;
+        frame.fill(value);
+    }
+    writer.write_float(&input);
+    writer.finish()
+}
+
+#[test]
+fn should_convert_wav_to_ogg_and_back() {
+    let file = sine_wav(SampleRate::Hz48000, Channels::Stereo, wav::SampleFormat::Int16, 48000 * 3);
+    let reader = wav::WavReader::new(&file).expect("parse wav");
+    reader.check(SampleRate::Hz48000, Channels::Stereo).expect("valid wav");
+    assert!(reader.check(SampleRate::Hz16000, Channels::Stereo).is_err());
+    assert!(reader.check(SampleRate::Hz48000, Channels::Mono).is_err());
+    assert_eq!(reader.format(), wav::SampleFormat::Int16);
+    assert_eq!(reader.samples(), 48000 * 3);
+    assert_eq!(reader.channel_mask(), None);
+
+    let mut samples = Vec::new();
+    reader.read_to_vec(&mut samples);
+    assert_eq!(samples.len(), 48000 * 3 * 2);
+
+    let mut encoder = Encoder::new(Channels::Stereo, SampleRate::Hz48000, Application::Audio).expect("Create");
+    let head = header::OpusHead::from_encoder(&mut encoder).expect("create head");
+    let mut tags = header::OpusTags::new();
+    tags.add("TITLE", "sine");
+
+    let mut writer = ogg::Writer::new(&head, &tags, 0x1234);
+    let mut packets = Vec::new();
+    for frame in samples.chunks(SIZE_20MS) {
+        let mut packet = Vec::with_capacity(1275);
+        encoder.encode_to_vec(frame, &mut packet).expect("to encode");
+        writer.add_packet(&packet).expect("add packet");
+        packets.push(packet);
+    }
+    assert_eq!(writer.granule_position(), 48000 * 3);
+    writer.set_end_trim(100);
+    let file = writer.finish();
+
+    let mut reader = ogg::Reader::new(&file).expect("parse ogg");
+    assert_eq!(*reader.head(), head);
+    assert_eq!(reader.tags().get("title"), Some("sine"));
+    assert_eq!(reader.tags().vendor, opusic_c::version());
+    assert_eq!(reader.serial(), 0x1234);
+
+    let mut decoder = Decoder::new(Channels::Stereo, SampleRate::Hz48000).expect("Create");
+    let mut output = wav::WavWriter::new(SampleRate::Hz48000, Channels::Stereo, wav::SampleFormat::Float32);
+    let mut decoded = vec![0f32; SIZE_20MS];
+    let mut count = 0;
+    let mut last_granule = 0;
+    while let Some(packet) = reader.next_packet().expect("valid packet") {
+        assert_eq!(packet.data, packets[count].as_slice());
+        if let Some(granule) = packet.granule_position {
+            assert!(granule > last_granule);
+            last_granule = granule;
+        }
+        let len = decoder.decode_float_to_slice(packet.data, &mut decoded, false).expect("to decode");
+        output.write_float(&decoded[..len * 2]);
+        count += 1;
+
+        if packet.is_last {
+            assert_eq!(packet.granule_position, Some(48000 * 3 - 100));
+        }
+    }
+    assert_eq!(count, packets.len());
+
+    let output = output.finish();
+    let reader = wav::WavReader::new(&output).expect("parse wav");
+    assert_eq!(reader.format(), wav::SampleFormat::Float32);
+    assert_eq!(reader.samples(), 48000 * 3);
+}
+
+#[test]
+fn should_map_extensible_channel_mask() {
+    let mut writer = wav::WavWriter::multichannel(SampleRate::Hz48000, 6, 0x3F, wav::SampleFormat::Float32).expect("create writer");
+    writer.write_float(&[0.0; 6 * 960]);
+    let file = writer.finish();
+
+    let reader = wav::WavReader::new(&file).expect("parse wav");
+    assert_eq!(reader.channels(), 6);
+    assert_eq!(reader.channel_mask(), Some(0x3F));
+    assert_eq!(reader.samples(), 960);
+
+    let config = reader.multistream_config::<6>().expect("map channels");
+    assert_eq!(config.streams(), 4);
+    assert_eq!(config.coupled_streams(), 2);
+    assert_eq!(*config.mapping(), [0, 1, 4, 5, 2, 3]);
+    assert!(reader.multistream_config::<5>().is_err());
+
+    //5.1 with side speakers is the same layout
+    let file = wav::WavWriter::multichannel(SampleRate::Hz48000, 6, 0x60F, wav::SampleFormat::Int16).expect("create writer").finish();
+    let reader = wav::WavReader::new(&file).expect("parse wav");
+    let config = reader.multistream_config::<6>().expect("map channels");
+    assert_eq!(*config.mapping(), [0, 1, 4, 5, 2, 3]);
+
+    //Opus supports at most 255 channels
+    let file = wav::WavWriter::multichannel(SampleRate::Hz48000, 255, 0, wav::SampleFormat::Float32).expect("create writer").finish();
+    assert_eq!(wav::WavReader::new(&file).expect("parse wav").channels(), 255);
+    assert!(wav::WavWriter::multichannel(SampleRate::Hz48000, 256, 0, wav::SampleFormat::Float32).is_err());
+    assert!(wav::WavWriter::multichannel(SampleRate::Hz48000, u16::MAX, 0, wav::SampleFormat::Float32).is_err());
+
+    let vorbis = multistream::Config::<6>::vorbis().expect("vorbis config");
+    let head = header::OpusHead::multistream(1, &vorbis, 312);
+    let mut encoder = multistream::Encoder::new(config, SampleRate::Hz48000, Application::Audio).expect("Create");
+    let mut packet = Vec::with_capacity(1275 * 4);
+    encoder.encode_to_vec(&[0; 6 * 960], &mut packet).expect("to encode");
+
+    let mut writer = ogg::Writer::new(&head, &header::OpusTags::new(), 1);
+    writer.add_packet(&packet).expect("add packet");
+    let file = writer.finish();
+    let mut reader = ogg::Reader::new(&file).expect("parse ogg");
+    assert_eq!(*reader.head(), head);
+    let packet = reader.next_packet().expect("valid").expect("to have packet");
+    assert!(packet.is_last);
+    assert_eq!(packet.granule_position, Some(960));
+}
+
+#[test]
+fn should_reject_invalid_files() {
+    assert!(wav::WavReader::new(&[]).is_err());
+    assert!(wav::WavReader::new(b"RIFF\0\0\0\0WAVE").is_err());
+
+    let file = sine_wav(SampleRate::Hz16000, Channels::Mono, wav::SampleFormat::Float32, 160);
+    assert!(wav::WavReader::new(&file[..30]).is_err());
+    let reader = wav::WavReader::new(&file[..file.len() - 3]).expect("parse truncated wav");
+    assert_eq!(reader.samples(), 159);
+    assert_eq!(reader.opus_sample_rate(), Ok(SampleRate::Hz16000));
+
+    assert!(ogg::Reader::new(&[]).is_err());
+    assert!(ogg::Reader::new(b"OggS").is_err());
+}
+
+#[test]
+fn should_recover_damaged_ogg_stream() {
+    let mut encoder = Encoder::new(Channels::Mono, SampleRate::Hz48000, Application::Audio).expect("Create");
+    let head = header::OpusHead::from_encoder(&mut encoder).expect("create head");
+    let mut writer = ogg::Writer::new(&head, &header::OpusTags::new(), 5);
+    let mut packet = Vec::with_capacity(1275);
+    for _ in 0..200 {
+        packet.clear();
+        encoder.encode_to_vec(&[0; 960], &mut packet).expect("to encode");
+        writer.add_packet(&packet).expect("add packet");
+    }
+    let file = writer.finish();
+
+    let mut pages = Vec::new();
+    let mut pos = 0;
+    while pos < file.len() {
+        let (_, size) = ogg::Page::parse_checked(&file[pos..]).expect("valid page");
+        pages.push(pos..pos + size);
+        pos += size;
+    }
+    //Headers and 4 pages of 1 second each
+    assert_eq!(pages.len(), 6);
+    let page = ogg::Page::parse(&file[pages[3].clone()]).expect("valid page").0;
+    assert_eq!(page.start_granule(), Some(48000));
+
+    let read_all = |data: &[u8]| {
+        let mut reader = ogg::Reader::new(data).expect("parse ogg");
+        let mut packets = 0;
+        let mut lost = Vec::new();
+        while let Some(packet) = reader.next_packet().expect("read packet") {
+            packets += 1;
+            if packet.lost_samples > 0 {
+                lost.push((packets, packet.lost_samples));
+            }
+        }
+        (packets, lost)
+    };
+    assert_eq!(read_all(&file), (200, Vec::new()));
+
+    //Corrupted page is skipped and reported as lost
+    let mut damaged = file.clone();
+    damaged[pages[3].start + 100] ^= 0xff;
+    assert!(ogg::Page::parse_checked(&damaged[pages[3].clone()]).is_err());
+    assert_eq!(read_all(&damaged), (150, vec![(51, 48000)]));
+
+    //Garbage between pages is skipped without loss
+    let mut garbage = file[..pages[3].start].to_vec();
+    garbage.extend_from_slice(b"OggS garbage");
+    garbage.extend_from_slice(&file[pages[3].start..]);
+    assert_eq!(read_all(&garbage), (200, Vec::new()));
+
+    //Truncated page at the end is dropped
+    assert_eq!(read_all(&file[..pages[5].start + 50]), (150, Vec::new()));
+}
@@ -0,0 +1,84 @@
+use opusic_c::{header, webm, Encoder, Decoder};
+use opusic_c::{frame_bytes_size, SampleRate, Channels, Application};
+
+const SIZE_20MS: usize = frame_bytes_size(SampleRate::Hz48000, Channels::Stereo, 20);
+
+fn encode_sine(encoder: &mut Encoder, frames: usize) -> Vec<Vec<u8>> {
+    let mut packets = Vec::with_capacity(frames);
+    let mut input = [0f32; SIZE_20MS];
+    for frame in 0..frames {
+        for (idx, sample) in input.chunks_mut(2).enumerate() {
+            let time = (frame * SIZE_20MS / 2 + idx) as f32 / 48000.0;
+            let value = (time * 440.0 * 2.0 * core::f32::consts::PI).sin() * 0.5;
+            sample[0] = value;
+            sample[1] = value;
+        }
+
+        let mut packet = vec![0; 1275];
+        let len = encoder.encode_float_to_slice(&input, &mut packet).expect("to encode");
+        packet.truncate(len);
+        packets.push(packet);
+    }
+    packets
+}
+
+#[test]
+fn should_mux_and_demux_webm() {
+    let mut encoder = Encoder::new(Channels::Stereo, SampleRate::Hz48000, Application::Audio).expect("Create");
+    let head = header::OpusHead::from_encoder(&mut encoder).expect("create head");
+    assert_eq!(head.pre_skip, 312);
+    assert_eq!(head.input_sample_rate, 48000);
+
+    let packets = encode_sine(&mut encoder, 150);
+    let mut muxer = webm::Muxer::new(&head);
+    for packet in packets.iter() {
+        muxer.add_packet(packet).expect("add packet");
+    }
+    assert_eq!(muxer.samples(), 150 * 960);
+    let file = muxer.finish();
+
+    let mut demuxer = webm::Demuxer::new(&file).expect("parse webm");
+    assert_eq!(*demuxer.head(), head);
+    assert_eq!(demuxer.codec_delay(), 6_500_000);
+    assert_eq!(demuxer.seek_pre_roll(), webm::OPUS_SEEK_PRE_ROLL);
+    assert_eq!(demuxer.duration(), Some(3_000_000_000));
+
+    let mut decoder = Decoder::new(Channels::Stereo, SampleRate::Hz48000).expect("Create");
+    let mut decoded = [0u16; SIZE_20MS];
+    let mut count = 0;
+    while let Some(packet) = demuxer.next_packet() {
+        let packet = packet.expect("valid packet");
+        assert_eq!(packet.data, packets[count].as_slice());
+        assert_eq!(packet.timestamp, count as u64 * 20_000_000);
+        let len = decoder.decode_to_slice(packet.data, &mut decoded, false).expect("to decode");
+        assert_eq!(len, SIZE_20MS / 2);
+        count += 1;
+    }
+    assert_eq!(count, packets.len());
+
+    let position = demuxer.seek(2_050_000_000);
+    assert_eq!(position, 1_000_000_000);
+    let packet = demuxer.next().expect("to have packet").expect("valid packet");
+    assert_eq!(packet.timestamp, position);
+    assert_eq!(packet.data, packets[50].as_slice());
+
+    let position = demuxer.seek(0);
+    assert_eq!(position, 0);
+    assert_eq!(demuxer.count(), packets.len());
+}
+
+#[test]
+fn should_reject_invalid_webm() {
+    assert!(webm::Demuxer::new(&[]).is_err());
+    assert!(webm::Demuxer::new(&[0x1A, 0x45, 0xDF, 0xA3, 0x80]).is_err());
+
+    let head = header::OpusHead::new(Channels::Mono, 312);
+    let file = webm::Muxer::new(&head).finish();
+    let demuxer = webm::Demuxer::new(&file).expect("parse empty webm");
+    assert_eq!(*demuxer.head(), head);
+    assert_eq!(demuxer.count(), 0);
+
+    let encoded = head.to_vec();
+    assert_eq!(header::OpusHead::parse(&encoded).expect("parse head"), head);
+    assert!(header::OpusHead::parse(&encoded[..encoded.len() - 1]).is_err());
+}